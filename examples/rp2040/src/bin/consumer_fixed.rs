@@ -90,25 +90,33 @@ fn main() -> ! {
     led_pin.set_low().ok();
 
     let mut input_count_down = timer.count_down();
-    input_count_down.start(50.millis());
+    input_count_down.start(10.millis());
 
-    let mut last = get_report(&mut input_pins);
+    let mut tick_count_down = timer.count_down();
+    tick_count_down.start(1.millis());
 
     loop {
         //Poll every 10ms
         if input_count_down.wait().is_ok() {
-            let report = get_report(&mut input_pins);
-            if report != last {
-                match consumer.device().write_report(&report) {
-                    Err(UsbError::WouldBlock) => {}
-                    Ok(_) => {
-                        last = report;
-                    }
-                    Err(e) => {
-                        core::panic!("Failed to write consumer report: {:?}", e)
-                    }
+            match consumer.device().write_report(&get_report(&mut input_pins)) {
+                Err(UsbHidError::WouldBlock) => {}
+                Err(UsbHidError::Duplicate) => {}
+                Ok(_) => {}
+                Err(e) => {
+                    core::panic!("Failed to write consumer report: {:?}", e)
                 }
-            }
+            };
+        }
+
+        //Tick once per ms
+        if tick_count_down.wait().is_ok() {
+            match consumer.tick() {
+                Err(UsbHidError::WouldBlock) => {}
+                Ok(_) => {}
+                Err(e) => {
+                    core::panic!("Failed to process consumer tick: {:?}", e)
+                }
+            };
         }
 
         usb_dev.poll(&mut [&mut consumer]);
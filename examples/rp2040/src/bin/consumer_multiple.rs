@@ -16,7 +16,7 @@ use panic_probe as _;
 #[allow(clippy::wildcard_imports)]
 use usb_device::class_prelude::*;
 use usb_device::prelude::*;
-use usbd_human_interface_device::device::consumer::MultipleConsumerReport;
+use usbd_human_interface_device::device::consumer::{ConsumerCode, MultipleConsumerReport};
 use usbd_human_interface_device::prelude::*;
 
 use usbd_human_interface_device::page::Consumer;
@@ -91,29 +91,37 @@ fn main() -> ! {
 
     led_pin.set_low().ok();
 
-    let mut last = get_report(&mut input_pins);
-
     let mut input_count_down = timer.count_down();
-    input_count_down.start(50.millis());
+    input_count_down.start(10.millis());
+
+    let mut tick_count_down = timer.count_down();
+    tick_count_down.start(1.millis());
 
     loop {
-        //Poll the every 10ms
+        //Poll every 10ms
         if input_count_down.wait().is_ok() {
-            let report = get_report(&mut input_pins);
-            if report != last {
-                match consumer.device().write_report(&report) {
-                    Err(UsbError::WouldBlock) => {}
-                    Ok(_) => {
-                        last = report;
-                    }
-                    Err(e) => {
-                        core::panic!("Failed to write consumer report: {:?}", e)
-                    }
+            match consumer.device().write_report(&get_report(&mut input_pins)) {
+                Err(UsbHidError::WouldBlock) => {}
+                Err(UsbHidError::Duplicate) => {}
+                Ok(_) => {}
+                Err(e) => {
+                    core::panic!("Failed to write consumer report: {:?}", e)
+                }
+            };
+        }
+
+        //Tick once per ms
+        if tick_count_down.wait().is_ok() {
+            match consumer.tick() {
+                Err(UsbHidError::WouldBlock) => {}
+                Ok(_) => {}
+                Err(e) => {
+                    core::panic!("Failed to process consumer tick: {:?}", e)
                 }
-            }
+            };
         }
 
-        if usb_dev.poll(&mut [&mut consumer]) {}
+        usb_dev.poll(&mut [&mut consumer]);
     }
 }
 
@@ -132,13 +140,13 @@ fn get_report(pins: &mut [Pin<DynPinId, FunctionSioInput, PullUp>; 9]) -> Multip
     ];
 
     let mut report = MultipleConsumerReport {
-        codes: [Consumer::Unassigned; 4],
+        codes: [ConsumerCode::UNASSIGNED; 4],
     };
 
     let mut it = pins.iter().filter(|&&c| c != Consumer::Unassigned);
     for c in report.codes.iter_mut() {
         if let Some(&code) = it.next() {
-            *c = code;
+            *c = code.into();
         } else {
             break;
         }
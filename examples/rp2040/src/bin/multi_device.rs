@@ -21,7 +21,7 @@ use static_cell::StaticCell;
 use usb_device::class_prelude::*;
 use usb_device::prelude::*;
 
-use usbd_human_interface_device::device::consumer::{ConsumerControl, MultipleConsumerReport};
+use usbd_human_interface_device::device::consumer::{ConsumerCode, ConsumerControl, MultipleConsumerReport};
 use usbd_human_interface_device::device::keyboard::NKROBootKeyboard;
 use usbd_human_interface_device::device::mouse::{WheelMouse, WheelMouseReport};
 use usbd_human_interface_device::page::Consumer;
@@ -123,7 +123,6 @@ fn main() -> ! {
 
     let mut consumer_poll = timer.count_down();
     consumer_poll.start(CONSUMER_POLL);
-    let mut last_consumer_report = MultipleConsumerReport::default();
 
     let mut write_pending_poll = timer.count_down();
     write_pending_poll.start(WRITE_PENDING_POLL);
@@ -168,25 +167,22 @@ fn main() -> ! {
             let codes = get_consumer_codes(&mut consumer_pins);
             let consumer_report = MultipleConsumerReport {
                 codes: [
-                    codes[0],
-                    codes[1],
-                    Consumer::Unassigned,
-                    Consumer::Unassigned,
+                    codes[0].into(),
+                    codes[1].into(),
+                    ConsumerCode::UNASSIGNED,
+                    ConsumerCode::UNASSIGNED,
                 ],
             };
 
-            if last_consumer_report != consumer_report {
-                let consumer = multi_device.device::<ConsumerControl<'_, _>, _>();
-                match consumer.write_report(&consumer_report) {
-                    Err(UsbError::WouldBlock) => {}
-                    Ok(_) => {
-                        last_consumer_report = consumer_report;
-                    }
-                    Err(e) => {
-                        core::panic!("Failed to write consumer report: {:?}", e)
-                    }
-                };
-            }
+            let consumer = multi_device.device::<ConsumerControl<'_, _>, _>();
+            match consumer.write_report(&consumer_report) {
+                Err(UsbHidError::WouldBlock) => {}
+                Err(UsbHidError::Duplicate) => {}
+                Ok(_) => {}
+                Err(e) => {
+                    core::panic!("Failed to write consumer report: {:?}", e)
+                }
+            };
         }
 
         //Tick once per ms
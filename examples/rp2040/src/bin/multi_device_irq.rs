@@ -25,7 +25,7 @@ use static_cell::StaticCell;
 #[allow(clippy::wildcard_imports)]
 use usb_device::class_prelude::*;
 use usb_device::prelude::*;
-use usbd_human_interface_device::device::consumer::{ConsumerControl, MultipleConsumerReport};
+use usbd_human_interface_device::device::consumer::{ConsumerCode, ConsumerControl, MultipleConsumerReport};
 use usbd_human_interface_device::device::keyboard::NKROBootKeyboard;
 use usbd_human_interface_device::device::mouse::{WheelMouse, WheelMouseReport};
 use usbd_human_interface_device::page::Consumer;
@@ -146,7 +146,6 @@ fn main() -> ! {
 
     let mut consumer_input_timer = timer.count_down();
     consumer_input_timer.start(CONSUMER_POLL);
-    let mut last_consumer_report = MultipleConsumerReport::default();
 
     let mut tick_timer = timer.count_down();
     tick_timer.start(1.millis());
@@ -202,25 +201,22 @@ fn main() -> ! {
                 let codes = get_consumer_codes(&mut consumer_pins);
                 let consumer_report = MultipleConsumerReport {
                     codes: [
-                        codes[0],
-                        codes[1],
-                        Consumer::Unassigned,
-                        Consumer::Unassigned,
+                        codes[0].into(),
+                        codes[1].into(),
+                        ConsumerCode::UNASSIGNED,
+                        ConsumerCode::UNASSIGNED,
                     ],
                 };
 
-                if last_consumer_report != consumer_report {
-                    let consumer = multi_device.device::<ConsumerControl<'_, _>, _>();
-                    match consumer.write_report(&consumer_report) {
-                        Err(UsbError::WouldBlock) => {}
-                        Ok(_) => {
-                            last_consumer_report = consumer_report;
-                        }
-                        Err(e) => {
-                            core::panic!("Failed to write consumer report: {:?}", e)
-                        }
-                    };
-                }
+                let consumer = multi_device.device::<ConsumerControl<'_, _>, _>();
+                match consumer.write_report(&consumer_report) {
+                    Err(UsbHidError::WouldBlock) => {}
+                    Err(UsbHidError::Duplicate) => {}
+                    Ok(_) => {}
+                    Err(e) => {
+                        core::panic!("Failed to write consumer report: {:?}", e)
+                    }
+                };
             });
         }
 
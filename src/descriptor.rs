@@ -49,6 +49,18 @@ pub enum HidProtocol {
     Report = 0x01,
 }
 
+/// Report type carried in the high byte of `wValue` for `GET_REPORT` and `SET_REPORT` requests
+///
+/// See section 7.2.1 of [Device Class Definition for Human Interface Devices (Hid) Version 1.11](<https://www.usb.org/sites/default/files/hid1_11.pdf>)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum ReportType {
+    Input = 0x01,
+    Output = 0x02,
+    Feature = 0x03,
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
@@ -60,3 +72,21 @@ pub(crate) enum HidRequest {
     SetIdle = 0x0A,
     SetProtocol = 0x0B,
 }
+
+/// Copy `data` into `bytes` at `len`, returning the updated buffer and length
+///
+/// Used by the report descriptors that are built at compile time, the buffer is passed by value
+/// as const functions can't take mutable references before Rust 1.83.
+pub(crate) const fn append<const N: usize>(
+    mut bytes: [u8; N],
+    mut len: usize,
+    data: &[u8],
+) -> ([u8; N], usize) {
+    let mut i = 0;
+    while i < data.len() {
+        bytes[len] = data[i];
+        len += 1;
+        i += 1;
+    }
+    (bytes, len)
+}
@@ -0,0 +1,620 @@
+//!HID absolute pointers with configurable ranges, e.g. for KVMs and automation
+//!
+//! Pixel coordinates on a [`VirtualDesktop`] of one or more monitors are mapped into report
+//! units with [`AbsolutePointer::map`].
+use crate::descriptor::append;
+use crate::usb_class::prelude::*;
+use fugit::ExtU32;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+
+/// Largest logical or physical value supported by [`AxisRange`]
+pub const MAX_AXIS_VALUE: u16 = 0x7FFF;
+
+const MAX_DESCRIPTOR_LEN: usize = 96;
+
+/// Logical and physical range of an axis
+///
+/// Values are limited to [`MAX_AXIS_VALUE`] and a minimum greater than the maximum is swapped
+/// with it. A physical range of zero to zero, the default, tells the host the physical range is
+/// the same as the logical range.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AxisRange {
+    logical_min: u16,
+    logical_max: u16,
+    physical_min: u16,
+    physical_max: u16,
+}
+
+impl AxisRange {
+    #[must_use]
+    pub const fn new(logical_min: u16, logical_max: u16) -> Self {
+        let (logical_min, logical_max) = ordered(logical_min, logical_max);
+        Self {
+            logical_min,
+            logical_max,
+            physical_min: 0,
+            physical_max: 0,
+        }
+    }
+
+    /// Copy of the range with a physical range, used by hosts for the aspect ratio of the pointer
+    #[must_use]
+    pub const fn physical(self, physical_min: u16, physical_max: u16) -> Self {
+        let (physical_min, physical_max) = ordered(physical_min, physical_max);
+        Self {
+            physical_min,
+            physical_max,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn logical_min(&self) -> u16 {
+        self.logical_min
+    }
+
+    #[must_use]
+    pub const fn logical_max(&self) -> u16 {
+        self.logical_max
+    }
+
+    #[must_use]
+    pub const fn physical_min(&self) -> u16 {
+        self.physical_min
+    }
+
+    #[must_use]
+    pub const fn physical_max(&self) -> u16 {
+        self.physical_max
+    }
+
+    /// Map `offset` in `0..extent` onto the logical range, rounding to the nearest unit
+    #[must_use]
+    pub fn map(&self, offset: u32, extent: u32) -> u16 {
+        let span = u64::from(self.logical_max.saturating_sub(self.logical_min));
+        let last = u64::from(extent.saturating_sub(1));
+        if last == 0 {
+            return self.logical_min;
+        }
+        let offset = u64::from(offset).min(last);
+        let value = (offset * span + last / 2) / last;
+        self.logical_min + unwrap!(u16::try_from(value).ok())
+    }
+}
+
+impl Default for AxisRange {
+    fn default() -> Self {
+        Self::new(0, MAX_AXIS_VALUE)
+    }
+}
+
+const fn limit(value: u16) -> u16 {
+    if value > MAX_AXIS_VALUE {
+        MAX_AXIS_VALUE
+    } else {
+        value
+    }
+}
+
+/// Limited `(min, max)`, swapped if `min` is greater than `max`
+const fn ordered(min: u16, max: u16) -> (u16, u16) {
+    let (min, max) = (limit(min), limit(max));
+    if min > max {
+        (max, min)
+    } else {
+        (min, max)
+    }
+}
+
+/// Top level collection reported by an [`AbsolutePointer`]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum AbsolutePointerKind {
+    /// Generic Desktop mouse with eight buttons and a wheel
+    #[default]
+    Mouse,
+    /// Digitizer pen, button 1 is the tip switch and button 2 the barrel switch. The pen is
+    /// always reported in range and the wheel is ignored.
+    ///
+    /// macOS handles absolute digitizers better than absolute Generic Desktop pointers.
+    Digitizer,
+}
+
+/// Absolute pointer report descriptor with configurable axis ranges
+///
+/// For [`AbsolutePointerKind::Mouse`]:
+/// * Byte 0 - Buttons 1-8
+/// * Bytes 1-2 - X
+/// * Bytes 3-4 - Y
+/// * Byte 5 - Wheel, -127 to 127
+///
+/// For [`AbsolutePointerKind::Digitizer`]:
+/// * Byte 0, Bit 0 - Tip Switch
+/// * Byte 0, Bit 1 - Barrel Switch
+/// * Byte 0, Bit 2 - In Range
+/// * Bytes 1-2 - X
+/// * Bytes 3-4 - Y
+///
+/// Built at compile time, for example:
+/// ```
+/// # use usbd_human_interface_device::device::absolute::*;
+/// static DESCRIPTOR: AbsolutePointerDescriptor = AbsolutePointerDescriptor::new(
+///     AbsolutePointerKind::Digitizer,
+///     AxisRange::new(0, 3839).physical(0, 16000),
+///     AxisRange::new(0, 2159).physical(0, 9000),
+/// );
+/// ```
+pub struct AbsolutePointerDescriptor {
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+    kind: AbsolutePointerKind,
+    x: AxisRange,
+    y: AxisRange,
+}
+
+impl AbsolutePointerDescriptor {
+    #[must_use]
+    pub const fn new(kind: AbsolutePointerKind, x: AxisRange, y: AxisRange) -> Self {
+        let mut bytes = [0u8; MAX_DESCRIPTOR_LEN];
+        let mut len = 0;
+
+        #[rustfmt::skip]
+        let header: &[u8] = match kind {
+            AbsolutePointerKind::Mouse => &[
+                0x05, 0x01, // Usage Page (Generic Desktop),
+                0x09, 0x02, // Usage (Mouse),
+                0xA1, 0x01, // Collection (Application),
+                0x09, 0x01, //   Usage (Pointer),
+                0xA1, 0x00, //   Collection (Physical),
+                0x05, 0x09, //     Usage Page (Buttons),
+                0x19, 0x01, //     Usage Minimum (1),
+                0x29, 0x08, //     Usage Maximum (8),
+                0x15, 0x00, //     Logical Minimum (0),
+                0x25, 0x01, //     Logical Maximum (1),
+                0x95, 0x08, //     Report Count (8),
+                0x75, 0x01, //     Report Size (1),
+                0x81, 0x02, //     Input (Data, Variable, Absolute),
+            ],
+            AbsolutePointerKind::Digitizer => &[
+                0x05, 0x0D, // Usage Page (Digitizer),
+                0x09, 0x02, // Usage (Pen),
+                0xA1, 0x01, // Collection (Application),
+                0x09, 0x20, //   Usage (Stylus),
+                0xA1, 0x00, //   Collection (Physical),
+                0x09, 0x42, //     Usage (Tip Switch),
+                0x09, 0x44, //     Usage (Barrel Switch),
+                0x09, 0x32, //     Usage (In Range),
+                0x15, 0x00, //     Logical Minimum (0),
+                0x25, 0x01, //     Logical Maximum (1),
+                0x95, 0x03, //     Report Count (3),
+                0x75, 0x01, //     Report Size (1),
+                0x81, 0x02, //     Input (Data, Variable, Absolute),
+                0x95, 0x05, //     Report Count (5),
+                0x81, 0x03, //     Input (Constant, Variable, Absolute),
+            ],
+        };
+        (bytes, len) = append(bytes, len, header);
+
+        (bytes, len) = append(bytes, len, &[0x05, 0x01]); // Usage Page (Generic Desktop),
+        (bytes, len) = append_axis(bytes, len, 0x30, x); // Usage (X),
+        (bytes, len) = append_axis(bytes, len, 0x31, y); // Usage (Y),
+
+        if matches!(kind, AbsolutePointerKind::Mouse) {
+            #[rustfmt::skip]
+            let wheel = [
+                0x09, 0x38, //     Usage (Wheel),
+                0x15, 0x81, //     Logical Minimum (-127),
+                0x25, 0x7F, //     Logical Maximum (127),
+                0x35, 0x00, //     Physical Minimum (0),
+                0x45, 0x00, //     Physical Maximum (0),
+                0x75, 0x08, //     Report Size (8),
+                0x81, 0x06, //     Input (Data, Variable, Relative),
+            ];
+            (bytes, len) = append(bytes, len, &wheel);
+        }
+
+        (bytes, len) = append(bytes, len, &[0xC0, 0xC0]); // End Collection, End Collection
+
+        Self {
+            bytes,
+            len,
+            kind,
+            x,
+            y,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+}
+
+const fn append_axis(
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+    usage: u8,
+    range: AxisRange,
+) -> ([u8; MAX_DESCRIPTOR_LEN], usize) {
+    let [lmin_lo, lmin_hi] = range.logical_min.to_le_bytes();
+    let [lmax_lo, lmax_hi] = range.logical_max.to_le_bytes();
+    let [pmin_lo, pmin_hi] = range.physical_min.to_le_bytes();
+    let [pmax_lo, pmax_hi] = range.physical_max.to_le_bytes();
+    #[rustfmt::skip]
+    let axis = [
+        0x09, usage,                // Usage,
+        0x16, lmin_lo, lmin_hi,     // Logical Minimum,
+        0x26, lmax_lo, lmax_hi,     // Logical Maximum,
+        0x36, pmin_lo, pmin_hi,     // Physical Minimum,
+        0x46, pmax_lo, pmax_hi,     // Physical Maximum,
+        0x75, 0x10,                 // Report Size (16),
+        0x95, 0x01,                 // Report Count (1),
+        0x81, 0x02,                 // Input (Data, Variable, Absolute),
+    ];
+    append(bytes, len, &axis)
+}
+
+/// Monitor on a [`VirtualDesktop`], position and size in pixels
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Monitor {
+    #[must_use]
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let dx = i64::from(x) - i64::from(self.x);
+        let dy = i64::from(y) - i64::from(self.y);
+        (0..i64::from(self.width)).contains(&dx) && (0..i64::from(self.height)).contains(&dy)
+    }
+}
+
+/// Host desktop spanning one or more monitors
+///
+/// Absolute pointers cover the bounding box of all the monitors, with the origin at the top left
+/// of the bounding box.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VirtualDesktop<'a> {
+    monitors: &'a [Monitor],
+}
+
+impl<'a> VirtualDesktop<'a> {
+    #[must_use]
+    pub const fn new(monitors: &'a [Monitor]) -> Self {
+        Self { monitors }
+    }
+
+    #[must_use]
+    pub fn monitors(&self) -> &'a [Monitor] {
+        self.monitors
+    }
+
+    /// Bounding box of all the monitors, `None` if there are none
+    #[must_use]
+    pub fn bounds(&self) -> Option<Monitor> {
+        let right = |m: &Monitor| i64::from(m.x) + i64::from(m.width);
+        let bottom = |m: &Monitor| i64::from(m.y) + i64::from(m.height);
+
+        let x = self.monitors.iter().map(|m| m.x).min()?;
+        let y = self.monitors.iter().map(|m| m.y).min()?;
+        let width = self.monitors.iter().map(right).max()? - i64::from(x);
+        let height = self.monitors.iter().map(bottom).max()? - i64::from(y);
+        Some(Monitor {
+            x,
+            y,
+            width: u32::try_from(width).ok()?,
+            height: u32::try_from(height).ok()?,
+        })
+    }
+
+    /// Map a pixel on the desktop to report units, `None` if the pixel is not on a monitor
+    #[must_use]
+    pub fn map(
+        &self,
+        x: i32,
+        y: i32,
+        x_range: AxisRange,
+        y_range: AxisRange,
+    ) -> Option<(u16, u16)> {
+        if !self.monitors.iter().any(|m| m.contains(x, y)) {
+            return None;
+        }
+        let bounds = self.bounds()?;
+        let dx = u32::try_from(i64::from(x) - i64::from(bounds.x)).ok()?;
+        let dy = u32::try_from(i64::from(y) - i64::from(bounds.y)).ok()?;
+        Some((
+            x_range.map(dx, bounds.width),
+            y_range.map(dy, bounds.height),
+        ))
+    }
+
+    /// Map a pixel relative to the top left of `monitor` to report units, `None` if the monitor
+    /// does not exist or the pixel is outside of it
+    #[must_use]
+    pub fn map_on_monitor(
+        &self,
+        monitor: usize,
+        x: u32,
+        y: u32,
+        x_range: AxisRange,
+        y_range: AxisRange,
+    ) -> Option<(u16, u16)> {
+        let m = self.monitors.get(monitor)?;
+        if x >= m.width || y >= m.height {
+            return None;
+        }
+        let x = i32::try_from(i64::from(m.x) + i64::from(x)).ok()?;
+        let y = i32::try_from(i64::from(m.y) + i64::from(y)).ok()?;
+        self.map(x, y, x_range, y_range)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct AbsolutePointerReport {
+    pub buttons: u8,
+    pub x: u16,
+    pub y: u16,
+    pub wheel: i8,
+}
+
+/// Interface implementing an absolute pointer described by an [`AbsolutePointerDescriptor`]
+pub struct AbsolutePointer<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    kind: AbsolutePointerKind,
+    x: AxisRange,
+    y: AxisRange,
+}
+
+impl<B: UsbBus> AbsolutePointer<'_, B> {
+    /// Write a report, coordinates are limited to the logical ranges of the descriptor
+    pub fn write_report(&mut self, report: &AbsolutePointerReport) -> Result<(), UsbHidError> {
+        let [x0, x1] = report
+            .x
+            .clamp(self.x.logical_min, self.x.logical_max)
+            .to_le_bytes();
+        let [y0, y1] = report
+            .y
+            .clamp(self.y.logical_min, self.y.logical_max)
+            .to_le_bytes();
+
+        let result = match self.kind {
+            AbsolutePointerKind::Mouse => {
+                let [wheel] = report.wheel.max(-127).to_le_bytes();
+                self.interface
+                    .write_report(&[report.buttons, x0, x1, y0, y1, wheel])
+            }
+            AbsolutePointerKind::Digitizer => {
+                // In Range is always set
+                let flags = (report.buttons & 0x03) | 0x04;
+                self.interface.write_report(&[flags, x0, x1, y0, y1])
+            }
+        };
+        result.map(|_| ()).map_err(UsbHidError::from)
+    }
+
+    /// Map a pixel on `desktop` to report units, `None` if the pixel is not on a monitor
+    #[must_use]
+    pub fn map(&self, desktop: &VirtualDesktop, x: i32, y: i32) -> Option<(u16, u16)> {
+        desktop.map(x, y, self.x, self.y)
+    }
+
+    /// Map a pixel relative to the top left of a monitor on `desktop` to report units
+    #[must_use]
+    pub fn map_on_monitor(
+        &self,
+        desktop: &VirtualDesktop,
+        monitor: usize,
+        x: u32,
+        y: u32,
+    ) -> Option<(u16, u16)> {
+        desktop.map_on_monitor(monitor, x, y, self.x, self.y)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for AbsolutePointer<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+}
+
+pub struct AbsolutePointerConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    kind: AbsolutePointerKind,
+    x: AxisRange,
+    y: AxisRange,
+}
+
+impl<'a> AbsolutePointerConfig<'a> {
+    /// Create a configuration using `descriptor`
+    #[must_use]
+    pub fn new(descriptor: &'a AbsolutePointerDescriptor) -> Self {
+        let description = match descriptor.kind {
+            AbsolutePointerKind::Mouse => "Absolute Pointer",
+            AbsolutePointerKind::Digitizer => "Absolute Digitizer",
+        };
+        Self::with_interface(
+            unwrap!(unwrap!(InterfaceBuilder::new(descriptor.as_bytes()))
+                .description(description)
+                .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+            descriptor,
+        )
+    }
+
+    /// Create a configuration from an interface built with `descriptor`
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+        descriptor: &AbsolutePointerDescriptor,
+    ) -> Self {
+        Self {
+            interface,
+            kind: descriptor.kind,
+            x: descriptor.x,
+            y: descriptor.y,
+        }
+    }
+}
+
+impl Default for AbsolutePointerConfig<'_> {
+    fn default() -> Self {
+        static DESCRIPTOR: AbsolutePointerDescriptor = AbsolutePointerDescriptor::new(
+            AbsolutePointerKind::Mouse,
+            AxisRange::new(0, MAX_AXIS_VALUE),
+            AxisRange::new(0, MAX_AXIS_VALUE),
+        );
+        Self::new(&DESCRIPTOR)
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for AbsolutePointerConfig<'a> {
+    type Allocated = AbsolutePointer<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            kind: self.kind,
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn descriptor_axis_ranges() {
+        let descriptor = AbsolutePointerDescriptor::new(
+            AbsolutePointerKind::Digitizer,
+            AxisRange::new(0, 1919).physical(0, 5000),
+            AxisRange::new(0, 0xFFFF),
+        );
+        let bytes = descriptor.as_bytes();
+        assert_eq!(bytes[..4], [0x05, 0x0D, 0x09, 0x02]);
+        #[rustfmt::skip]
+        let axes: &[u8] = &[
+            0x05, 0x01,
+            0x09, 0x30, 0x16, 0x00, 0x00, 0x26, 0x7F, 0x07,
+            0x36, 0x00, 0x00, 0x46, 0x88, 0x13,
+            0x75, 0x10, 0x95, 0x01, 0x81, 0x02,
+            0x09, 0x31, 0x16, 0x00, 0x00, 0x26, 0xFF, 0x7F,
+            0x36, 0x00, 0x00, 0x46, 0x00, 0x00,
+            0x75, 0x10, 0x95, 0x01, 0x81, 0x02,
+            0xC0, 0xC0,
+        ];
+        assert!(bytes.ends_with(axes));
+
+        let mouse = AbsolutePointerDescriptor::new(
+            AbsolutePointerKind::Mouse,
+            AxisRange::default(),
+            AxisRange::default(),
+        );
+        assert!(mouse.as_bytes().len() <= MAX_DESCRIPTOR_LEN);
+        assert_eq!(mouse.as_bytes()[..4], [0x05, 0x01, 0x09, 0x02]);
+    }
+
+    #[test]
+    fn axis_range_orders_bounds() {
+        let range = AxisRange::new(1000, 10).physical(0xFFFF, 5);
+        assert_eq!(range.logical_min(), 10);
+        assert_eq!(range.logical_max(), 1000);
+        assert_eq!(range.physical_min(), 5);
+        assert_eq!(range.physical_max(), MAX_AXIS_VALUE);
+        assert_eq!(range.map(0, 100), 10);
+        assert_eq!(range.map(99, 100), 1000);
+    }
+
+    #[test]
+    fn desktop_maps_monitors_with_offsets() {
+        // 1920x1080 primary with a 1280x1024 monitor to the left, 100px lower
+        let monitors = [
+            Monitor::new(0, 0, 1920, 1080),
+            Monitor::new(-1280, 100, 1280, 1024),
+        ];
+        let desktop = VirtualDesktop::new(&monitors);
+        assert_eq!(desktop.bounds(), Some(Monitor::new(-1280, 0, 3200, 1124)));
+
+        let range = AxisRange::new(0, 3199);
+        let y_range = AxisRange::new(0, 1123);
+        assert_eq!(desktop.map(-1280, 0, range, y_range), None);
+        assert_eq!(desktop.map(0, 0, range, y_range), Some((1280, 0)));
+        assert_eq!(
+            desktop.map_on_monitor(1, 1279, 1023, range, y_range),
+            Some((1279, 1123))
+        );
+        assert_eq!(desktop.map_on_monitor(0, 1920, 0, range, y_range), None);
+
+        let scaled = AxisRange::new(0, MAX_AXIS_VALUE);
+        assert_eq!(
+            desktop.map(1919, 1079, scaled, scaled),
+            Some((MAX_AXIS_VALUE, 31483))
+        );
+    }
+
+    #[test]
+    fn absolute_digitizer_maps_desktop_pixels() {
+        static DESCRIPTOR: AbsolutePointerDescriptor = AbsolutePointerDescriptor::new(
+            AbsolutePointerKind::Digitizer,
+            AxisRange::new(0, 1000),
+            AxisRange::new(0, 1000),
+        );
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(AbsolutePointerConfig::new(&DESCRIPTOR))
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        let monitors = [
+            Monitor::new(0, 0, 1001, 501),
+            Monitor::new(0, 501, 1001, 500),
+        ];
+        let desktop = VirtualDesktop::new(&monitors);
+        let (x, y) = hid.device().map_on_monitor(&desktop, 1, 500, 499).unwrap();
+
+        hid.device()
+            .write_report(&AbsolutePointerReport {
+                buttons: 0x01,
+                x,
+                y,
+                wheel: 0,
+            })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x05, 0xF4, 0x01, 0xE8, 0x03]);
+    }
+}
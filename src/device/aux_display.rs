@@ -0,0 +1,460 @@
+//!HID auxiliary character display, e.g. a small status LCD the host writes text to
+//!
+//! Implements an alphanumeric display on the Auxiliary Display page (0x14). The host reads the
+//! display size from the Display Attributes feature report and sends text, cursor, display
+//! control and font output reports, which are returned by [`AuxDisplay::read_report`] as
+//! [`AuxDisplayEvent`]s.
+//!
+//! See [HID Usage Tables FOR Universal Serial Bus (USB) Version 1.5](<https://usb.org/sites/default/files/hut1_5.pdf>), Auxiliary Display Page
+use fugit::ExtU32;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use packed_struct::prelude::*;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+use crate::usb_class::prelude::*;
+use crate::usb_class::{BuilderResult, UsbHidBuilderError};
+
+/// Report ID of the Display Attributes feature report
+pub const DISPLAY_ATTRIBUTES_REPORT_ID: u8 = 0x01;
+/// Report ID of the Display Status input report
+pub const DISPLAY_STATUS_REPORT_ID: u8 = 0x02;
+/// Report ID of the Display Control output report
+pub const DISPLAY_CONTROL_REPORT_ID: u8 = 0x03;
+/// Report ID of the Character output report
+pub const CHARACTER_REPORT_ID: u8 = 0x04;
+/// Report ID of the Cursor Position output report
+pub const CURSOR_POSITION_REPORT_ID: u8 = 0x05;
+/// Report ID of the Font output report
+pub const FONT_REPORT_ID: u8 = 0x06;
+/// Characters of display data in each Character Report
+pub const DISPLAY_DATA_SIZE: usize = 16;
+/// Bytes of font data in each Font Report, one byte per row of a character
+pub const FONT_DATA_SIZE: usize = 8;
+
+///Auxiliary display report descriptor
+///
+/// * Report 1 - Display Attributes feature report, rows, columns, character width and height
+///   and the ASCII Character Set flag
+/// * Report 2 - Display Status input report, [`DisplayStatus`]
+/// * Report 3 - Display Control output report, [`DisplayControl`]
+/// * Report 4 - Character output report, 16 bytes of display data
+/// * Report 5 - Cursor Position output report, row and column
+/// * Report 6 - Font output report, character code and 8 bytes of font data
+#[rustfmt::skip]
+pub const AUX_DISPLAY_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x14,                         // Usage Page (Auxiliary Display),
+    0x09, 0x01,                         // Usage (Alphanumeric Display),
+    0xA1, 0x01,                         // Collection (Application),
+    0x15, 0x00,                         //   Logical Minimum (0),
+    0x09, 0x20,                         //   Usage (Display Attributes Report),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, DISPLAY_ATTRIBUTES_REPORT_ID, //     Report ID (1),
+    0x09, 0x35,                         //     Usage (Rows),
+    0x09, 0x36,                         //     Usage (Columns),
+    0x09, 0x3D,                         //     Usage (Character Width),
+    0x09, 0x3E,                         //     Usage (Character Height),
+    0x26, 0xFF, 0x00,                   //     Logical Maximum (255),
+    0x75, 0x08,                         //     Report Size (8),
+    0x95, 0x04,                         //     Report Count (4),
+    0xB1, 0x03,                         //     Feature (Constant, Variable, Absolute),
+    0x09, 0x21,                         //     Usage (ASCII Character Set),
+    0x25, 0x01,                         //     Logical Maximum (1),
+    0x75, 0x01,                         //     Report Size (1),
+    0x95, 0x01,                         //     Report Count (1),
+    0xB1, 0x03,                         //     Feature (Constant, Variable, Absolute),
+    0x95, 0x07,                         //     Report Count (7),
+    0xB1, 0x03,                         //     Feature (Constant, Variable, Absolute),
+    0xC0,                               //   End Collection,
+    0x09, 0x2D,                         //   Usage (Display Status),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, DISPLAY_STATUS_REPORT_ID,     //     Report ID (2),
+    0x09, 0x2E,                         //     Usage (Stat Not Ready),
+    0x09, 0x2F,                         //     Usage (Stat Ready),
+    0x09, 0x30,                         //     Usage (Err Not a loadable character),
+    0x09, 0x31,                         //     Usage (Err Font data cannot be read),
+    0x25, 0x03,                         //     Logical Maximum (3),
+    0x75, 0x08,                         //     Report Size (8),
+    0x95, 0x01,                         //     Report Count (1),
+    0x81, 0x00,                         //     Input (Data, Array, Absolute),
+    0xC0,                               //   End Collection,
+    0x09, 0x24,                         //   Usage (Display Control Report),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, DISPLAY_CONTROL_REPORT_ID,    //     Report ID (3),
+    0x09, 0x25,                         //     Usage (Clear Display),
+    0x09, 0x26,                         //     Usage (Display Enable),
+    0x09, 0x39,                         //     Usage (Cursor Enable),
+    0x09, 0x3A,                         //     Usage (Cursor Blink),
+    0x25, 0x01,                         //     Logical Maximum (1),
+    0x75, 0x01,                         //     Report Size (1),
+    0x95, 0x04,                         //     Report Count (4),
+    0x91, 0x02,                         //     Output (Data, Variable, Absolute),
+    0x95, 0x04,                         //     Report Count (4),
+    0x91, 0x03,                         //     Output (Constant, Variable, Absolute),
+    0xC0,                               //   End Collection,
+    0x09, 0x2B,                         //   Usage (Character Report),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, CHARACTER_REPORT_ID,          //     Report ID (4),
+    0x09, 0x2C,                         //     Usage (Display Data),
+    0x26, 0xFF, 0x00,                   //     Logical Maximum (255),
+    0x75, 0x08,                         //     Report Size (8),
+    0x95, 0x10,                         //     Report Count (16),
+    0x92, 0x02, 0x01,                   //     Output (Data, Variable, Absolute, Buffered Bytes),
+    0xC0,                               //   End Collection,
+    0x09, 0x32,                         //   Usage (Cursor Position Report),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, CURSOR_POSITION_REPORT_ID,    //     Report ID (5),
+    0x09, 0x33,                         //     Usage (Row),
+    0x09, 0x34,                         //     Usage (Column),
+    0x95, 0x02,                         //     Report Count (2),
+    0x91, 0x02,                         //     Output (Data, Variable, Absolute),
+    0xC0,                               //   End Collection,
+    0x09, 0x3B,                         //   Usage (Font Report),
+    0xA1, 0x02,                         //   Collection (Logical),
+    0x85, FONT_REPORT_ID,               //     Report ID (6),
+    0x09, 0x2C,                         //     Usage (Display Data),
+    0x95, 0x01,                         //     Report Count (1),
+    0x91, 0x02,                         //     Output (Data, Variable, Absolute),
+    0x09, 0x3C,                         //     Usage (Font Data),
+    0x95, 0x08,                         //     Report Count (8),
+    0x92, 0x02, 0x01,                   //     Output (Data, Variable, Absolute, Buffered Bytes),
+    0xC0,                               //   End Collection,
+    0xC0,                               // End Collection
+];
+
+/// Size of the display, reported in the Display Attributes feature report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DisplayAttributes {
+    pub rows: u8,
+    pub columns: u8,
+    /// Character width in pixels
+    pub character_width: u8,
+    /// Character height in pixels, at most [`FONT_DATA_SIZE`] for loadable fonts
+    pub character_height: u8,
+}
+
+impl Default for DisplayAttributes {
+    /// A 16x2 character LCD with a 5x8 pixel font
+    fn default() -> Self {
+        Self {
+            rows: 2,
+            columns: 16,
+            character_width: 5,
+            character_height: 8,
+        }
+    }
+}
+
+/// Selectors of the Display Status input report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum DisplayStatus {
+    NotReady = 0,
+    #[default]
+    Ready = 1,
+    ErrorNotLoadableCharacter = 2,
+    ErrorFontDataCannotBeRead = 3,
+}
+
+/// Display Control output report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct DisplayControl {
+    #[packed_field(bits = "0")]
+    pub clear_display: bool,
+    #[packed_field(bits = "1")]
+    pub display_enable: bool,
+    #[packed_field(bits = "2")]
+    pub cursor_enable: bool,
+    #[packed_field(bits = "3")]
+    pub cursor_blink: bool,
+}
+
+/// Text from a Character Report, with trailing null characters removed
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DisplayText {
+    data: [u8; DISPLAY_DATA_SIZE],
+    len: usize,
+}
+
+impl DisplayText {
+    fn new(data: &[u8]) -> Self {
+        let len = data.iter().rposition(|&c| c != 0).map_or(0, |i| i + 1);
+        let mut text = Self {
+            data: [0; DISPLAY_DATA_SIZE],
+            len: len.min(DISPLAY_DATA_SIZE),
+        };
+        text.data[..text.len].copy_from_slice(&data[..text.len]);
+        text
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+/// Output from the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AuxDisplayEvent {
+    /// Text to write at the cursor
+    WriteText(DisplayText),
+    /// Move the cursor, zero based and within the display attributes
+    Cursor { row: u8, column: u8 },
+    /// Clear the display and set the display and cursor modes
+    Control(DisplayControl),
+    /// Load the bitmap of a character, one byte per row with bit 0 the leftmost pixel
+    Font {
+        character: u8,
+        bitmap: [u8; FONT_DATA_SIZE],
+    },
+}
+
+/// Interface implementing an auxiliary character display
+pub struct AuxDisplay<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutBytes32, ReportSingle>,
+    attributes: DisplayAttributes,
+    attributes_report: [u8; 6],
+}
+
+impl<B: UsbBus> AuxDisplay<'_, B> {
+    /// Read the next output report from the host
+    ///
+    /// Returns [`UsbError::WouldBlock`] if there is no report and [`UsbError::ParseError`] for
+    /// malformed reports or cursor positions outside the display.
+    pub fn read_report(&mut self) -> usb_device::Result<AuxDisplayEvent> {
+        let mut data = [0u8; 1 + DISPLAY_DATA_SIZE];
+        let len = self.interface.read_report(&mut data)?;
+        if len == 0 {
+            return Err(UsbError::ParseError);
+        }
+        match (data[0], &data[1..len]) {
+            (CHARACTER_REPORT_ID, text) => Ok(AuxDisplayEvent::WriteText(DisplayText::new(text))),
+            (CURSOR_POSITION_REPORT_ID, &[row, column, ..]) => {
+                if row >= self.attributes.rows || column >= self.attributes.columns {
+                    return Err(UsbError::ParseError);
+                }
+                Ok(AuxDisplayEvent::Cursor { row, column })
+            }
+            (DISPLAY_CONTROL_REPORT_ID, &[control, ..]) => DisplayControl::unpack(&[control])
+                .map(AuxDisplayEvent::Control)
+                .map_err(|_| UsbError::ParseError),
+            (FONT_REPORT_ID, &[character, ref bitmap @ ..]) if bitmap.len() >= FONT_DATA_SIZE => {
+                Ok(AuxDisplayEvent::Font {
+                    character,
+                    bitmap: unwrap!(bitmap[..FONT_DATA_SIZE].try_into().ok()),
+                })
+            }
+            _ => Err(UsbError::ParseError),
+        }
+    }
+
+    /// Report whether the display is ready for more output, or failed to load a character
+    pub fn write_status(&mut self, status: DisplayStatus) -> Result<(), UsbHidError> {
+        self.interface
+            .write_report(&[DISPLAY_STATUS_REPORT_ID, status.into()])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+
+    #[must_use]
+    pub fn attributes(&self) -> DisplayAttributes {
+        self.attributes
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for AuxDisplay<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutBytes32, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        (report_id == DISPLAY_ATTRIBUTES_REPORT_ID)
+            .then_some(FeatureReport::DynamicReport(&self.attributes_report))
+    }
+}
+
+pub struct AuxDisplayConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutBytes32, ReportSingle>,
+    attributes: DisplayAttributes,
+}
+
+impl Default for AuxDisplayConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                AUX_DISPLAY_REPORT_DESCRIPTOR
+            ))
+            .description("Auxiliary Display")
+            .in_endpoint(100.millis()))
+            .with_out_endpoint(10.millis()))
+            .build(),
+        )
+    }
+}
+
+impl<'a> AuxDisplayConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutBytes32, ReportSingle>) -> Self {
+        Self {
+            interface,
+            attributes: DisplayAttributes::default(),
+        }
+    }
+
+    /// Set the display size, rows, columns and the character size must be non-zero
+    pub fn attributes(mut self, attributes: DisplayAttributes) -> BuilderResult<Self> {
+        if attributes.rows == 0
+            || attributes.columns == 0
+            || attributes.character_width == 0
+            || attributes.character_height == 0
+        {
+            return Err(UsbHidBuilderError::ValueOverflow);
+        }
+        self.attributes = attributes;
+        Ok(self)
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for AuxDisplayConfig<'a> {
+    type Allocated = AuxDisplay<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        let attributes = self.attributes;
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            attributes,
+            attributes_report: [
+                DISPLAY_ATTRIBUTES_REPORT_ID,
+                attributes.rows,
+                attributes.columns,
+                attributes.character_width,
+                attributes.character_height,
+                0x01, // ASCII Character Set
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn display_control_unpacks() {
+        assert_eq!(
+            DisplayControl::unpack(&[0x0D]).unwrap(),
+            DisplayControl {
+                clear_display: true,
+                display_enable: false,
+                cursor_enable: true,
+                cursor_blink: true,
+            }
+        );
+    }
+
+    #[test]
+    fn aux_display_text_and_cursor_events() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(
+                AuxDisplayConfig::default()
+                    .attributes(DisplayAttributes {
+                        rows: 4,
+                        columns: 20,
+                        character_width: 5,
+                        character_height: 8,
+                    })
+                    .unwrap(),
+            )
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Get the display attributes
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x01, 6),
+            [0x01, 4, 20, 5, 8, 0x01]
+        );
+
+        let display = hid.device();
+        assert_eq!(display.read_report(), Err(UsbError::WouldBlock));
+
+        manager.host_write_out(&[0x03, 0x07]).unwrap();
+        assert_eq!(
+            display.read_report(),
+            Ok(AuxDisplayEvent::Control(DisplayControl {
+                clear_display: true,
+                display_enable: true,
+                cursor_enable: true,
+                cursor_blink: false,
+            }))
+        );
+
+        manager.host_write_out(&[0x05, 3, 19]).unwrap();
+        assert_eq!(
+            display.read_report(),
+            Ok(AuxDisplayEvent::Cursor { row: 3, column: 19 })
+        );
+
+        // Outside the display
+        manager.host_write_out(&[0x05, 4, 0]).unwrap();
+        assert_eq!(display.read_report(), Err(UsbError::ParseError));
+
+        let mut report = [0u8; 17];
+        report[0] = 0x04;
+        report[1..6].copy_from_slice(b"Hello");
+        manager.host_write_out(&report).unwrap();
+        let Ok(AuxDisplayEvent::WriteText(text)) = display.read_report() else {
+            panic!("expected text");
+        };
+        assert_eq!(text.as_bytes(), b"Hello");
+
+        manager
+            .host_write_out(&[0x06, 0x01, 0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00])
+            .unwrap();
+        assert_eq!(
+            display.read_report(),
+            Ok(AuxDisplayEvent::Font {
+                character: 0x01,
+                bitmap: [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11, 0x00],
+            })
+        );
+
+        display.write_status(DisplayStatus::Ready).unwrap();
+        assert_eq!(manager.host_read_in(), [0x02, 0x01]);
+    }
+
+    #[test]
+    fn zero_length_report_rejected() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(AuxDisplayConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        manager.host_write_out(&[]).unwrap();
+        assert_eq!(hid.device().read_report(), Err(UsbError::ParseError));
+    }
+}
@@ -0,0 +1,391 @@
+//!HID POS barcode scanner, recognized by the Windows point of service API without drivers
+//!
+//! Decoded scans are queued by [`BarcodeScanner::write_scan`] and sent by
+//! [`UsbHidClass::tick()`] as Scanned Data Reports of up to [`DECODED_DATA_CHUNK_SIZE`] bytes,
+//! with Decode Data Continued set on all but the last report of a scan.
+//!
+//! See [HID Point of Sale Usage Tables Version 1.02](<https://www.usb.org/sites/default/files/pos1_02.pdf>)
+use fugit::ExtU32;
+use heapless::Deque;
+use packed_struct::prelude::*;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+use crate::usb_class::prelude::*;
+
+/// Report ID of the Attribute feature report
+pub const ATTRIBUTE_REPORT_ID: u8 = 0x01;
+/// Report ID of the Scanned Data input report
+pub const SCANNED_DATA_REPORT_ID: u8 = 0x02;
+/// Report ID of the Trigger output report
+pub const TRIGGER_REPORT_ID: u8 = 0x04;
+/// Decoded data bytes sent in each Scanned Data Report
+pub const DECODED_DATA_CHUNK_SIZE: usize = 56;
+/// Number of scans that can be queued
+pub const BARCODE_QUEUE_SIZE: usize = 8;
+/// Total decoded data bytes that can be queued
+pub const BARCODE_BUFFER_SIZE: usize = 512;
+/// Largest Scanner Data Accuracy, as a percentage
+pub const SCANNER_DATA_ACCURACY_MAX: u8 = 100;
+
+const SCANNED_DATA_REPORT_SIZE: usize = 6 + DECODED_DATA_CHUNK_SIZE + 1;
+
+///Barcode scanner report descriptor
+///
+/// * Report 1 - Attribute feature report, [`BarcodeAttributes`]
+/// * Report 2 - Scanned Data input report, byte count, three byte AIM symbology identifier,
+///   scanner data accuracy, 56 bytes of decoded data and the Decode Data Continued flag
+/// * Report 4 - Trigger output report, bit 0 Trigger State
+#[rustfmt::skip]
+pub const BARCODE_SCANNER_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x8C,                 // Usage Page (Bar Code Scanner),
+    0x09, 0x02,                 // Usage (Bar Code Scanner),
+    0xA1, 0x01,                 // Collection (Application),
+    0x09, 0x10,                 //   Usage (Attribute Report),
+    0xA1, 0x02,                 //   Collection (Logical),
+    0x85, ATTRIBUTE_REPORT_ID,  //     Report ID (1),
+    0x09, 0x30,                 //     Usage (Aiming/Pointer Mode),
+    0x09, 0x3B,                 //     Usage (Hands Free Scanning),
+    0x09, 0x42,                 //     Usage (Triggerless),
+    0x15, 0x00,                 //     Logical Minimum (0),
+    0x25, 0x01,                 //     Logical Maximum (1),
+    0x75, 0x01,                 //     Report Size (1),
+    0x95, 0x03,                 //     Report Count (3),
+    0xB1, 0x02,                 //     Feature (Data, Variable, Absolute),
+    0x95, 0x05,                 //     Report Count (5),
+    0xB1, 0x03,                 //     Feature (Constant, Variable, Absolute),
+    0xC0,                       //   End Collection,
+    0x09, 0x12,                 //   Usage (Scanned Data Report),
+    0xA1, 0x02,                 //   Collection (Logical),
+    0x85, SCANNED_DATA_REPORT_ID, //   Report ID (2),
+    0x05, 0x01,                 //     Usage Page (Desktop),
+    0x09, 0x3B,                 //     Usage (Byte Count),
+    0x25, 0x38,                 //     Logical Maximum (56),
+    0x75, 0x08,                 //     Report Size (8),
+    0x95, 0x01,                 //     Report Count (1),
+    0x81, 0x02,                 //     Input (Data, Variable, Absolute),
+    0x05, 0x8C,                 //     Usage Page (Bar Code Scanner),
+    0x09, 0xFB,                 //     Usage (Symbology Identifier 1),
+    0x09, 0xFC,                 //     Usage (Symbology Identifier 2),
+    0x09, 0xFD,                 //     Usage (Symbology Identifier 3),
+    0x26, 0xFF, 0x00,           //     Logical Maximum (255),
+    0x95, 0x03,                 //     Report Count (3),
+    0x81, 0x02,                 //     Input (Data, Variable, Absolute),
+    0x0A, 0x01, 0x01,           //     Usage (Scanner Data Accuracy),
+    0x25, 0x64,                 //     Logical Maximum (100),
+    0x95, 0x01,                 //     Report Count (1),
+    0x81, 0x02,                 //     Input (Data, Variable, Absolute),
+    0x09, 0xFE,                 //     Usage (Decoded Data),
+    0x26, 0xFF, 0x00,           //     Logical Maximum (255),
+    0x95, 0x38,                 //     Report Count (56),
+    0x82, 0x02, 0x01,           //     Input (Data, Variable, Absolute, Buffered Bytes),
+    0x09, 0xFF,                 //     Usage (Decode Data Continued),
+    0x25, 0x01,                 //     Logical Maximum (1),
+    0x75, 0x01,                 //     Report Size (1),
+    0x95, 0x01,                 //     Report Count (1),
+    0x81, 0x02,                 //     Input (Data, Variable, Absolute),
+    0x95, 0x07,                 //     Report Count (7),
+    0x81, 0x03,                 //     Input (Constant, Variable, Absolute),
+    0xC0,                       //   End Collection,
+    0x09, 0x14,                 //   Usage (Trigger Report),
+    0xA1, 0x02,                 //   Collection (Logical),
+    0x85, TRIGGER_REPORT_ID,    //     Report ID (4),
+    0x09, 0x6C,                 //     Usage (Trigger State),
+    0x95, 0x01,                 //     Report Count (1),
+    0x91, 0x02,                 //     Output (Data, Variable, Absolute),
+    0x95, 0x07,                 //     Report Count (7),
+    0x91, 0x03,                 //     Output (Constant, Variable, Absolute),
+    0xC0,                       //   End Collection,
+    0xC0,                       // End Collection
+];
+
+/// Scanner capabilities reported in the Attribute feature report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct BarcodeAttributes {
+    #[packed_field(bits = "0")]
+    pub aiming: bool,
+    #[packed_field(bits = "1")]
+    pub hands_free: bool,
+    #[packed_field(bits = "2")]
+    pub triggerless: bool,
+}
+
+/// AIM symbology identifier, e.g. `]E0` for EAN-13
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct Symbology(pub [u8; 3]);
+
+impl Symbology {
+    pub const UNKNOWN: Self = Self([0; 3]);
+    pub const EAN_13: Self = Self(*b"]E0");
+    pub const EAN_8: Self = Self(*b"]E4");
+    pub const CODE_39: Self = Self(*b"]A0");
+    pub const CODE_128: Self = Self(*b"]C0");
+    pub const GS1_128: Self = Self(*b"]C1");
+    pub const DATA_MATRIX: Self = Self(*b"]d1");
+    pub const PDF417: Self = Self(*b"]L0");
+    pub const QR_CODE: Self = Self(*b"]Q1");
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PendingScan {
+    symbology: Symbology,
+    accuracy: u8,
+    remaining: usize,
+}
+
+/// Interface implementing a HID POS barcode scanner
+///
+/// **Note:** Scans are sent one report at a time, [`UsbHidClass::tick()`] must be called every
+/// 1ms to send queued scans and read the trigger.
+pub struct BarcodeScanner<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes64, OutBytes8, ReportSingle>,
+    attributes: [u8; 2],
+    scans: Deque<PendingScan, BARCODE_QUEUE_SIZE>,
+    data: Deque<u8, BARCODE_BUFFER_SIZE>,
+    trigger: bool,
+}
+
+impl<B: UsbBus> BarcodeScanner<'_, B> {
+    /// Queue a decoded scan, `accuracy` is a percentage up to [`SCANNER_DATA_ACCURACY_MAX`]
+    ///
+    /// Returns [`UsbHidError::WouldBlock`] if the queue is full, scans longer than
+    /// [`BARCODE_BUFFER_SIZE`] are rejected with [`UsbError::BufferOverflow`].
+    pub fn write_scan(
+        &mut self,
+        symbology: Symbology,
+        accuracy: u8,
+        data: &[u8],
+    ) -> Result<(), UsbHidError> {
+        if data.len() > BARCODE_BUFFER_SIZE {
+            return Err(UsbHidError::UsbError(UsbError::BufferOverflow));
+        }
+        if self.scans.is_full() || self.data.capacity() - self.data.len() < data.len() {
+            return Err(UsbHidError::WouldBlock);
+        }
+        for &b in data {
+            unwrap!(self.data.push_back(b).ok());
+        }
+        unwrap!(self
+            .scans
+            .push_back(PendingScan {
+                symbology,
+                accuracy: accuracy.min(SCANNER_DATA_ACCURACY_MAX),
+                remaining: data.len(),
+            })
+            .ok());
+        self.flush()
+    }
+
+    /// Number of scans waiting to be sent
+    #[must_use]
+    pub fn pending_scans(&self) -> usize {
+        self.scans.len()
+    }
+
+    /// Trigger State last set by the host
+    #[must_use]
+    pub fn trigger(&self) -> bool {
+        self.trigger
+    }
+
+    fn flush(&mut self) -> Result<(), UsbHidError> {
+        while let Some(&scan) = self.scans.front() {
+            let len = scan.remaining.min(DECODED_DATA_CHUNK_SIZE);
+            let continued = scan.remaining > DECODED_DATA_CHUNK_SIZE;
+
+            let mut report = [0u8; SCANNED_DATA_REPORT_SIZE];
+            report[0] = SCANNED_DATA_REPORT_ID;
+            #[allow(clippy::cast_possible_truncation)]
+            {
+                report[1] = len as u8;
+            }
+            report[2..5].copy_from_slice(&scan.symbology.0);
+            report[5] = scan.accuracy;
+            for (r, &b) in report[6..6 + len].iter_mut().zip(self.data.iter()) {
+                *r = b;
+            }
+            report[6 + DECODED_DATA_CHUNK_SIZE] = u8::from(continued);
+
+            match self.interface.write_report(&report) {
+                Ok(_) => {
+                    for _ in 0..len {
+                        self.data.pop_front();
+                    }
+                    if continued {
+                        if let Some(front) = self.scans.front_mut() {
+                            front.remaining -= len;
+                        }
+                    } else {
+                        self.scans.pop_front();
+                    }
+                }
+                //retry on the next tick
+                Err(UsbError::WouldBlock) => return Ok(()),
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for BarcodeScanner<'a, B> {
+    type I = Interface<'a, B, InBytes64, OutBytes8, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.scans.clear();
+        self.data.clear();
+        self.trigger = false;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        let mut data = [0u8; 8];
+        loop {
+            match self.interface.read_report(&mut data) {
+                Ok(2..) if data[0] == TRIGGER_REPORT_ID => self.trigger = data[1] & 0x01 != 0,
+                Ok(len) => warn!("Unexpected barcode output report, {} bytes", len),
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+        self.flush()
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        (report_id == ATTRIBUTE_REPORT_ID).then_some(FeatureReport::DynamicReport(&self.attributes))
+    }
+}
+
+pub struct BarcodeScannerConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes64, OutBytes8, ReportSingle>,
+    attributes: BarcodeAttributes,
+}
+
+impl Default for BarcodeScannerConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                BARCODE_SCANNER_REPORT_DESCRIPTOR
+            ))
+            .description("Barcode Scanner")
+            .in_endpoint(1.millis()))
+            .with_out_endpoint(10.millis()))
+            .build(),
+        )
+    }
+}
+
+impl<'a> BarcodeScannerConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes64, OutBytes8, ReportSingle>) -> Self {
+        Self {
+            interface,
+            attributes: BarcodeAttributes::default(),
+        }
+    }
+
+    /// Set the capabilities reported to the host
+    #[must_use]
+    pub fn attributes(mut self, attributes: BarcodeAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for BarcodeScannerConfig<'a> {
+    type Allocated = BarcodeScanner<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        let [attributes] = unwrap!(self.attributes.pack().ok());
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            attributes: [ATTRIBUTE_REPORT_ID, attributes],
+            scans: Deque::new(),
+            data: Deque::new(),
+            trigger: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn attributes_pack() {
+        let attributes = BarcodeAttributes {
+            aiming: true,
+            hands_free: false,
+            triggerless: true,
+        };
+        assert_eq!(attributes.pack().unwrap(), [0x05]);
+    }
+
+    #[test]
+    fn barcode_scan_sent_in_chunks() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(
+                BarcodeScannerConfig::default().attributes(BarcodeAttributes {
+                    aiming: true,
+                    hands_free: false,
+                    triggerless: false,
+                }),
+            )
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Get the attribute feature report
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x01, 2),
+            [0x01, 0x01]
+        );
+
+        // Host pulls the trigger
+        manager.host_write_out(&[0x04, 0x01]).unwrap();
+        hid.tick().unwrap();
+        assert!(hid.device().trigger());
+
+        let data: Vec<u8> = (0..70).collect();
+        let scanner = hid.device();
+        scanner.write_scan(Symbology::CODE_128, 95, &data).unwrap();
+        scanner
+            .write_scan(Symbology::EAN_13, 100, b"4006381333931")
+            .unwrap();
+        assert_eq!(scanner.pending_scans(), 2);
+
+        let report = manager.host_read_in();
+        assert_eq!(report.len(), 63);
+        assert_eq!(report[..6], [0x02, 56, b']', b'C', b'0', 95]);
+        assert_eq!(report[6..62], data[..56]);
+        assert_eq!(report[62], 0x01);
+
+        hid.tick().unwrap();
+        let report = manager.host_read_in();
+        assert_eq!(report[..6], [0x02, 14, b']', b'C', b'0', 95]);
+        assert_eq!(report[6..20], data[56..]);
+        assert_eq!(report[62], 0x00);
+
+        hid.tick().unwrap();
+        let report = manager.host_read_in();
+        assert_eq!(report[..6], [0x02, 13, b']', b'E', b'0', 100]);
+        assert_eq!(report[6..19], *b"4006381333931");
+        assert_eq!(report[62], 0x00);
+        assert_eq!(hid.device().pending_scans(), 0);
+    }
+}
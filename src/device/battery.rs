@@ -0,0 +1,397 @@
+//!HID battery strength and charging status
+//!
+//! The battery is a separate interface that can be added alongside any other device, the host
+//! associates the reported battery level with the composite USB device. Alternatively the battery
+//! reports can be embedded in another device using [`BatteryDescriptor::fragment()`].
+
+use fugit::ExtU32;
+use packed_struct::prelude::*;
+#[allow(clippy::wildcard_imports)]
+use usb_device::class_prelude::*;
+
+use crate::descriptor::append;
+use crate::usb_class::prelude::*;
+
+/// Maximum value of [`BatteryReport::strength`], representing a full battery
+pub const BATTERY_STRENGTH_MAX: u8 = 100;
+
+const MAX_DESCRIPTOR_LEN: usize = 96;
+const HEADER_LEN: usize = 6;
+
+/// Battery report descriptor, built at compile time
+///
+/// Identical input and feature reports, allowing the host to either be notified of changes or
+/// poll the current state
+/// * Byte 0 - Report ID, omitted if the report ID is 0
+/// * Byte 1 - Battery Strength, 0-100%
+/// * Byte 2, Bit 0 - Charging
+/// * Byte 2, Bit 1 - Discharging
+/// * Byte 2, Bit 2 - AC Present
+/// * Byte 2, Bit 3-7 - Reserved
+///
+/// [`BatteryDescriptor::as_bytes()`] is a complete report descriptor for a separate battery
+/// interface. [`BatteryDescriptor::fragment()`] contains only the reports, to be embedded in the
+/// Application collection of another device with a report ID not already used by that device
+/// and sent with [`BatteryReport::with_report_id()`]:
+/// ```
+/// # use usbd_human_interface_device::device::battery::*;
+/// static BATTERY: BatteryDescriptor = BatteryDescriptor::new(0x05);
+/// ```
+pub struct BatteryDescriptor {
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+}
+
+impl BatteryDescriptor {
+    #[must_use]
+    pub const fn new(report_id: u8) -> Self {
+        let mut bytes = [0u8; MAX_DESCRIPTOR_LEN];
+        let mut len = 0;
+
+        #[rustfmt::skip]
+        let header = [
+            0x05, 0x06, // Usage Page (Generic Device Controls),
+            0x09, 0x01, // Usage (Background/Nonuser Controls),
+            0xA1, 0x01, // Collection (Application),
+        ];
+        (bytes, len) = append(bytes, len, &header);
+
+        if report_id != 0 {
+            (bytes, len) = append(bytes, len, &[0x85, report_id]); // Report ID,
+        }
+
+        #[rustfmt::skip]
+        let reports = [
+            0x05, 0x06, //     Usage Page (Generic Device Controls),
+            0x09, 0x20, //     Usage (Battery Strength),
+            0x15, 0x00, //     Logical Minimum (0),
+            0x25, 0x64, //     Logical Maximum (100),
+            0x75, 0x08, //     Report Size (8),
+            0x95, 0x01, //     Report Count (1),
+            0x81, 0x02, //     Input (Data, Variable, Absolute),
+            0x05, 0x85, //     Usage Page (Battery System),
+            0x09, 0x44, //     Usage (Charging),
+            0x09, 0x45, //     Usage (Discharging),
+            0x09, 0xD0, //     Usage (AC Present),
+            0x25, 0x01, //     Logical Maximum (1),
+            0x75, 0x01, //     Report Size (1),
+            0x95, 0x03, //     Report Count (3),
+            0x81, 0x02, //     Input (Data, Variable, Absolute),
+            0x95, 0x05, //     Report Count (5),
+            0x81, 0x01, //     Input (Constant),
+            0x05, 0x06, //     Usage Page (Generic Device Controls),
+            0x09, 0x20, //     Usage (Battery Strength),
+            0x25, 0x64, //     Logical Maximum (100),
+            0x75, 0x08, //     Report Size (8),
+            0x95, 0x01, //     Report Count (1),
+            0xB1, 0x02, //     Feature (Data, Variable, Absolute),
+            0x05, 0x85, //     Usage Page (Battery System),
+            0x09, 0x44, //     Usage (Charging),
+            0x09, 0x45, //     Usage (Discharging),
+            0x09, 0xD0, //     Usage (AC Present),
+            0x25, 0x01, //     Logical Maximum (1),
+            0x75, 0x01, //     Report Size (1),
+            0x95, 0x03, //     Report Count (3),
+            0xB1, 0x02, //     Feature (Data, Variable, Absolute),
+            0x95, 0x05, //     Report Count (5),
+            0xB1, 0x01, //     Feature (Constant),
+        ];
+        (bytes, len) = append(bytes, len, &reports);
+
+        (bytes, len) = append(bytes, len, &[0xC0]); // End Collection
+
+        Self { bytes, len }
+    }
+
+    /// Report descriptor of a separate battery interface
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+
+    /// Battery reports for embedding in another device's report descriptor
+    ///
+    /// The fragment changes the Usage Page, Report Size, Report Count and logical ranges, items
+    /// following it must set these again.
+    #[must_use]
+    pub const fn fragment(&self) -> &[u8] {
+        self.bytes.split_at(self.len - 1).0.split_at(HEADER_LEN).1
+    }
+}
+
+/// Descriptor of the [`Battery`] interface, without a report ID
+pub static BATTERY_DESCRIPTOR: BatteryDescriptor = BatteryDescriptor::new(0);
+
+/// Source of power for the device
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum PowerSource {
+    /// Running from the battery
+    #[default]
+    Battery,
+    /// On external power and charging the battery
+    Charging,
+    /// On external power with the battery not charging, e.g. when full
+    External,
+}
+
+/// Report of the battery level and charging state, used for both the input and feature reports
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "2")]
+pub struct BatteryReport {
+    #[packed_field(bytes = "0")]
+    pub strength: u8,
+    #[packed_field(bits = "15")]
+    pub charging: bool,
+    #[packed_field(bits = "14")]
+    pub discharging: bool,
+    #[packed_field(bits = "13")]
+    pub ac_present: bool,
+}
+
+impl BatteryReport {
+    /// Create a report, clamping `strength` to [`BATTERY_STRENGTH_MAX`]
+    #[must_use]
+    pub fn new(strength: u8, source: PowerSource) -> Self {
+        Self {
+            strength: strength.min(BATTERY_STRENGTH_MAX),
+            charging: source == PowerSource::Charging,
+            discharging: source == PowerSource::Battery,
+            ac_present: source != PowerSource::Battery,
+        }
+    }
+
+    /// The report prefixed with `report_id`, for devices embedding
+    /// [`BatteryDescriptor::fragment()`]
+    #[must_use]
+    pub fn with_report_id(&self, report_id: u8) -> [u8; 3] {
+        let [strength, status] = self.pack().unwrap_or_default();
+        [report_id, strength, status]
+    }
+}
+
+/// Interface reporting battery strength and charging status
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms. The battery state is only sent
+/// when it changes unless the host sets an idle rate, in which case tick repeats it at that rate.
+pub struct Battery<'a, B: UsbBus> {
+    interface: ManagedIdleInterface<'a, B, BatteryReport, InBytes8, OutNone>,
+    feature_report: [u8; 2],
+}
+
+impl<B: UsbBus> Battery<'_, B> {
+    /// Update the battery state
+    ///
+    /// The state is served from the feature report even if the input report could not be
+    /// written. Until the first update the feature report returns the initial report of the
+    /// [`BatteryConfig`].
+    pub fn write_report(&mut self, report: &BatteryReport) -> Result<(), UsbHidError> {
+        self.feature_report = report.pack().map_err(|_| {
+            error!("Error packing BatteryReport");
+            UsbHidError::SerializationError
+        })?;
+        self.interface.write_report(report)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for Battery<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        self.interface.interface()
+    }
+
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        (report_id == 0).then_some(FeatureReport::DynamicReport(&self.feature_report))
+    }
+}
+
+pub struct BatteryConfig<'a> {
+    interface: ManagedIdleInterfaceConfig<'a, BatteryReport, InBytes8, OutNone>,
+    initial_report: BatteryReport,
+}
+
+impl<'a> BatteryConfig<'a> {
+    /// Create a configuration reporting `initial_report` to the host until the first call to
+    /// [`Battery::write_report()`]
+    #[must_use]
+    pub fn new(initial_report: BatteryReport) -> Self {
+        Self::with_interface(
+            ManagedIdleInterfaceConfig::new(
+                unwrap!(unwrap!(
+                    unwrap!(InterfaceBuilder::new(BATTERY_DESCRIPTOR.as_bytes()))
+                        .description("Battery")
+                        .idle_default(0.millis())
+                )
+                .in_endpoint(100.millis()))
+                .without_out_endpoint()
+                .build(),
+            ),
+            initial_report,
+        )
+    }
+
+    #[must_use]
+    pub fn with_interface(
+        interface: ManagedIdleInterfaceConfig<'a, BatteryReport, InBytes8, OutNone>,
+        initial_report: BatteryReport,
+    ) -> Self {
+        Self {
+            interface,
+            initial_report,
+        }
+    }
+}
+
+impl Default for BatteryConfig<'_> {
+    /// Configuration of a full battery, running from the battery
+    fn default() -> Self {
+        Self::new(BatteryReport::new(
+            BATTERY_STRENGTH_MAX,
+            PowerSource::Battery,
+        ))
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for BatteryConfig<'a> {
+    type Allocated = Battery<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: self.interface.allocate(usb_alloc),
+            feature_report: self.initial_report.pack().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{report_bits, test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn battery_report_packing() {
+        assert_eq!(
+            BatteryReport::new(75, PowerSource::Charging)
+                .pack()
+                .unwrap(),
+            [75, 0b101]
+        );
+        assert_eq!(
+            BatteryReport::new(200, PowerSource::Battery)
+                .pack()
+                .unwrap(),
+            [100, 0b010]
+        );
+        assert_eq!(
+            BatteryReport::new(100, PowerSource::External)
+                .pack()
+                .unwrap(),
+            [100, 0b100]
+        );
+        assert_eq!(
+            BatteryReport::new(50, PowerSource::Battery).with_report_id(0x05),
+            [0x05, 50, 0b010]
+        );
+    }
+
+    #[test]
+    fn descriptor_fragment() {
+        static DESCRIPTOR: BatteryDescriptor = BatteryDescriptor::new(0x05);
+
+        let bytes = DESCRIPTOR.as_bytes();
+        assert_eq!(bytes[..8], [0x05, 0x06, 0x09, 0x01, 0xA1, 0x01, 0x85, 0x05]);
+        assert_eq!(bytes.last(), Some(&0xC0));
+        assert_eq!(DESCRIPTOR.fragment(), &bytes[6..bytes.len() - 1]);
+
+        let bytes = BATTERY_DESCRIPTOR.as_bytes();
+        assert_eq!(bytes.len(), DESCRIPTOR.as_bytes().len() - 2);
+        assert_eq!(bytes[6..8], [0x05, 0x06]);
+    }
+
+    #[test]
+    fn descriptor_fragment_embedded_in_keyboard() {
+        static DESCRIPTOR: BatteryDescriptor = BatteryDescriptor::new(0x05);
+
+        #[rustfmt::skip]
+        let keyboard = [
+            0x05, 0x01,                 // Usage Page (Generic Desktop),
+            0x09, 0x06,                 // Usage (Keyboard),
+            0xA1, 0x01,                 // Collection (Application),
+            0x85, 0x01,                 //   Report ID (1),
+            0x05, 0x07,                 //   Usage Page (Keyboard),
+            0x19, 0xE0,                 //   Usage Minimum (Left Control),
+            0x29, 0xE7,                 //   Usage Maximum (Right GUI),
+            0x15, 0x00,                 //   Logical Minimum (0),
+            0x25, 0x01,                 //   Logical Maximum (1),
+            0x75, 0x01,                 //   Report Size (1),
+            0x95, 0x08,                 //   Report Count (8),
+            0x81, 0x02,                 //   Input (Data, Variable, Absolute),
+        ];
+        let descriptor = [&keyboard[..], DESCRIPTOR.fragment(), &[0xC0]].concat();
+
+        assert_eq!(
+            report_bits(&descriptor),
+            [(0x80, 0x01, 8), (0x80, 0x05, 16), (0xB0, 0x05, 16)]
+        );
+        let report = BatteryReport::new(30, PowerSource::External).with_report_id(0x05);
+        assert_eq!(report, [0x05, 30, 0b100]);
+    }
+
+    #[test]
+    fn battery_feature_report() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(BatteryConfig::new(BatteryReport::new(
+                80,
+                PowerSource::Charging,
+            )))
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Initial state before the first update
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 2),
+            [80, 0b101]
+        );
+
+        hid.device()
+            .write_report(&BatteryReport::new(20, PowerSource::Battery))
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [20, 0b010]);
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 2),
+            [20, 0b010]
+        );
+    }
+
+    #[test]
+    fn battery_default_feature_report() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(BatteryConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 2),
+            [BATTERY_STRENGTH_MAX, 0b010]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//!HID braille display, supported natively by Windows Narrator and NVDA
+//!
+//! Implements the Braille Display page (0x41) with a configurable number of 8 dot cells. The
+//! host writes cells as an output report, read with [`BrailleDisplay::read_cells`], and the
+//! router, navigation and braille keyboard keys are sent with [`BrailleDisplay::write_keys`].
+//!
+//! See [HID Usage Tables FOR Universal Serial Bus (USB) Version 1.5](<https://usb.org/sites/default/files/hut1_5.pdf>), Braille Display Page
+use fugit::ExtU32;
+use packed_struct::prelude::*;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+
+use crate::descriptor::append;
+use crate::usb_class::prelude::*;
+
+/// Largest number of cells, limited by the 64 byte output report
+pub const MAX_BRAILLE_CELLS: u8 = 64;
+
+const MAX_DESCRIPTOR_LEN: usize = 96;
+const MAX_INPUT_REPORT_SIZE: usize = 2 + MAX_BRAILLE_CELLS as usize / 8;
+
+/// Braille display report descriptor for a number of cells
+///
+/// Input report:
+/// * Byte 0 - Braille Keyboard Dot 1 to 8
+/// * Byte 1 - [`BrailleButtons`]
+/// * Bytes 2.. - Router Key for each cell, one bit per cell
+///
+/// Output report:
+/// * One 8 Dot Braille Cell byte per cell, see [`BrailleCell`]
+///
+/// Built at compile time, for example:
+/// ```
+/// # use usbd_human_interface_device::device::braille::*;
+/// static DESCRIPTOR: BrailleDescriptor = BrailleDescriptor::new(40);
+/// ```
+pub struct BrailleDescriptor {
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+    cells: u8,
+}
+
+impl BrailleDescriptor {
+    /// Create a descriptor, `cells` is limited to 1 to [`MAX_BRAILLE_CELLS`]
+    #[must_use]
+    pub const fn new(cells: u8) -> Self {
+        let cells = if cells == 0 {
+            1
+        } else if cells > MAX_BRAILLE_CELLS {
+            MAX_BRAILLE_CELLS
+        } else {
+            cells
+        };
+        let router_padding = (8 - cells % 8) % 8;
+
+        let mut bytes = [0u8; MAX_DESCRIPTOR_LEN];
+        let mut len = 0;
+
+        #[rustfmt::skip]
+        let header = [
+            0x05, 0x41,         // Usage Page (Braille Display),
+            0x09, 0x01,         // Usage (Braille Display),
+            0xA1, 0x01,         // Collection (Application),
+            0x15, 0x00,         //   Logical Minimum (0),
+            0x25, 0x01,         //   Logical Maximum (1),
+            0x75, 0x01,         //   Report Size (1),
+            0x1A, 0x01, 0x02,   //   Usage Minimum (Braille Keyboard Dot 1),
+            0x2A, 0x08, 0x02,   //   Usage Maximum (Braille Keyboard Dot 8),
+            0x95, 0x08,         //   Report Count (8),
+            0x81, 0x02,         //   Input (Data, Variable, Absolute),
+            0x0A, 0x09, 0x02,   //   Usage (Braille Keyboard Space),
+            0x0A, 0x0A, 0x02,   //   Usage (Braille Keyboard Left Space),
+            0x0A, 0x0B, 0x02,   //   Usage (Braille Keyboard Right Space),
+            0x0A, 0x1A, 0x02,   //   Usage (Braille Pan Left),
+            0x0A, 0x1B, 0x02,   //   Usage (Braille Pan Right),
+            0x0A, 0x1C, 0x02,   //   Usage (Braille Rocker Up),
+            0x0A, 0x1D, 0x02,   //   Usage (Braille Rocker Down),
+            0x95, 0x07,         //   Report Count (7),
+            0x81, 0x02,         //   Input (Data, Variable, Absolute),
+            0x95, 0x01,         //   Report Count (1),
+            0x81, 0x03,         //   Input (Constant, Variable, Absolute),
+            0x09, 0xFA,         //   Usage (Router Set 1),
+            0xA1, 0x02,         //   Collection (Logical),
+            0x0A, 0x00, 0x01,   //     Usage (Router Key),
+            0x95, cells,        //     Report Count (cells),
+            0x81, 0x02,         //     Input (Data, Variable, Absolute),
+        ];
+        (bytes, len) = append(bytes, len, &header);
+
+        if router_padding > 0 {
+            // Report Count (padding), Input (Constant, Variable, Absolute)
+            (bytes, len) = append(bytes, len, &[0x95, router_padding, 0x81, 0x03]);
+        }
+
+        #[rustfmt::skip]
+        let footer = [
+            0xC0,               //   End Collection,
+            0x09, 0x02,         //   Usage (Braille Row),
+            0xA1, 0x02,         //   Collection (Logical),
+            0x09, 0x03,         //     Usage (8 Dot Braille Cell),
+            0x26, 0xFF, 0x00,   //     Logical Maximum (255),
+            0x75, 0x08,         //     Report Size (8),
+            0x95, cells,        //     Report Count (cells),
+            0x91, 0x02,         //     Output (Data, Variable, Absolute),
+            0xC0,               //   End Collection,
+            0xC0,               // End Collection
+        ];
+        (bytes, len) = append(bytes, len, &footer);
+
+        Self { bytes, len, cells }
+    }
+
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+}
+
+/// An 8 dot braille cell, bit 0 is dot 1 and bit 7 is dot 8
+///
+/// Uses the same dot numbering as the Unicode braille patterns block (U+2800).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct BrailleCell(pub u8);
+
+impl BrailleCell {
+    pub const BLANK: Self = Self(0);
+
+    /// Create a cell from dot numbers, 1 to 8, other numbers are ignored
+    #[must_use]
+    pub fn from_dots(dots: &[u8]) -> Self {
+        Self(
+            dots.iter()
+                .filter(|&&d| (1..=8).contains(&d))
+                .fold(0, |cell, d| cell | (1 << (d - 1))),
+        )
+    }
+
+    /// Create a cell from a Unicode braille pattern, `None` for other characters
+    #[must_use]
+    pub fn from_char(c: char) -> Option<Self> {
+        u32::from(c)
+            .checked_sub(0x2800)
+            .and_then(|dots| u8::try_from(dots).ok())
+            .map(Self)
+    }
+
+    /// Returns `true` if dot `dot`, 1 to 8, is raised
+    #[must_use]
+    pub fn dot(self, dot: u8) -> bool {
+        (1..=8).contains(&dot) && self.0 & (1 << (dot - 1)) != 0
+    }
+
+    /// The Unicode braille pattern of the cell
+    #[must_use]
+    pub fn to_char(self) -> char {
+        unwrap!(char::from_u32(0x2800 + u32::from(self.0)))
+    }
+}
+
+/// Space and navigation keys
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct BrailleButtons {
+    #[packed_field(bits = "0")]
+    pub space: bool,
+    #[packed_field(bits = "1")]
+    pub left_space: bool,
+    #[packed_field(bits = "2")]
+    pub right_space: bool,
+    #[packed_field(bits = "3")]
+    pub pan_left: bool,
+    #[packed_field(bits = "4")]
+    pub pan_right: bool,
+    #[packed_field(bits = "5")]
+    pub rocker_up: bool,
+    #[packed_field(bits = "6")]
+    pub rocker_down: bool,
+}
+
+/// Keys held on the display
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct BrailleKeys {
+    /// Braille keyboard dot keys
+    pub dots: BrailleCell,
+    pub buttons: BrailleButtons,
+    /// Router keys, bit n is the key above cell n
+    pub routers: u64,
+}
+
+impl BrailleKeys {
+    /// Copy of the keys with the router key above `cell` pressed
+    #[must_use]
+    pub fn with_router(mut self, cell: u8) -> Self {
+        if cell < MAX_BRAILLE_CELLS {
+            self.routers |= 1 << cell;
+        }
+        self
+    }
+}
+
+/// Interface implementing a braille display described by a [`BrailleDescriptor`]
+pub struct BrailleDisplay<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes16, OutBytes64, ReportSingle>,
+    cells: u8,
+}
+
+impl<B: UsbBus> BrailleDisplay<'_, B> {
+    /// Send the keys currently held, router keys beyond the number of cells are ignored
+    pub fn write_keys(&mut self, keys: &BrailleKeys) -> Result<(), UsbHidError> {
+        let [buttons] = keys.buttons.pack().map_err(|_| {
+            error!("Error packing BrailleButtons");
+            UsbHidError::SerializationError
+        })?;
+        let router_bytes = usize::from(self.cells.div_ceil(8));
+
+        let mut report = [0u8; MAX_INPUT_REPORT_SIZE];
+        report[0] = keys.dots.0;
+        report[1] = buttons;
+        report[2..2 + router_bytes].copy_from_slice(&keys.routers.to_le_bytes()[..router_bytes]);
+        // Clear the router bits beyond the last cell
+        let last_routers = self.cells % 8;
+        if last_routers != 0 {
+            report[1 + router_bytes] &= (1 << last_routers) - 1;
+        }
+
+        self.interface
+            .write_report(&report[..2 + router_bytes])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+
+    /// Read the cells written by the host into `cells`, returning the number of cells read
+    ///
+    /// Returns [`usb_device::UsbError::WouldBlock`] if the host has not written the cells since
+    /// the last read.
+    pub fn read_cells(&mut self, cells: &mut [BrailleCell]) -> usb_device::Result<usize> {
+        let mut data = [0u8; MAX_BRAILLE_CELLS as usize];
+        let len = self.interface.read_report(&mut data)?;
+        let len = len.min(cells.len());
+        for (cell, &dots) in cells.iter_mut().zip(&data[..len]) {
+            *cell = BrailleCell(dots);
+        }
+        Ok(len)
+    }
+
+    /// Number of cells on the display
+    #[must_use]
+    pub fn cells(&self) -> u8 {
+        self.cells
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for BrailleDisplay<'a, B> {
+    type I = Interface<'a, B, InBytes16, OutBytes64, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+}
+
+pub struct BrailleDisplayConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes16, OutBytes64, ReportSingle>,
+    cells: u8,
+}
+
+impl<'a> BrailleDisplayConfig<'a> {
+    /// Create a configuration using `descriptor`
+    #[must_use]
+    pub fn new(descriptor: &'a BrailleDescriptor) -> Self {
+        Self::with_interface(
+            unwrap!(
+                unwrap!(unwrap!(InterfaceBuilder::new(descriptor.as_bytes()))
+                    .description("Braille Display")
+                    .in_endpoint(10.millis()))
+                .with_out_endpoint(10.millis())
+            )
+            .build(),
+            descriptor,
+        )
+    }
+
+    /// Create a configuration from an interface built with `descriptor`
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes16, OutBytes64, ReportSingle>,
+        descriptor: &BrailleDescriptor,
+    ) -> Self {
+        Self {
+            interface,
+            cells: descriptor.cells,
+        }
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for BrailleDisplayConfig<'a> {
+    type Allocated = BrailleDisplay<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            cells: self.cells,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+    use usb_device::UsbError;
+
+    #[test]
+    fn descriptor_cell_counts() {
+        let descriptor = BrailleDescriptor::new(40);
+        let bytes = descriptor.as_bytes();
+        assert_eq!(descriptor.cells, 40);
+        assert!(!bytes.windows(4).any(|w| w == [0x95, 0x00, 0x81, 0x03]));
+        // A Braille Row of 8 Dot Braille Cells
+        assert!(bytes.ends_with(&[
+            0xC0, 0x09, 0x02, 0xA1, 0x02, 0x09, 0x03, 0x26, 0xFF, 0x00, 0x75, 0x08, 0x95, 40, 0x91,
+            0x02, 0xC0, 0xC0
+        ]));
+
+        let descriptor = BrailleDescriptor::new(14);
+        assert!(descriptor
+            .as_bytes()
+            .windows(4)
+            .any(|w| w == [0x95, 0x02, 0x81, 0x03]));
+
+        assert_eq!(BrailleDescriptor::new(0).cells, 1);
+        let descriptor = BrailleDescriptor::new(200);
+        assert_eq!(descriptor.cells, MAX_BRAILLE_CELLS);
+        assert!(descriptor.as_bytes().len() <= MAX_DESCRIPTOR_LEN);
+    }
+
+    #[test]
+    fn cells_match_unicode_patterns() {
+        let cell = BrailleCell::from_dots(&[1, 2, 5]);
+        assert_eq!(cell, BrailleCell(0x13));
+        assert_eq!(cell.to_char(), '⠓');
+        assert_eq!(BrailleCell::from_char('⠓'), Some(cell));
+        assert_eq!(BrailleCell::from_char('h'), None);
+        assert!(cell.dot(5));
+        assert!(!cell.dot(3));
+        assert!(!cell.dot(9));
+    }
+
+    #[test]
+    fn braille_display_cells_and_keys() {
+        static DESCRIPTOR: BrailleDescriptor = BrailleDescriptor::new(12);
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(BrailleDisplayConfig::new(&DESCRIPTOR))
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        let display = hid.device();
+        assert_eq!(display.cells(), 12);
+
+        let mut cells = [BrailleCell::BLANK; 12];
+        assert_eq!(display.read_cells(&mut cells), Err(UsbError::WouldBlock));
+
+        let mut report = [0u8; 12];
+        report[0] = 0x13;
+        report[11] = 0xFF;
+        manager.host_write_out(&report).unwrap();
+        assert_eq!(display.read_cells(&mut cells), Ok(12));
+        assert_eq!(cells[0].to_char(), '⠓');
+        assert_eq!(cells[1], BrailleCell::BLANK);
+        assert_eq!(cells[11], BrailleCell(0xFF));
+
+        // Router keys beyond the last cell are dropped
+        let keys = BrailleKeys {
+            dots: BrailleCell::from_dots(&[1, 4]),
+            buttons: BrailleButtons {
+                pan_right: true,
+                ..Default::default()
+            },
+            routers: 0,
+        }
+        .with_router(0)
+        .with_router(11)
+        .with_router(12);
+        display.write_keys(&keys).unwrap();
+        assert_eq!(manager.host_read_in(), [0x09, 0x10, 0x01, 0x08]);
+    }
+}
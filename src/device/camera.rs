@@ -0,0 +1,210 @@
+//!HID camera control, remote shutter and focus buttons accepted by camera apps
+
+use fugit::ExtU32;
+use packed_struct::prelude::*;
+#[allow(clippy::wildcard_imports)]
+use usb_device::class_prelude::*;
+
+use crate::usb_class::prelude::*;
+
+///Camera control report descriptor - Four buttons as a bitmap (1 byte)
+///
+/// * Bit 0 - Camera Auto-focus (Camera Control page 0x90)
+/// * Bit 1 - Camera Shutter (Camera Control page 0x90)
+/// * Bit 2 - Snapshot (Consumer page 0x0C)
+/// * Bit 3 - Record (Consumer page 0x0C)
+#[rustfmt::skip]
+pub const CAMERA_CONTROL_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0C, // Usage Page (Consumer),
+    0x09, 0x01, // Usage (Consumer Control),
+    0xA1, 0x01, // Collection (Application),
+    0x0B, 0x20, 0x00, 0x90, 0x00, //     Usage (Camera Control: Camera Auto-focus)
+    0x0B, 0x21, 0x00, 0x90, 0x00, //     Usage (Camera Control: Camera Shutter)
+    0x09, 0x65, //     Usage (Snapshot)
+    0x09, 0xB2, //     Usage (Record)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x25, 0x01, //     Logical Maximum (1)
+    0x75, 0x01, //     Report Size (1)
+    0x95, 0x04, //     Report Count (4)
+    0x81, 0x02, //     Input (Data, Variable, Absolute)
+    0x95, 0x04, //     Report Count (4)
+    0x81, 0x03, //     Input (Constant, Variable, Absolute)
+    0xC0, // End Collection
+];
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct CameraControlReport {
+    #[packed_field(bits = "0")]
+    pub auto_focus: bool,
+    #[packed_field(bits = "1")]
+    pub shutter: bool,
+    #[packed_field(bits = "2")]
+    pub snapshot: bool,
+    #[packed_field(bits = "3")]
+    pub record: bool,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CameraButton {
+    AutoFocus,
+    Shutter,
+    Snapshot,
+    Record,
+}
+
+impl CameraControlReport {
+    /// Copy of the report with `button` pressed
+    #[must_use]
+    pub fn with_button(mut self, button: CameraButton) -> Self {
+        match button {
+            CameraButton::AutoFocus => self.auto_focus = true,
+            CameraButton::Shutter => self.shutter = true,
+            CameraButton::Snapshot => self.snapshot = true,
+            CameraButton::Record => self.record = true,
+        }
+        self
+    }
+}
+
+/// Interface implementing a HID camera control, e.g. a remote shutter release
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, it repeats the held buttons at
+/// the host's idle rate and sends the release of [`CameraControl::tap()`].
+pub struct CameraControl<'a, B: UsbBus> {
+    interface: ManagedIdleInterface<'a, B, CameraControlReport, InBytes8, OutNone>,
+}
+
+impl<B: UsbBus> CameraControl<'_, B> {
+    /// Set the currently held buttons, e.g. a half-pressed shutter holding auto-focus
+    pub fn write_report(&mut self, report: &CameraControlReport) -> Result<(), UsbHidError> {
+        self.interface.write_report(report)
+    }
+
+    /// Press and release a button, any held buttons stay pressed
+    ///
+    /// The release is sent from a later call to [`UsbHidClass::tick()`], so the host never
+    /// sees a stuck button. Returns [`UsbHidError::Duplicate`] without sending anything if the
+    /// button is already held.
+    pub fn tap(&mut self, button: CameraButton) -> Result<(), UsbHidError> {
+        let report = self
+            .interface
+            .last_report()
+            .unwrap_or_default()
+            .with_button(button);
+        self.interface.write_momentary_report(&report)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for CameraControl<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        self.interface.interface()
+    }
+
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
+    }
+}
+
+pub struct CameraControlConfig<'a> {
+    interface: ManagedIdleInterfaceConfig<'a, CameraControlReport, InBytes8, OutNone>,
+}
+
+impl<'a> CameraControlConfig<'a> {
+    #[must_use]
+    pub fn new(
+        interface: ManagedIdleInterfaceConfig<'a, CameraControlReport, InBytes8, OutNone>,
+    ) -> Self {
+        Self { interface }
+    }
+}
+
+impl Default for CameraControlConfig<'_> {
+    fn default() -> Self {
+        Self::new(ManagedIdleInterfaceConfig::new(
+            unwrap!(unwrap!(
+                unwrap!(InterfaceBuilder::new(CAMERA_CONTROL_REPORT_DESCRIPTOR))
+                    .description("Camera Control")
+                    .idle_default(500.millis())
+            )
+            .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+        ))
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for CameraControlConfig<'a> {
+    type Allocated = CameraControl<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: self.interface.allocate(usb_alloc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn camera_control_report_packs_buttons() {
+        let report = CameraControlReport::default().with_button(CameraButton::Shutter);
+        assert_eq!(report.pack().unwrap(), [0x02]);
+
+        let report = report
+            .with_button(CameraButton::AutoFocus)
+            .with_button(CameraButton::Record);
+        assert_eq!(report.pack().unwrap(), [0x0B]);
+        assert_eq!(
+            CameraControlReport::default()
+                .with_button(CameraButton::Snapshot)
+                .pack()
+                .unwrap(),
+            [0x04]
+        );
+    }
+
+    #[test]
+    fn camera_tap_keeps_held_auto_focus() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(CameraControlConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_report(&CameraControlReport::default().with_button(CameraButton::AutoFocus))
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x01]);
+
+        hid.device().tap(CameraButton::Shutter).unwrap();
+        assert_eq!(manager.host_read_in(), [0x03]);
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x01]);
+
+        // The held button can't be tapped
+        assert!(matches!(
+            hid.device().tap(CameraButton::AutoFocus),
+            Err(UsbHidError::Duplicate)
+        ));
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+    }
+}
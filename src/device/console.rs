@@ -0,0 +1,294 @@
+//! HID debug console, compatible with `hid_listen` and the QMK console
+//!
+//! Text written to a [`ConsoleBuffer`] is streamed to the host in 32 byte input reports. With
+//! the `log` feature enabled the buffer is also a [`log::Log`] implementation, for example:
+//! ```rust, ignore
+//! static CONSOLE: ConsoleBuffer<1024> = ConsoleBuffer::new();
+//!
+//! log::set_logger(&CONSOLE).unwrap();
+//! log::set_max_level(log::LevelFilter::Info);
+//!
+//! let mut hid = UsbHidClassBuilder::new()
+//!     .add_device(ConsoleConfig::new(&CONSOLE))
+//!     .build(&usb_alloc);
+//! ```
+//!
+//! Requires the `console` feature.
+use core::cell::RefCell;
+use core::fmt::Write;
+
+use critical_section::Mutex;
+use fugit::ExtU32;
+use heapless::Deque;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+use crate::usb_class::prelude::*;
+
+/// Size of a console report
+pub const CONSOLE_REPORT_SIZE: usize = 32;
+
+/// Console report descriptor, vendor usage page 0xFF31 and usage 0x74 as expected by
+/// `hid_listen`
+#[rustfmt::skip]
+pub const CONSOLE_REPORT_DESCRIPTOR: &[u8] = &[
+    0x06, 0x31, 0xFF, // Usage Page (Vendor Defined - PJRC Teensy compatible),
+    0x09, 0x74, // Usage (Vendor Defined - PJRC Teensy compatible),
+    0xA1, 0x01, // Collection (Application),
+    0x09, 0x75, //     Usage (Vendor Defined - PJRC Teensy compatible),
+    0x15, 0x00, //     Logical Minimum (0),
+    0x26, 0xFF, 0x00, //     Logical Maximum (255),
+    0x95, 0x20, //     Report Count (32),
+    0x75, 0x08, //     Report Size (8),
+    0x81, 0x02, //     Input (Data, Variable, Absolute),
+    0xC0,       // End Collection
+];
+
+/// Ring buffer of console output waiting to be sent to the host
+///
+/// Can be written from any context, writes are dropped once the buffer is full.
+pub struct ConsoleBuffer<const N: usize> {
+    buffer: Mutex<RefCell<Deque<u8, N>>>,
+}
+
+impl<const N: usize> ConsoleBuffer<N> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            buffer: Mutex::new(RefCell::new(Deque::new())),
+        }
+    }
+
+    /// Append `data` to the buffer, returning the number of bytes written
+    pub fn write(&self, data: &[u8]) -> usize {
+        critical_section::with(|cs| {
+            let mut buffer = self.buffer.borrow_ref_mut(cs);
+            data.iter()
+                .take_while(|&&b| buffer.push_back(b).is_ok())
+                .count()
+        })
+    }
+
+    /// Remove up to `data.len()` bytes from the buffer, returning the number of bytes read
+    pub fn read(&self, data: &mut [u8]) -> usize {
+        critical_section::with(|cs| {
+            let mut buffer = self.buffer.borrow_ref_mut(cs);
+            let mut len = 0;
+            while len < data.len() {
+                let Some(b) = buffer.pop_front() else {
+                    break;
+                };
+                data[len] = b;
+                len += 1;
+            }
+            len
+        })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        critical_section::with(|cs| self.buffer.borrow_ref(cs).is_empty())
+    }
+}
+
+impl<const N: usize> Default for ConsoleBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Write for &ConsoleBuffer<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "log")]
+impl<const N: usize> log::Log for ConsoleBuffer<N> {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut writer = self;
+        let _ = writeln!(
+            writer,
+            "{:<5} [{}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Interface streaming the contents of a [`ConsoleBuffer`] to the host
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, a report is sent each tick while
+/// the buffer has data.
+pub struct Console<'a, B: UsbBus, const N: usize> {
+    interface: Interface<'a, B, InBytes32, OutNone, ReportSingle>,
+    buffer: &'a ConsoleBuffer<N>,
+    pending: Option<[u8; CONSOLE_REPORT_SIZE]>,
+}
+
+impl<B: UsbBus, const N: usize> Console<'_, B, N> {
+    /// Append `data` to the console buffer, returning the number of bytes written
+    #[allow(clippy::must_use_candidate)]
+    pub fn write(&self, data: &[u8]) -> usize {
+        self.buffer.write(data)
+    }
+}
+
+impl<'a, B: UsbBus, const N: usize> DeviceClass<'a> for Console<'a, B, N> {
+    type I = Interface<'a, B, InBytes32, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        let report = if let Some(report) = self.pending {
+            report
+        } else {
+            let mut report = [0u8; CONSOLE_REPORT_SIZE];
+            if self.buffer.read(&mut report) == 0 {
+                return Ok(());
+            }
+            report
+        };
+
+        match self.interface.write_report(&report) {
+            Ok(_) => {
+                self.pending = None;
+                Ok(())
+            }
+            //retry on the next tick
+            Err(UsbError::WouldBlock) => {
+                self.pending = Some(report);
+                Ok(())
+            }
+            Err(e) => Err(UsbHidError::UsbError(e)),
+        }
+    }
+}
+
+pub struct ConsoleConfig<'a, const N: usize> {
+    interface: InterfaceConfig<'a, InBytes32, OutNone, ReportSingle>,
+    buffer: &'a ConsoleBuffer<N>,
+}
+
+impl<'a, const N: usize> ConsoleConfig<'a, N> {
+    #[must_use]
+    pub fn new(buffer: &'a ConsoleBuffer<N>) -> Self {
+        Self::with_interface(
+            unwrap!(unwrap!(InterfaceBuilder::new(CONSOLE_REPORT_DESCRIPTOR))
+                .description("Console")
+                .in_endpoint(1.millis()))
+            .without_out_endpoint()
+            .build(),
+            buffer,
+        )
+    }
+
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes32, OutNone, ReportSingle>,
+        buffer: &'a ConsoleBuffer<N>,
+    ) -> Self {
+        Self { interface, buffer }
+    }
+}
+
+impl<'a, B: UsbBus + 'a, const N: usize> UsbAllocatable<'a, B> for ConsoleConfig<'a, N> {
+    type Allocated = Console<'a, B, N>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            buffer: self.buffer,
+            pending: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn buffer_drops_writes_when_full() {
+        let buffer = ConsoleBuffer::<8>::new();
+        assert_eq!(buffer.write(b"hello"), 5);
+        assert_eq!(buffer.write(b" world"), 3);
+
+        let mut data = [0u8; 6];
+        assert_eq!(buffer.read(&mut data), 6);
+        assert_eq!(&data, b"hello ");
+        assert_eq!(buffer.read(&mut data), 2);
+        assert_eq!(&data[..2], b"wo");
+        assert!(buffer.is_empty());
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn log_record_format() {
+        use log::Log;
+
+        let buffer = ConsoleBuffer::<64>::new();
+        buffer.log(
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .target("app")
+                .args(format_args!("x={}", 42))
+                .build(),
+        );
+
+        let mut data = [0u8; 64];
+        let len = buffer.read(&mut data);
+        assert_eq!(&data[..len], b"WARN  [app] x=42\n");
+    }
+
+    #[test]
+    fn console_streams_buffer() {
+        static CONSOLE: ConsoleBuffer<64> = ConsoleBuffer::new();
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(ConsoleConfig::new(&CONSOLE))
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty());
+
+        let text = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(hid.device().write(text), text.len());
+
+        // Second report is held until the first has been read
+        hid.tick().unwrap();
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), text[..32]);
+
+        hid.tick().unwrap();
+        let mut expected = Vec::from(&text[32..]);
+        expected.resize(32, 0);
+        assert_eq!(manager.host_read_in(), expected);
+        assert!(CONSOLE.is_empty());
+    }
+}
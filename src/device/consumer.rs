@@ -2,13 +2,17 @@
 
 use fugit::ExtU32;
 use packed_struct::prelude::*;
+use packed_struct::{PackedStructInfo, PackingResult};
 #[allow(clippy::wildcard_imports)]
 use usb_device::class_prelude::*;
+use usb_device::UsbError;
 
 use crate::page::Consumer;
 use crate::usb_class::prelude::*;
 
 ///Consumer control report descriptor - Four `u16` consumer control usage codes as an array (8 bytes)
+///
+/// Covers the full 16-bit Consumer usage range
 #[rustfmt::skip]
 pub const MULTIPLE_CODE_REPORT_DESCRIPTOR: &[u8] = &[
     0x05, 0x0C, // Usage Page (Consumer),
@@ -17,19 +21,64 @@ pub const MULTIPLE_CODE_REPORT_DESCRIPTOR: &[u8] = &[
     0x75, 0x10, //     Report Size(16)
     0x95, 0x04, //     Report Count(4)
     0x15, 0x00, //     Logical Minimum(0)
-    0x26, 0x9C, 0x02, //     Logical Maximum(0x029C)
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //     Logical Maximum(0xFFFF)
     0x19, 0x00, //     Usage Minimum(0)
-    0x2A, 0x9C, 0x02, //     Usage Maximum(0x029C)
+    0x2A, 0xFF, 0xFF, //     Usage Maximum(0xFFFF)
     0x81, 0x00, //     Input (Array, Data, Variable)
     0xC0, // End Collection
 ];
 
+/// Raw 16-bit Consumer page usage code, including usages not listed in [`Consumer`]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, Hash)]
+pub struct ConsumerCode(pub u16);
+
+impl ConsumerCode {
+    pub const UNASSIGNED: Self = Self(0);
+}
+
+impl From<Consumer> for ConsumerCode {
+    fn from(code: Consumer) -> Self {
+        Self(code.into())
+    }
+}
+
+impl From<u16> for ConsumerCode {
+    fn from(code: u16) -> Self {
+        Self(code)
+    }
+}
+
+impl From<ConsumerCode> for u16 {
+    fn from(code: ConsumerCode) -> Self {
+        code.0
+    }
+}
+
+impl PackedStruct for ConsumerCode {
+    type ByteArray = [u8; 2];
+
+    fn pack(&self) -> PackingResult<Self::ByteArray> {
+        Ok(self.0.to_le_bytes())
+    }
+
+    fn unpack(src: &Self::ByteArray) -> PackingResult<Self> {
+        Ok(Self(u16::from_le_bytes(*src)))
+    }
+}
+
+impl PackedStructInfo for ConsumerCode {
+    fn packed_bits() -> usize {
+        16
+    }
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
 #[packed_struct(endian = "lsb", size_bytes = "8")]
 pub struct MultipleConsumerReport {
-    #[packed_field(ty = "enum", element_size_bytes = "2")]
-    pub codes: [Consumer; 4],
+    #[packed_field(element_size_bytes = "2")]
+    pub codes: [ConsumerCode; 4],
 }
 
 #[allow(clippy::doc_markdown)]
@@ -70,7 +119,7 @@ pub const FIXED_FUNCTION_REPORT_DESCRIPTOR: &[u8] = &[
 ];
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Clone, Copy, Debug, Eq, PartialEq, PackedStruct)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
 #[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
 pub struct FixedFunctionReport {
     #[packed_field(bits = "0")]
@@ -89,17 +138,39 @@ pub struct FixedFunctionReport {
     pub volume_decrement: bool,
 }
 
+/// Interface implementing a generic consumer control with up to four simultaneous usages
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, it repeats the held usages at
+/// the host's idle rate and sends the release of [`ConsumerControl::tap()`].
 pub struct ConsumerControl<'a, B: UsbBus> {
-    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    interface: ManagedIdleInterface<'a, B, MultipleConsumerReport, InBytes8, OutNone>,
 }
 
 impl<B: UsbBus> ConsumerControl<'_, B> {
-    pub fn write_report(&mut self, report: &MultipleConsumerReport) -> usb_device::Result<usize> {
-        let data = report.pack().map_err(|_| {
-            error!("Error packing MultipleConsumerReport");
-            UsbError::ParseError
-        })?;
-        self.interface.write_report(&data)
+    pub fn write_report(&mut self, report: &MultipleConsumerReport) -> Result<(), UsbHidError> {
+        self.interface.write_report(report)
+    }
+
+    /// Press and release a usage alongside any usages held by the last report
+    ///
+    /// The release is sent from a later call to [`UsbHidClass::tick()`]. Returns
+    /// [`UsbHidError::Duplicate`] without sending anything if the usage is already held, or
+    /// [`UsbHidError::WouldBlock`] if all four usages are already held.
+    pub fn tap(&mut self, code: impl Into<ConsumerCode>) -> Result<(), UsbHidError> {
+        let code = code.into();
+        let mut report = self.interface.last_report().unwrap_or_default();
+        if report.codes.contains(&code) {
+            return Err(UsbHidError::Duplicate);
+        }
+        let Some(slot) = report
+            .codes
+            .iter_mut()
+            .find(|c| **c == ConsumerCode::UNASSIGNED)
+        else {
+            return Err(UsbHidError::WouldBlock);
+        };
+        *slot = code;
+        self.interface.write_momentary_report(&report)
     }
 }
 
@@ -107,38 +178,43 @@ impl<'a, B: UsbBus> DeviceClass<'a> for ConsumerControl<'a, B> {
     type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
 
     fn interface(&mut self) -> &mut Self::I {
-        &mut self.interface
+        self.interface.interface()
     }
 
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
 
-    fn tick(&mut self) -> Result<(), crate::UsbHidError> {
-        Ok(())
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
     }
 }
 
 pub struct ConsumerControlConfig<'a> {
-    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    interface: ManagedIdleInterfaceConfig<'a, MultipleConsumerReport, InBytes8, OutNone>,
 }
 
 impl<'a> ConsumerControlConfig<'a> {
     #[must_use]
-    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
+    pub fn new(
+        interface: ManagedIdleInterfaceConfig<'a, MultipleConsumerReport, InBytes8, OutNone>,
+    ) -> Self {
         Self { interface }
     }
 }
 
 impl Default for ConsumerControlConfig<'_> {
     fn default() -> Self {
-        Self::new(
-            unwrap!(
+        Self::new(ManagedIdleInterfaceConfig::new(
+            unwrap!(unwrap!(
                 unwrap!(InterfaceBuilder::new(MULTIPLE_CODE_REPORT_DESCRIPTOR))
                     .description("Consumer Control")
-                    .in_endpoint(50.millis())
+                    .idle_default(500.millis())
             )
+            .in_endpoint(10.millis()))
             .without_out_endpoint()
             .build(),
-        )
+        ))
     }
 }
 
@@ -147,22 +223,42 @@ impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for ConsumerControlConfig<'a> {
 
     fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
         Self::Allocated {
-            interface: Interface::new(usb_alloc, self.interface),
+            interface: self.interface.allocate(usb_alloc),
         }
     }
 }
 
+/// Interface implementing a fixed function media control
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, it repeats the held controls at
+/// the host's idle rate and sends the release of [`ConsumerControlFixed::tap()`].
 pub struct ConsumerControlFixed<'a, B: UsbBus> {
-    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    interface: ManagedIdleInterface<'a, B, FixedFunctionReport, InBytes8, OutNone>,
 }
 
 impl<B: UsbBus> ConsumerControlFixed<'_, B> {
-    pub fn write_report(&mut self, report: &FixedFunctionReport) -> usb_device::Result<usize> {
-        let data = report.pack().map_err(|_| {
-            error!("Error packing MultipleConsumerReport");
-            UsbError::ParseError
-        })?;
-        self.interface.write_report(&data)
+    pub fn write_report(&mut self, report: &FixedFunctionReport) -> Result<(), UsbHidError> {
+        self.interface.write_report(report)
+    }
+
+    /// Press and release a usage alongside any usages held by the last report
+    ///
+    /// The release is sent from a later call to [`UsbHidClass::tick()`]. Returns
+    /// [`UsbError::Unsupported`] for usages not included in [`FIXED_FUNCTION_REPORT_DESCRIPTOR`],
+    /// or [`UsbHidError::Duplicate`] without sending anything if the usage is already held.
+    pub fn tap(&mut self, code: impl Into<ConsumerCode>) -> Result<(), UsbHidError> {
+        let mut report = self.interface.last_report().unwrap_or_default();
+        match Consumer::from(u16::from(code.into())) {
+            Consumer::ScanNextTrack => report.next = true,
+            Consumer::ScanPreviousTrack => report.previous = true,
+            Consumer::Stop => report.stop = true,
+            Consumer::PlayPause => report.play_pause = true,
+            Consumer::Mute => report.mute = true,
+            Consumer::VolumeIncrement => report.volume_increment = true,
+            Consumer::VolumeDecrement => report.volume_decrement = true,
+            _ => return Err(UsbHidError::UsbError(UsbError::Unsupported)),
+        }
+        self.interface.write_momentary_report(&report)
     }
 }
 
@@ -170,37 +266,43 @@ impl<'a, B: UsbBus> DeviceClass<'a> for ConsumerControlFixed<'a, B> {
     type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
 
     fn interface(&mut self) -> &mut Self::I {
-        &mut self.interface
+        self.interface.interface()
     }
 
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
 
-    fn tick(&mut self) -> Result<(), crate::UsbHidError> {
-        Ok(())
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
     }
 }
 
 pub struct ConsumerControlFixedConfig<'a> {
-    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    interface: ManagedIdleInterfaceConfig<'a, FixedFunctionReport, InBytes8, OutNone>,
 }
+
 impl<'a> ConsumerControlFixedConfig<'a> {
     #[must_use]
-    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
+    pub fn new(
+        interface: ManagedIdleInterfaceConfig<'a, FixedFunctionReport, InBytes8, OutNone>,
+    ) -> Self {
         Self { interface }
     }
 }
 
 impl Default for ConsumerControlFixedConfig<'_> {
     fn default() -> Self {
-        Self::new(
-            unwrap!(
+        Self::new(ManagedIdleInterfaceConfig::new(
+            unwrap!(unwrap!(
                 unwrap!(InterfaceBuilder::new(FIXED_FUNCTION_REPORT_DESCRIPTOR))
                     .description("Consumer Control")
-                    .in_endpoint(50.millis())
+                    .idle_default(500.millis())
             )
+            .in_endpoint(10.millis()))
             .without_out_endpoint()
             .build(),
-        )
+        ))
     }
 }
 
@@ -209,7 +311,135 @@ impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for ConsumerControlFixedConfig<'a
 
     fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
         Self::Allocated {
-            interface: Interface::new(usb_alloc, self.interface),
+            interface: self.interface.allocate(usb_alloc),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn multiple_consumer_report_packs_16_bit_usages() {
+        let report = MultipleConsumerReport {
+            codes: [
+                Consumer::PlayPause.into(),
+                Consumer::ACDesktopShowAllApplications.into(),
+                ConsumerCode(0xFFF0),
+                ConsumerCode::UNASSIGNED,
+            ],
+        };
+        assert_eq!(
+            report.pack().unwrap(),
+            [0xCD, 0x00, 0xA2, 0x02, 0xF0, 0xFF, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn consumer_tap_preserves_held_codes() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(ConsumerControlConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_report(&MultipleConsumerReport {
+                codes: [
+                    Consumer::Mute.into(),
+                    ConsumerCode::UNASSIGNED,
+                    ConsumerCode::UNASSIGNED,
+                    ConsumerCode::UNASSIGNED,
+                ],
+            })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0xE2, 0, 0, 0, 0, 0, 0, 0]);
+
+        hid.device().tap(Consumer::ACKeyboardLayoutSelect).unwrap();
+        assert_eq!(manager.host_read_in(), [0xE2, 0, 0x9D, 0x02, 0, 0, 0, 0]);
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0xE2, 0, 0, 0, 0, 0, 0, 0]);
+
+        // Raw codes outside the Consumer enum
+        hid.device().tap(0x0300).unwrap();
+        assert_eq!(manager.host_read_in(), [0xE2, 0, 0x00, 0x03, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn consumer_tap_with_all_codes_held_would_block() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(ConsumerControlConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        let report = MultipleConsumerReport {
+            codes: [
+                Consumer::Mute.into(),
+                Consumer::PlayPause.into(),
+                Consumer::VolumeIncrement.into(),
+                Consumer::ScanNextTrack.into(),
+            ],
+        };
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), report.pack().unwrap());
+
+        assert!(matches!(
+            hid.device().tap(Consumer::Stop),
+            Err(UsbHidError::WouldBlock)
+        ));
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+
+        // Tapping an already held code sends nothing
+        assert!(matches!(
+            hid.device().tap(Consumer::Mute),
+            Err(UsbHidError::Duplicate)
+        ));
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+    }
+
+    #[test]
+    fn consumer_fixed_tap_held_usage_is_duplicate() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(ConsumerControlFixedConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_report(&FixedFunctionReport {
+                play_pause: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x08]);
+
+        assert!(matches!(
+            hid.device().tap(Consumer::PlayPause),
+            Err(UsbHidError::Duplicate)
+        ));
+        assert!(matches!(
+            hid.device().tap(Consumer::Eject),
+            Err(UsbHidError::UsbError(UsbError::Unsupported))
+        ));
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+
+        hid.device().tap(Consumer::Mute).unwrap();
+        assert_eq!(manager.host_read_in(), [0x18]);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x08]);
+    }
+}
@@ -0,0 +1,786 @@
+//! CTAPHID transport framing for FIDO authenticators
+//!
+//! Handles channel allocation, message fragmentation and reassembly, and the transport level
+//! commands, passing complete CTAP1 (U2F) and CTAP2 messages to a [`CtapHidApp`].
+//!
+//! See the [CTAP HID specification](https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#usb)
+//! for protocol detail
+use heapless::Deque;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use usb_device::bus::UsbBus;
+use usb_device::UsbError;
+
+use super::{RawFido, RawFidoReport};
+use crate::UsbHidError;
+
+/// Size of a CTAPHID packet
+pub const PACKET_SIZE: usize = 64;
+const INIT_DATA_SIZE: usize = PACKET_SIZE - 7;
+const CONT_DATA_SIZE: usize = PACKET_SIZE - 5;
+/// Largest message that can be sent in an initialization packet and 128 continuation packets
+pub const MAX_MESSAGE_SIZE: usize = INIT_DATA_SIZE + 128 * CONT_DATA_SIZE;
+/// Channel used by the host to allocate a channel with [`Command::Init`]
+pub const BROADCAST_CID: u32 = 0xFFFF_FFFF;
+/// Time allowed between the packets of a message before it is abandoned, in ms
+pub const TRANSACTION_TIMEOUT_MS: u16 = 500;
+/// Interval between [`Command::Keepalive`] packets while a message is being processed, in ms
+pub const KEEPALIVE_INTERVAL_MS: u16 = 100;
+
+/// Capability flag - the authenticator implements [`Command::Wink`]
+pub const CAPABILITY_WINK: u8 = 0x01;
+/// Capability flag - the authenticator implements [`Command::Cbor`]
+pub const CAPABILITY_CBOR: u8 = 0x04;
+/// Capability flag - the authenticator does not implement [`Command::Msg`]
+pub const CAPABILITY_NMSG: u8 = 0x08;
+
+const PROTOCOL_VERSION: u8 = 2;
+const INIT_NONCE_SIZE: usize = 8;
+const CTAP2_ERR_KEEPALIVE_CANCEL: u8 = 0x2D;
+const OUTPUT_QUEUE_SIZE: usize = 4;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum Command {
+    Ping = 0x01,
+    Msg = 0x03,
+    Lock = 0x04,
+    Init = 0x06,
+    Wink = 0x08,
+    Cbor = 0x10,
+    Cancel = 0x11,
+    Keepalive = 0x3B,
+    Error = 0x3F,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+pub enum ErrorCode {
+    InvalidCommand = 0x01,
+    InvalidParameter = 0x02,
+    InvalidLength = 0x03,
+    InvalidSequence = 0x04,
+    MessageTimeout = 0x05,
+    ChannelBusy = 0x06,
+    LockRequired = 0x0A,
+    InvalidChannel = 0x0B,
+    Other = 0x7F,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+pub enum KeepaliveStatus {
+    Processing = 0x01,
+    UserPresenceNeeded = 0x02,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MessageType {
+    /// CTAP1/U2F APDU, received with [`Command::Msg`]
+    Ctap1,
+    /// CTAP2 CBOR message, received with [`Command::Cbor`]
+    Ctap2,
+}
+
+/// Progress of a message handled by a [`CtapHidApp`]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AppStatus {
+    /// Processing is complete, with the given number of response bytes
+    Complete(usize),
+    /// Processing continues, [`CtapHidApp::poll_message`] is called every 1ms until complete
+    Pending(KeepaliveStatus),
+    /// Processing failed, a CTAPHID error is sent to the host
+    Error(ErrorCode),
+}
+
+/// Authenticator application receiving complete CTAP messages
+pub trait CtapHidApp {
+    /// Capability flags reported to the host, see [`CAPABILITY_WINK`], [`CAPABILITY_CBOR`] and
+    /// [`CAPABILITY_NMSG`]
+    fn capabilities(&self) -> u8 {
+        CAPABILITY_CBOR | CAPABILITY_NMSG
+    }
+
+    /// Major, minor and build device version numbers reported to the host
+    fn device_version(&self) -> [u8; 3] {
+        [0; 3]
+    }
+
+    /// Handle a complete request message, writing any response to `response`
+    fn handle_message(
+        &mut self,
+        message_type: MessageType,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> AppStatus;
+
+    /// Continue processing a message after [`AppStatus::Pending`] was returned
+    fn poll_message(&mut self, _response: &mut [u8]) -> AppStatus {
+        AppStatus::Error(ErrorCode::Other)
+    }
+
+    /// The host cancelled the message being processed
+    fn cancel(&mut self) {}
+
+    /// Identify the authenticator to the user, e.g. by flashing an LED
+    fn wink(&mut self) {}
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Idle,
+    Receiving {
+        cid: u32,
+        command: u8,
+        len: usize,
+        received: usize,
+        seq: u8,
+        timeout: u16,
+    },
+    Processing {
+        cid: u32,
+        command: Command,
+        keepalive: u16,
+    },
+    Sending {
+        cid: u32,
+        command: Command,
+        len: usize,
+        /// Index of the next packet, 0 being the initialization packet
+        packet: usize,
+    },
+}
+
+/// CTAPHID transport for a [`RawFido`] interface
+///
+/// Only one message is processed at a time, requests on other channels are rejected with
+/// [`ErrorCode::ChannelBusy`].
+///
+/// **Note:** [`CtapHid::tick()`] must be called every 1ms, and [`CtapHid::poll()`] after every
+/// USB poll.
+pub struct CtapHid<A: CtapHidApp> {
+    app: A,
+    state: State,
+    next_cid: u32,
+    output: Deque<[u8; PACKET_SIZE], OUTPUT_QUEUE_SIZE>,
+    request: [u8; MAX_MESSAGE_SIZE],
+    response: [u8; MAX_MESSAGE_SIZE],
+}
+
+impl<A: CtapHidApp> CtapHid<A> {
+    #[must_use]
+    pub fn new(app: A) -> Self {
+        Self {
+            app,
+            state: State::Idle,
+            next_cid: 1,
+            output: Deque::new(),
+            request: [0; MAX_MESSAGE_SIZE],
+            response: [0; MAX_MESSAGE_SIZE],
+        }
+    }
+
+    pub fn app(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    /// Read any packets received by `fido` and write any pending packets to it
+    pub fn poll<B: UsbBus>(&mut self, fido: &mut RawFido<'_, B>) -> Result<(), UsbHidError> {
+        loop {
+            match fido.read_report() {
+                Ok(report) => self.handle_report(&report),
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+
+        while let Some(packet) = self.peek_output() {
+            match fido.write_report(&RawFidoReport { packet }) {
+                Ok(()) => self.pop_output(),
+                //retry on the next poll
+                Err(UsbHidError::WouldBlock) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a packet received from the host
+    pub fn handle_report(&mut self, report: &RawFidoReport) {
+        let packet = &report.packet;
+        let cid = u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]);
+
+        if packet[4] & 0x80 == 0 {
+            self.handle_continuation(cid, packet[4], &packet[5..]);
+        } else {
+            let len = usize::from(u16::from_be_bytes([packet[5], packet[6]]));
+            self.handle_init(cid, packet[4] & 0x7F, len, &packet[7..]);
+        }
+    }
+
+    /// Advance transaction timeouts, keepalives and pending messages, call every 1ms
+    pub fn tick(&mut self) {
+        match self.state {
+            State::Receiving {
+                cid,
+                command,
+                len,
+                received,
+                seq,
+                timeout,
+            } => {
+                if timeout <= 1 {
+                    warn!("CTAPHID message timeout on channel {:X}", cid);
+                    self.state = State::Idle;
+                    self.error(cid, ErrorCode::MessageTimeout);
+                } else {
+                    self.state = State::Receiving {
+                        cid,
+                        command,
+                        len,
+                        received,
+                        seq,
+                        timeout: timeout - 1,
+                    };
+                }
+            }
+            State::Processing {
+                cid,
+                command,
+                keepalive,
+            } => match self.app.poll_message(&mut self.response) {
+                AppStatus::Pending(status) => {
+                    let keepalive = if keepalive <= 1 {
+                        self.queue(cid, Command::Keepalive, &[status.into()]);
+                        KEEPALIVE_INTERVAL_MS
+                    } else {
+                        keepalive - 1
+                    };
+                    self.state = State::Processing {
+                        cid,
+                        command,
+                        keepalive,
+                    };
+                }
+                status => self.app_status(cid, command, status),
+            },
+            State::Idle | State::Sending { .. } => {}
+        }
+    }
+
+    fn handle_init(&mut self, cid: u32, command: u8, len: usize, data: &[u8]) {
+        if cid == 0 {
+            return self.error(cid, ErrorCode::InvalidChannel);
+        }
+        if command == u8::from(Command::Init) {
+            return self.init(cid, len, data);
+        }
+        if cid == BROADCAST_CID || cid >= self.next_cid {
+            return self.error(cid, ErrorCode::InvalidChannel);
+        }
+        if command == u8::from(Command::Cancel) {
+            return self.cancel(cid);
+        }
+
+        match self.state {
+            State::Idle => {}
+            State::Receiving { cid: current, .. } if current == cid => {
+                self.state = State::Idle;
+                return self.error(cid, ErrorCode::InvalidSequence);
+            }
+            _ => return self.error(cid, ErrorCode::ChannelBusy),
+        }
+
+        if len > MAX_MESSAGE_SIZE {
+            return self.error(cid, ErrorCode::InvalidLength);
+        }
+
+        let received = len.min(INIT_DATA_SIZE);
+        self.request[..received].copy_from_slice(&data[..received]);
+        if received == len {
+            self.dispatch(cid, command, len);
+        } else {
+            self.state = State::Receiving {
+                cid,
+                command,
+                len,
+                received,
+                seq: 0,
+                timeout: TRANSACTION_TIMEOUT_MS,
+            };
+        }
+    }
+
+    fn handle_continuation(&mut self, cid: u32, seq: u8, data: &[u8]) {
+        let State::Receiving {
+            cid: current,
+            command,
+            len,
+            received,
+            seq: expected,
+            ..
+        } = self.state
+        else {
+            //ignore spurious continuation packets
+            return;
+        };
+
+        if current != cid {
+            return;
+        }
+        if seq != expected {
+            self.state = State::Idle;
+            return self.error(cid, ErrorCode::InvalidSequence);
+        }
+
+        let n = (len - received).min(CONT_DATA_SIZE);
+        self.request[received..received + n].copy_from_slice(&data[..n]);
+        if received + n == len {
+            self.dispatch(cid, command, len);
+        } else {
+            self.state = State::Receiving {
+                cid,
+                command,
+                len,
+                received: received + n,
+                seq: seq + 1,
+                timeout: TRANSACTION_TIMEOUT_MS,
+            };
+        }
+    }
+
+    fn init(&mut self, cid: u32, len: usize, nonce: &[u8]) {
+        if len != INIT_NONCE_SIZE {
+            return self.error(cid, ErrorCode::InvalidLength);
+        }
+        if cid != BROADCAST_CID && cid >= self.next_cid {
+            return self.error(cid, ErrorCode::InvalidChannel);
+        }
+
+        // Resynchronise the channel, abandoning any transaction in progress
+        match self.state {
+            State::Receiving { cid: current, .. } | State::Sending { cid: current, .. }
+                if current == cid =>
+            {
+                self.state = State::Idle;
+            }
+            State::Processing { cid: current, .. } if current == cid => {
+                self.app.cancel();
+                self.state = State::Idle;
+            }
+            _ => {}
+        }
+
+        let new_cid = if cid == BROADCAST_CID {
+            let new_cid = self.next_cid;
+            self.next_cid = self.next_cid.saturating_add(1).min(BROADCAST_CID - 1);
+            new_cid
+        } else {
+            cid
+        };
+
+        let [major, minor, build] = self.app.device_version();
+        let mut response = [0u8; 17];
+        response[..INIT_NONCE_SIZE].copy_from_slice(&nonce[..INIT_NONCE_SIZE]);
+        response[8..12].copy_from_slice(&new_cid.to_be_bytes());
+        response[12..].copy_from_slice(&[
+            PROTOCOL_VERSION,
+            major,
+            minor,
+            build,
+            self.app.capabilities(),
+        ]);
+        self.queue(cid, Command::Init, &response);
+    }
+
+    fn cancel(&mut self, cid: u32) {
+        match self.state {
+            State::Processing {
+                cid: current,
+                command,
+                ..
+            } if current == cid => {
+                self.app.cancel();
+                if command == Command::Cbor {
+                    self.response[0] = CTAP2_ERR_KEEPALIVE_CANCEL;
+                    self.send(cid, command, 1);
+                } else {
+                    self.state = State::Idle;
+                }
+            }
+            State::Receiving { cid: current, .. } if current == cid => {
+                self.state = State::Idle;
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self, cid: u32, command: u8, len: usize) {
+        self.state = State::Idle;
+        let capabilities = self.app.capabilities();
+
+        match Command::try_from(command) {
+            Ok(Command::Ping) => {
+                self.response[..len].copy_from_slice(&self.request[..len]);
+                self.send(cid, Command::Ping, len);
+            }
+            Ok(Command::Wink) if capabilities & CAPABILITY_WINK != 0 => {
+                self.app.wink();
+                self.send(cid, Command::Wink, 0);
+            }
+            Ok(Command::Msg) if capabilities & CAPABILITY_NMSG == 0 => {
+                let status = self.app.handle_message(
+                    MessageType::Ctap1,
+                    &self.request[..len],
+                    &mut self.response,
+                );
+                self.app_status(cid, Command::Msg, status);
+            }
+            Ok(Command::Cbor) if capabilities & CAPABILITY_CBOR != 0 => {
+                let status = self.app.handle_message(
+                    MessageType::Ctap2,
+                    &self.request[..len],
+                    &mut self.response,
+                );
+                self.app_status(cid, Command::Cbor, status);
+            }
+            _ => self.error(cid, ErrorCode::InvalidCommand),
+        }
+    }
+
+    fn app_status(&mut self, cid: u32, command: Command, status: AppStatus) {
+        match status {
+            AppStatus::Complete(len) => self.send(cid, command, len.min(MAX_MESSAGE_SIZE)),
+            AppStatus::Pending(_) => {
+                self.state = State::Processing {
+                    cid,
+                    command,
+                    keepalive: KEEPALIVE_INTERVAL_MS,
+                };
+            }
+            AppStatus::Error(code) => {
+                self.state = State::Idle;
+                self.error(cid, code);
+            }
+        }
+    }
+
+    fn send(&mut self, cid: u32, command: Command, len: usize) {
+        self.state = State::Sending {
+            cid,
+            command,
+            len,
+            packet: 0,
+        };
+    }
+
+    fn error(&mut self, cid: u32, code: ErrorCode) {
+        self.queue(cid, Command::Error, &[code.into()]);
+    }
+
+    /// Queue a single packet message
+    fn queue(&mut self, cid: u32, command: Command, data: &[u8]) {
+        let packet = init_packet(cid, command, data.len(), data);
+        if self.output.push_back(packet).is_err() {
+            warn!("CTAPHID output queue full, dropping {:?}", command);
+        }
+    }
+
+    fn peek_output(&self) -> Option<[u8; PACKET_SIZE]> {
+        if let Some(packet) = self.output.front() {
+            return Some(*packet);
+        }
+
+        let State::Sending {
+            cid,
+            command,
+            len,
+            packet,
+        } = self.state
+        else {
+            return None;
+        };
+
+        if packet == 0 {
+            let end = len.min(INIT_DATA_SIZE);
+            Some(init_packet(cid, command, len, &self.response[..end]))
+        } else {
+            let start = INIT_DATA_SIZE + (packet - 1) * CONT_DATA_SIZE;
+            let end = len.min(start + CONT_DATA_SIZE);
+            let mut data = [0u8; PACKET_SIZE];
+            data[..4].copy_from_slice(&cid.to_be_bytes());
+            #[allow(clippy::cast_possible_truncation)]
+            {
+                data[4] = (packet - 1) as u8;
+            }
+            data[5..5 + end - start].copy_from_slice(&self.response[start..end]);
+            Some(data)
+        }
+    }
+
+    fn pop_output(&mut self) {
+        if self.output.pop_front().is_some() {
+            return;
+        }
+
+        if let State::Sending {
+            cid,
+            command,
+            len,
+            packet,
+        } = self.state
+        {
+            let next = packet + 1;
+            if INIT_DATA_SIZE + packet * CONT_DATA_SIZE >= len {
+                self.state = State::Idle;
+            } else {
+                self.state = State::Sending {
+                    cid,
+                    command,
+                    len,
+                    packet: next,
+                };
+            }
+        }
+    }
+}
+
+fn init_packet(cid: u32, command: Command, len: usize, data: &[u8]) -> [u8; PACKET_SIZE] {
+    let mut packet = [0u8; PACKET_SIZE];
+    packet[..4].copy_from_slice(&cid.to_be_bytes());
+    packet[4] = u8::from(command) | 0x80;
+    #[allow(clippy::cast_possible_truncation)]
+    packet[5..7].copy_from_slice(&(len as u16).to_be_bytes());
+    packet[7..7 + data.len()].copy_from_slice(data);
+    packet
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestApp {
+        pending: bool,
+        cancelled: bool,
+        winks: u8,
+    }
+
+    impl CtapHidApp for TestApp {
+        fn capabilities(&self) -> u8 {
+            CAPABILITY_WINK | CAPABILITY_CBOR
+        }
+
+        fn device_version(&self) -> [u8; 3] {
+            [1, 2, 3]
+        }
+
+        fn handle_message(
+            &mut self,
+            message_type: MessageType,
+            request: &[u8],
+            response: &mut [u8],
+        ) -> AppStatus {
+            if self.pending {
+                return AppStatus::Pending(KeepaliveStatus::UserPresenceNeeded);
+            }
+            // Respond with the message type followed by the reversed request
+            response[0] = u8::from(message_type == MessageType::Ctap2);
+            for (r, q) in response[1..].iter_mut().zip(request.iter().rev()) {
+                *r = *q;
+            }
+            AppStatus::Complete(request.len() + 1)
+        }
+
+        fn poll_message(&mut self, response: &mut [u8]) -> AppStatus {
+            if self.pending {
+                AppStatus::Pending(KeepaliveStatus::UserPresenceNeeded)
+            } else {
+                response[0] = 0;
+                AppStatus::Complete(1)
+            }
+        }
+
+        fn cancel(&mut self) {
+            self.cancelled = true;
+            self.pending = false;
+        }
+
+        fn wink(&mut self) {
+            self.winks += 1;
+        }
+    }
+
+    fn ctaphid() -> Box<CtapHid<TestApp>> {
+        Box::new(CtapHid::new(TestApp::default()))
+    }
+
+    fn packet(bytes: &[u8]) -> RawFidoReport {
+        let mut report = RawFidoReport::default();
+        report.packet[..bytes.len()].copy_from_slice(bytes);
+        report
+    }
+
+    fn send_message(ctaphid: &mut CtapHid<TestApp>, cid: u32, command: u8, data: &[u8]) {
+        let mut header = Vec::from(cid.to_be_bytes());
+        header.push(command | 0x80);
+        header.extend_from_slice(&u16::try_from(data.len()).unwrap().to_be_bytes());
+        let (first, rest) = data.split_at(data.len().min(INIT_DATA_SIZE));
+        header.extend_from_slice(first);
+        ctaphid.handle_report(&packet(&header));
+
+        for (seq, chunk) in rest.chunks(CONT_DATA_SIZE).enumerate() {
+            let mut cont = Vec::from(cid.to_be_bytes());
+            cont.push(u8::try_from(seq).unwrap());
+            cont.extend_from_slice(chunk);
+            ctaphid.handle_report(&packet(&cont));
+        }
+    }
+
+    fn read_packets(ctaphid: &mut CtapHid<TestApp>) -> Vec<[u8; PACKET_SIZE]> {
+        let mut packets = Vec::new();
+        while let Some(p) = ctaphid.peek_output() {
+            ctaphid.pop_output();
+            packets.push(p);
+        }
+        packets
+    }
+
+    fn allocate_channel(ctaphid: &mut CtapHid<TestApp>) -> u32 {
+        send_message(ctaphid, BROADCAST_CID, 0x06, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let packets = read_packets(ctaphid);
+        u32::from_be_bytes(packets[0][15..19].try_into().unwrap())
+    }
+
+    #[test]
+    fn init_allocates_channel() {
+        let mut ctaphid = ctaphid();
+        send_message(&mut ctaphid, BROADCAST_CID, 0x06, &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let packets = read_packets(&mut ctaphid);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            packets[0][..24],
+            [
+                0xFF, 0xFF, 0xFF, 0xFF, 0x86, 0x00, 17, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 1, 2, 1,
+                2, 3, 0x05
+            ]
+        );
+        assert_eq!(allocate_channel(&mut ctaphid), 2);
+    }
+
+    #[test]
+    fn fragmented_message_round_trip() {
+        let mut ctaphid = ctaphid();
+        let cid = allocate_channel(&mut ctaphid);
+
+        let request: Vec<u8> = (0..200u8).collect();
+        send_message(&mut ctaphid, cid, 0x10, &request);
+
+        let packets = read_packets(&mut ctaphid);
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[0][4..8], [0x90, 0x00, 201, 1]);
+        assert_eq!(packets[0][8], 199);
+        assert_eq!(packets[1][4], 0);
+        assert_eq!(packets[3][4], 2);
+
+        let mut response = Vec::from(&packets[0][7..]);
+        for p in &packets[1..] {
+            response.extend_from_slice(&p[5..]);
+        }
+        response.truncate(201);
+        let expected: Vec<u8> = core::iter::once(1).chain((0..200u8).rev()).collect();
+        assert_eq!(response, expected);
+    }
+
+    #[test]
+    fn ping_and_wink() {
+        let mut ctaphid = ctaphid();
+        let cid = allocate_channel(&mut ctaphid);
+
+        send_message(&mut ctaphid, cid, 0x01, &[0xAA; 60]);
+        let packets = read_packets(&mut ctaphid);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][7..], [0xAA; INIT_DATA_SIZE]);
+        assert_eq!(packets[1][5..8], [0xAA; 3]);
+
+        send_message(&mut ctaphid, cid, 0x08, &[]);
+        let packets = read_packets(&mut ctaphid);
+        assert_eq!(packets[0][4..7], [0x88, 0, 0]);
+        assert_eq!(ctaphid.app().winks, 1);
+    }
+
+    #[test]
+    fn invalid_sequence_and_busy_channel() {
+        let mut ctaphid = ctaphid();
+        let cid = allocate_channel(&mut ctaphid);
+        let other = allocate_channel(&mut ctaphid);
+
+        let mut init = Vec::from(cid.to_be_bytes());
+        init.extend_from_slice(&[0x90, 0x00, 100]);
+        ctaphid.handle_report(&packet(&init));
+
+        let mut busy = Vec::from(other.to_be_bytes());
+        busy.extend_from_slice(&[0x81, 0x00, 0x01, 0x00]);
+        ctaphid.handle_report(&packet(&busy));
+
+        let mut cont = Vec::from(cid.to_be_bytes());
+        cont.push(1);
+        ctaphid.handle_report(&packet(&cont));
+
+        let packets = read_packets(&mut ctaphid);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][..8], [0, 0, 0, 2, 0xBF, 0, 1, 0x06]);
+        assert_eq!(packets[1][..8], [0, 0, 0, 1, 0xBF, 0, 1, 0x04]);
+
+        ctaphid.handle_report(&packet(&[0, 0, 0, 9, 0x81, 0, 0]));
+        assert_eq!(
+            read_packets(&mut ctaphid)[0][..8],
+            [0, 0, 0, 9, 0xBF, 0, 1, 0x0B]
+        );
+    }
+
+    #[test]
+    fn transaction_timeout() {
+        let mut ctaphid = ctaphid();
+        let cid = allocate_channel(&mut ctaphid);
+
+        let mut init = Vec::from(cid.to_be_bytes());
+        init.extend_from_slice(&[0x90, 0x00, 100]);
+        ctaphid.handle_report(&packet(&init));
+
+        for _ in 0..TRANSACTION_TIMEOUT_MS - 1 {
+            ctaphid.tick();
+        }
+        assert!(read_packets(&mut ctaphid).is_empty());
+
+        ctaphid.tick();
+        assert_eq!(read_packets(&mut ctaphid)[0][4..8], [0xBF, 0, 1, 0x05]);
+    }
+
+    #[test]
+    fn keepalive_and_cancel() {
+        let mut ctaphid = ctaphid();
+        let cid = allocate_channel(&mut ctaphid);
+
+        ctaphid.app().pending = true;
+        send_message(&mut ctaphid, cid, 0x10, &[0x01]);
+        for _ in 0..KEEPALIVE_INTERVAL_MS {
+            ctaphid.tick();
+        }
+        assert_eq!(read_packets(&mut ctaphid)[0][4..8], [0xBB, 0, 1, 0x02]);
+
+        send_message(&mut ctaphid, cid, 0x11, &[]);
+        assert!(ctaphid.app().cancelled);
+        assert_eq!(read_packets(&mut ctaphid)[0][4..8], [0x90, 0, 1, 0x2D]);
+    }
+}
@@ -4,6 +4,8 @@ use fugit::ExtU32;
 use usb_device::bus::UsbBus;
 use usb_device::class_prelude::UsbBusAllocator;
 
+pub mod ctaphid;
+
 /// Raw FIDO report descriptor.
 /// 
 /// See the [FIDO U2F HID Protocol Specification](https://fidoalliance.org/specs/fido-u2f-v1.2-ps-20170411/fido-u2f-hid-protocol-v1.2-ps-20170411.html)
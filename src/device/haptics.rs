@@ -0,0 +1,705 @@
+//!HID haptic actuator, e.g. the feedback motor of a touchpad or pen
+//!
+//! [`HapticController`] implements the Simple Haptic Controller reports and turns the host's
+//! requests into [`HapticEvent`]s. It can be embedded in any device using
+//! [`HapticDescriptor::fragment()`], or added alongside another device as a separate
+//! [`HapticActuator`] interface.
+//!
+//! Reference: <https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/haptic-touchpad-implementation-guide>
+use crate::page::Haptics;
+use crate::usb_class::prelude::*;
+use fugit::ExtU32;
+use heapless::Deque;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Report ID of the waveform, duration, auto trigger and intensity feature report of
+/// [`HAPTIC_DESCRIPTOR`]
+pub const HAPTIC_FEATURE_REPORT_ID: u8 = 0x01;
+/// Report ID of the manual trigger output report of [`HAPTIC_DESCRIPTOR`]
+pub const HAPTIC_OUTPUT_REPORT_ID: u8 = 0x02;
+/// Largest intensity, as a percentage
+pub const HAPTIC_INTENSITY_MAX: u8 = 100;
+
+/// Waveforms supported by the actuator, ordinals 3 to 7
+///
+/// Ordinal 1 is [`Haptics::WaveformNone`] and ordinal 2 [`Haptics::WaveformStop`].
+pub const HAPTIC_WAVEFORMS: [Haptics; 5] = [
+    Haptics::WaveformClick,
+    Haptics::WaveformBuzzContinuous,
+    Haptics::WaveformRumbleContinuous,
+    Haptics::WaveformPress,
+    Haptics::WaveformRelease,
+];
+
+const FEATURE_REPORT_SIZE: usize = 28;
+const OUTPUT_REPORT_SIZE: usize = 7;
+const EVENT_QUEUE_SIZE: usize = 8;
+/// Button 1, the click of the device plays the auto trigger waveform
+const AUTO_TRIGGER_ASSOCIATED_CONTROL: u32 = 0x0009_0001;
+/// 20ms click, press and release waveforms
+const DEFAULT_DURATIONS_MS: [u16; HAPTIC_WAVEFORMS.len()] = [20, 0, 0, 20, 20];
+
+/// Length of a [`HapticDescriptor`]
+const DESCRIPTOR_LEN: usize = 146;
+
+/// Simple Haptic Controller report descriptor, built at compile time with the report IDs used by
+/// the device
+///
+/// [`HapticDescriptor::as_bytes()`] is a complete report descriptor for a separate haptic
+/// interface. [`HapticDescriptor::fragment()`] describes the same reports as a Logical collection,
+/// to be embedded in the Application collection of another device such as a touchpad, using
+/// report IDs not already used by that device:
+/// ```
+/// # use usbd_human_interface_device::device::haptics::*;
+/// static HAPTICS: HapticDescriptor = HapticDescriptor::new(0x06, 0x07);
+/// ```
+///
+/// Feature report:
+/// * Bytes 1-10 - Waveform List, the waveform usage of ordinals 3 to 7
+/// * Bytes 11-20 - Duration List, the duration of each waveform in milliseconds, 0 for
+///   continuous waveforms
+/// * Bytes 21-22 - Auto Trigger, ordinal of the waveform played by the device on a click
+/// * Bytes 23-26 - Auto Trigger Associated Control, always Button 1 (`0x0009_0001`), the click
+///   that plays the auto trigger waveform
+/// * Byte 27 - Intensity, 0-100%
+///
+/// Output report:
+/// * Bytes 1-2 - Manual Trigger, ordinal of the waveform to play
+/// * Byte 3 - Intensity, 0-100%
+/// * Byte 4 - Repeat Count
+/// * Bytes 5-6 - Retrigger Period in milliseconds
+pub struct HapticDescriptor {
+    application: [u8; DESCRIPTOR_LEN],
+    logical: [u8; DESCRIPTOR_LEN],
+    feature_report_id: u8,
+    output_report_id: u8,
+}
+
+impl HapticDescriptor {
+    #[must_use]
+    pub const fn new(feature_report_id: u8, output_report_id: u8) -> Self {
+        Self {
+            application: descriptor(0x01, feature_report_id, output_report_id),
+            logical: descriptor(0x02, feature_report_id, output_report_id),
+            feature_report_id,
+            output_report_id,
+        }
+    }
+
+    /// Report descriptor of a separate haptic interface
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        &self.application
+    }
+
+    /// Simple Haptic Controller Logical collection, for embedding in another device's report
+    /// descriptor
+    ///
+    /// The fragment changes the Usage Page, Report Size, Report Count and logical ranges, items
+    /// following it must set these again.
+    #[must_use]
+    pub const fn fragment(&self) -> &[u8] {
+        &self.logical
+    }
+
+    #[must_use]
+    pub const fn feature_report_id(&self) -> u8 {
+        self.feature_report_id
+    }
+
+    #[must_use]
+    pub const fn output_report_id(&self) -> u8 {
+        self.output_report_id
+    }
+}
+
+/// Descriptor using [`HAPTIC_FEATURE_REPORT_ID`] and [`HAPTIC_OUTPUT_REPORT_ID`]
+pub static HAPTIC_DESCRIPTOR: HapticDescriptor =
+    HapticDescriptor::new(HAPTIC_FEATURE_REPORT_ID, HAPTIC_OUTPUT_REPORT_ID);
+
+const fn descriptor(
+    collection: u8,
+    feature_report_id: u8,
+    output_report_id: u8,
+) -> [u8; DESCRIPTOR_LEN] {
+    #[rustfmt::skip]
+    let bytes = [
+        0x05, 0x0E,                 // Usage Page (Haptics)
+        0x09, 0x01,                 // Usage (Simple Haptic Controller)
+        0xA1, collection,           // Collection (Application or Logical)
+        0x85, feature_report_id,    //   Report ID
+        0x09, 0x10,                 //   Usage (Waveform List)
+        0xA1, 0x02,                 //   Collection (Logical)
+        0x05, 0x0A,                 //     Usage Page (Ordinal)
+        0x19, 0x03,                 //     Usage Minimum (3)
+        0x29, 0x07,                 //     Usage Maximum (7)
+        0x16, 0x03, 0x10,           //     Logical Minimum (0x1003)
+        0x26, 0x07, 0x10,           //     Logical Maximum (0x1007)
+        0x75, 0x10,                 //     Report Size (16)
+        0x95, 0x05,                 //     Report Count (5)
+        0xB1, 0x03,                 //     Feature (Constant, Variable, Absolute)
+        0xC0,                       //   End Collection
+        0x05, 0x0E,                 //   Usage Page (Haptics)
+        0x09, 0x11,                 //   Usage (Duration List)
+        0xA1, 0x02,                 //   Collection (Logical)
+        0x05, 0x0A,                 //     Usage Page (Ordinal)
+        0x19, 0x03,                 //     Usage Minimum (3)
+        0x29, 0x07,                 //     Usage Maximum (7)
+        0x15, 0x00,                 //     Logical Minimum (0)
+        0x26, 0xFF, 0x7F,           //     Logical Maximum (32767)
+        0x55, 0x0D,                 //     Unit Exponent (-3)
+        0x66, 0x01, 0x10,           //     Unit (Seconds)
+        0xB1, 0x03,                 //     Feature (Constant, Variable, Absolute)
+        0xC0,                       //   End Collection
+        0x05, 0x0E,                 //   Usage Page (Haptics)
+        0x55, 0x00,                 //   Unit Exponent (0)
+        0x65, 0x00,                 //   Unit (None)
+        0x09, 0x20,                 //   Usage (Auto Trigger)
+        0x15, 0x01,                 //   Logical Minimum (1)
+        0x25, 0x07,                 //   Logical Maximum (7)
+        0x95, 0x01,                 //   Report Count (1)
+        0xB1, 0x02,                 //   Feature (Data, Variable, Absolute)
+        0x09, 0x22,                 //   Usage (Auto Trigger Associated Control)
+        0x17, 0x01, 0x00, 0x09, 0x00, //   Logical Minimum (Button 1)
+        0x27, 0x01, 0x00, 0x09, 0x00, //   Logical Maximum (Button 1)
+        0x75, 0x20,                 //   Report Size (32)
+        0xB1, 0x03,                 //   Feature (Constant, Variable, Absolute)
+        0x09, 0x23,                 //   Usage (Intensity)
+        0x15, 0x00,                 //   Logical Minimum (0)
+        0x25, 0x64,                 //   Logical Maximum (100)
+        0x75, 0x08,                 //   Report Size (8)
+        0xB1, 0x02,                 //   Feature (Data, Variable, Absolute)
+        0x85, output_report_id,     //   Report ID
+        0x09, 0x21,                 //   Usage (Manual Trigger)
+        0x15, 0x01,                 //   Logical Minimum (1)
+        0x25, 0x07,                 //   Logical Maximum (7)
+        0x75, 0x10,                 //   Report Size (16)
+        0x91, 0x02,                 //   Output (Data, Variable, Absolute)
+        0x09, 0x23,                 //   Usage (Intensity)
+        0x15, 0x00,                 //   Logical Minimum (0)
+        0x25, 0x64,                 //   Logical Maximum (100)
+        0x75, 0x08,                 //   Report Size (8)
+        0x91, 0x02,                 //   Output (Data, Variable, Absolute)
+        0x09, 0x24,                 //   Usage (Repeat Count)
+        0x26, 0xFF, 0x00,           //   Logical Maximum (255)
+        0x91, 0x02,                 //   Output (Data, Variable, Absolute)
+        0x09, 0x25,                 //   Usage (Retrigger Period)
+        0x26, 0xFF, 0x7F,           //   Logical Maximum (32767)
+        0x75, 0x10,                 //   Report Size (16)
+        0x55, 0x0D,                 //   Unit Exponent (-3)
+        0x66, 0x01, 0x10,           //   Unit (Seconds)
+        0x91, 0x02,                 //   Output (Data, Variable, Absolute)
+        0x55, 0x00,                 //   Unit Exponent (0)
+        0x65, 0x00,                 //   Unit (None)
+        0xC0,                       // End Collection
+    ];
+    bytes
+}
+
+/// Waveform played on request of the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HapticTrigger {
+    /// One of [`HAPTIC_WAVEFORMS`]
+    pub waveform: Haptics,
+    /// 0-100%
+    pub intensity: u8,
+    /// Number of times to repeat the waveform after it has been played once
+    pub repeat_count: u8,
+    /// Time between repeats in milliseconds
+    pub retrigger_period_ms: u16,
+}
+
+/// Request from the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HapticEvent {
+    /// Play a waveform
+    ManualTrigger(HapticTrigger),
+    /// Stop any waveform being played
+    Stop,
+    /// The waveform the device plays by itself on a click, [`Haptics::WaveformNone`] to disable
+    AutoTrigger(Haptics),
+    /// The intensity of waveforms played by the device by itself, 0-100%
+    Intensity(u8),
+}
+
+/// Simple Haptic Controller state, for devices using a [`HapticDescriptor`]
+pub struct HapticController {
+    feature_report_id: u8,
+    output_report_id: u8,
+    durations: [u16; HAPTIC_WAVEFORMS.len()],
+    auto_trigger: Haptics,
+    intensity: u8,
+    events: Deque<HapticEvent, EVENT_QUEUE_SIZE>,
+    feature_report: [u8; FEATURE_REPORT_SIZE],
+}
+
+impl HapticController {
+    /// Create a controller for the reports of `descriptor`, `durations_ms` is the duration of
+    /// each of [`HAPTIC_WAVEFORMS`]
+    #[must_use]
+    pub fn new(descriptor: &HapticDescriptor, durations_ms: [u16; HAPTIC_WAVEFORMS.len()]) -> Self {
+        Self {
+            feature_report_id: descriptor.feature_report_id,
+            output_report_id: descriptor.output_report_id,
+            durations: durations_ms,
+            auto_trigger: Haptics::WaveformClick,
+            intensity: HAPTIC_INTENSITY_MAX,
+            events: Deque::new(),
+            feature_report: [0; FEATURE_REPORT_SIZE],
+        }
+    }
+
+    /// The waveform the device should play by itself on a click
+    #[must_use]
+    pub fn auto_trigger(&self) -> Haptics {
+        self.auto_trigger
+    }
+
+    /// The intensity of waveforms played by the device by itself
+    #[must_use]
+    pub fn intensity(&self) -> u8 {
+        self.intensity
+    }
+
+    /// Take the oldest request from the host
+    pub fn read_event(&mut self) -> Option<HapticEvent> {
+        self.events.pop_front()
+    }
+
+    /// Restore the auto trigger and intensity defaults and drop queued events
+    pub fn reset(&mut self) {
+        self.auto_trigger = Haptics::WaveformClick;
+        self.intensity = HAPTIC_INTENSITY_MAX;
+        self.events.clear();
+    }
+
+    pub fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        if report_id != self.feature_report_id {
+            return None;
+        }
+
+        self.feature_report[0] = report_id;
+        let (waveforms, rest) = self.feature_report[1..].split_at_mut(10);
+        let (durations, rest) = rest.split_at_mut(10);
+        for (i, waveform) in HAPTIC_WAVEFORMS.iter().enumerate() {
+            waveforms[i * 2..i * 2 + 2].copy_from_slice(&u16::from(*waveform).to_le_bytes());
+            durations[i * 2..i * 2 + 2].copy_from_slice(&self.durations[i].to_le_bytes());
+        }
+        rest[..2].copy_from_slice(&ordinal(self.auto_trigger).to_le_bytes());
+        rest[2..6].copy_from_slice(&AUTO_TRIGGER_ASSOCIATED_CONTROL.to_le_bytes());
+        rest[6] = self.intensity;
+
+        Some(FeatureReport::DynamicReport(&self.feature_report))
+    }
+
+    /// Handle a feature report from the host, `data` includes the report ID
+    pub fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        if report_id != self.feature_report_id {
+            return Err(UsbError::Unsupported);
+        }
+        if data.len() != FEATURE_REPORT_SIZE {
+            return Err(UsbError::ParseError);
+        }
+
+        let auto_trigger = waveform(u16::from_le_bytes([data[21], data[22]]))
+            .filter(|w| *w != Haptics::WaveformStop)
+            .ok_or(UsbError::ParseError)?;
+        let intensity = data[27].min(HAPTIC_INTENSITY_MAX);
+
+        if auto_trigger != self.auto_trigger {
+            self.auto_trigger = auto_trigger;
+            self.push_event(HapticEvent::AutoTrigger(auto_trigger));
+        }
+        if intensity != self.intensity {
+            self.intensity = intensity;
+            self.push_event(HapticEvent::Intensity(intensity));
+        }
+        Ok(())
+    }
+
+    /// Handle an output report from the host, `data` includes the report ID
+    pub fn handle_output_report(&mut self, data: &[u8]) -> usb_device::Result<()> {
+        let [report_id, t0, t1, intensity, repeat_count, p0, p1] = *data else {
+            return Err(UsbError::ParseError);
+        };
+        if report_id != self.output_report_id {
+            return Err(UsbError::ParseError);
+        }
+
+        let event = match waveform(u16::from_le_bytes([t0, t1])) {
+            Some(Haptics::WaveformNone) => return Ok(()),
+            Some(Haptics::WaveformStop) => HapticEvent::Stop,
+            Some(waveform) => HapticEvent::ManualTrigger(HapticTrigger {
+                waveform,
+                intensity: intensity.min(HAPTIC_INTENSITY_MAX),
+                repeat_count,
+                retrigger_period_ms: u16::from_le_bytes([p0, p1]),
+            }),
+            None => return Err(UsbError::ParseError),
+        };
+        self.push_event(event);
+        Ok(())
+    }
+
+    fn push_event(&mut self, event: HapticEvent) {
+        if self.events.push_back(event).is_err() {
+            warn!("Haptic event queue full, dropping event");
+        }
+    }
+}
+
+impl Default for HapticController {
+    /// Controller for [`HAPTIC_DESCRIPTOR`] with 20ms click, press and release waveforms
+    fn default() -> Self {
+        Self::new(&HAPTIC_DESCRIPTOR, DEFAULT_DURATIONS_MS)
+    }
+}
+
+/// Waveform of a waveform ordinal
+fn waveform(ordinal: u16) -> Option<Haptics> {
+    match ordinal {
+        1 => Some(Haptics::WaveformNone),
+        2 => Some(Haptics::WaveformStop),
+        _ => HAPTIC_WAVEFORMS
+            .get(usize::from(ordinal.checked_sub(3)?))
+            .copied(),
+    }
+}
+
+/// Ordinal of a waveform, 1 ([`Haptics::WaveformNone`]) if the waveform is not supported
+fn ordinal(waveform: Haptics) -> u16 {
+    match waveform {
+        Haptics::WaveformStop => 2,
+        _ => HAPTIC_WAVEFORMS
+            .iter()
+            .position(|w| *w == waveform)
+            .and_then(|i| u16::try_from(i + 3).ok())
+            .unwrap_or(1),
+    }
+}
+
+/// Interface implementing a haptic actuator
+///
+/// **Note:** Output reports are read from [`UsbHidClass::tick()`], which must be called every
+/// 1ms.
+pub struct HapticActuator<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutBytes8, ReportSingle>,
+    controller: HapticController,
+}
+
+impl<B: UsbBus> HapticActuator<'_, B> {
+    /// Take the oldest request from the host
+    pub fn read_event(&mut self) -> Option<HapticEvent> {
+        self.controller.read_event()
+    }
+
+    #[must_use]
+    pub fn controller(&self) -> &HapticController {
+        &self.controller
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for HapticActuator<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutBytes8, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.controller.reset();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        let mut data = [0u8; OUTPUT_REPORT_SIZE];
+        loop {
+            match self.interface.read_report(&mut data) {
+                Ok(len) => {
+                    if let Err(e) = self.controller.handle_output_report(&data[..len]) {
+                        warn!("Invalid haptic output report - {:?}", e);
+                    }
+                }
+                Err(UsbError::WouldBlock) => return Ok(()),
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        self.controller.get_feature_report(report_id)
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        self.controller.set_feature_report(report_id, data)
+    }
+}
+
+pub struct HapticActuatorConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutBytes8, ReportSingle>,
+    controller: HapticController,
+}
+
+impl Default for HapticActuatorConfig<'_> {
+    fn default() -> Self {
+        Self::new(&HAPTIC_DESCRIPTOR, DEFAULT_DURATIONS_MS)
+    }
+}
+
+impl<'a> HapticActuatorConfig<'a> {
+    /// Create a configuration using `descriptor`, `durations_ms` is the duration of each of
+    /// [`HAPTIC_WAVEFORMS`]
+    #[must_use]
+    pub fn new(
+        descriptor: &'static HapticDescriptor,
+        durations_ms: [u16; HAPTIC_WAVEFORMS.len()],
+    ) -> Self {
+        Self::with_interface(
+            unwrap!(unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                descriptor.as_bytes()
+            ))
+            .description("Haptic Actuator")
+            .in_endpoint(10.millis()))
+            .with_out_endpoint(10.millis()))
+            .build(),
+            HapticController::new(descriptor, durations_ms),
+        )
+    }
+
+    /// Create a configuration from an interface using the [`HapticDescriptor`] of `controller`
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes8, OutBytes8, ReportSingle>,
+        controller: HapticController,
+    ) -> Self {
+        Self {
+            interface,
+            controller,
+        }
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for HapticActuatorConfig<'a> {
+    type Allocated = HapticActuator<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            controller: self.controller,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{report_bits, test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn feature_report_lists_waveforms() {
+        let mut controller = HapticController::new(&HAPTIC_DESCRIPTOR, [10, 0, 0, 15, 25]);
+        let Some(FeatureReport::DynamicReport(report)) =
+            controller.get_feature_report(HAPTIC_FEATURE_REPORT_ID)
+        else {
+            panic!("Expected feature report");
+        };
+        assert_eq!(
+            report,
+            [
+                0x01, 0x03, 0x10, 0x04, 0x10, 0x05, 0x10, 0x06, 0x10, 0x07, 0x10, 10, 0, 0, 0, 0,
+                0, 15, 0, 25, 0, 0x03, 0x00, 0x01, 0x00, 0x09, 0x00, 100
+            ]
+        );
+        assert!(controller.get_feature_report(0x02).is_none());
+    }
+
+    #[test]
+    fn descriptor_report_ids() {
+        static DESCRIPTOR: HapticDescriptor = HapticDescriptor::new(0x06, 0x07);
+
+        let bytes = DESCRIPTOR.as_bytes();
+        assert_eq!(bytes[..8], [0x05, 0x0E, 0x09, 0x01, 0xA1, 0x01, 0x85, 0x06]);
+        assert!(bytes.windows(4).any(|w| w == [0x85, 0x07, 0x09, 0x21]));
+        assert_eq!(bytes.last(), Some(&0xC0));
+
+        let fragment = DESCRIPTOR.fragment();
+        assert_eq!(
+            fragment[..8],
+            [0x05, 0x0E, 0x09, 0x01, 0xA1, 0x02, 0x85, 0x06]
+        );
+        assert_eq!(fragment[8..], bytes[8..]);
+
+        let mut controller = HapticController::new(&DESCRIPTOR, DEFAULT_DURATIONS_MS);
+        assert!(controller
+            .get_feature_report(HAPTIC_FEATURE_REPORT_ID)
+            .is_none());
+        assert!(controller.get_feature_report(0x06).is_some());
+        assert_eq!(
+            controller.handle_output_report(&[0x02, 0x03, 0x00, 100, 0, 0, 0]),
+            Err(UsbError::ParseError)
+        );
+        controller
+            .handle_output_report(&[0x07, 0x03, 0x00, 100, 0, 0, 0])
+            .unwrap();
+        assert!(matches!(
+            controller.read_event(),
+            Some(HapticEvent::ManualTrigger(_))
+        ));
+    }
+
+    #[test]
+    fn fragment_embedded_in_touchpad() {
+        static HAPTICS: HapticDescriptor = HapticDescriptor::new(0x06, 0x07);
+
+        #[rustfmt::skip]
+        let touchpad = [
+            0x05, 0x0D,                 // Usage Page (Digitizers)
+            0x09, 0x05,                 // Usage (Touch Pad)
+            0xA1, 0x01,                 // Collection (Application)
+            0x85, 0x01,                 //   Report ID (1)
+            0x05, 0x09,                 //   Usage Page (Button)
+            0x09, 0x01,                 //   Usage (Button 1)
+            0x15, 0x00,                 //   Logical Minimum (0)
+            0x25, 0x01,                 //   Logical Maximum (1)
+            0x75, 0x01,                 //   Report Size (1)
+            0x95, 0x01,                 //   Report Count (1)
+            0x81, 0x02,                 //   Input (Data, Variable, Absolute)
+            0x95, 0x07,                 //   Report Count (7)
+            0x81, 0x03,                 //   Input (Constant, Variable, Absolute)
+        ];
+        #[rustfmt::skip]
+        let touchpad_feature = [
+            0x05, 0x0D,                 //   Usage Page (Digitizers)
+            0x85, 0x02,                 //   Report ID (2)
+            0x09, 0x55,                 //   Usage (Contact Count Maximum)
+            0x25, 0x05,                 //   Logical Maximum (5)
+            0x75, 0x08,                 //   Report Size (8)
+            0x95, 0x01,                 //   Report Count (1)
+            0xB1, 0x02,                 //   Feature (Data, Variable, Absolute)
+            0xC0,                       // End Collection
+        ];
+        let descriptor = [&touchpad[..], HAPTICS.fragment(), &touchpad_feature[..]].concat();
+
+        assert_eq!(
+            report_bits(&descriptor),
+            [
+                (0x80, 0x01, 8),
+                (0x90, 0x07, 6 * 8),
+                (0xB0, 0x02, 8),
+                (0xB0, 0x06, 27 * 8)
+            ]
+        );
+
+        let mut controller = HapticController::new(&HAPTICS, DEFAULT_DURATIONS_MS);
+        assert!(controller.get_feature_report(0x02).is_none());
+        let Some(FeatureReport::DynamicReport(feature)) = controller.get_feature_report(0x06)
+        else {
+            panic!("Expected feature report");
+        };
+        assert_eq!(feature.len(), 28);
+        assert_eq!(feature[0], 0x06);
+
+        let mut feature: [u8; 28] = feature.try_into().unwrap();
+        feature[27] = 30;
+        controller.set_feature_report(0x06, &feature).unwrap();
+        controller
+            .handle_output_report(&[0x07, 0x07, 0x00, 100, 0, 0, 0])
+            .unwrap();
+        assert_eq!(controller.read_event(), Some(HapticEvent::Intensity(30)));
+        assert!(matches!(
+            controller.read_event(),
+            Some(HapticEvent::ManualTrigger(HapticTrigger {
+                waveform: Haptics::WaveformRelease,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn host_requests_become_events() {
+        let mut controller = HapticController::default();
+
+        let mut feature = [0u8; FEATURE_REPORT_SIZE];
+        feature[0] = HAPTIC_FEATURE_REPORT_ID;
+        feature[21] = 1;
+        feature[27] = 50;
+        controller
+            .set_feature_report(HAPTIC_FEATURE_REPORT_ID, &feature)
+            .unwrap();
+        assert_eq!(controller.auto_trigger(), Haptics::WaveformNone);
+
+        controller
+            .handle_output_report(&[0x02, 0x06, 0x00, 80, 2, 0x64, 0x00])
+            .unwrap();
+        controller
+            .handle_output_report(&[0x02, 0x02, 0x00, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(
+            controller.handle_output_report(&[0x02, 0x08, 0x00, 0, 0, 0, 0]),
+            Err(UsbError::ParseError)
+        );
+
+        assert_eq!(
+            controller.read_event(),
+            Some(HapticEvent::AutoTrigger(Haptics::WaveformNone))
+        );
+        assert_eq!(controller.read_event(), Some(HapticEvent::Intensity(50)));
+        assert_eq!(
+            controller.read_event(),
+            Some(HapticEvent::ManualTrigger(HapticTrigger {
+                waveform: Haptics::WaveformPress,
+                intensity: 80,
+                repeat_count: 2,
+                retrigger_period_ms: 100,
+            }))
+        );
+        assert_eq!(controller.read_event(), Some(HapticEvent::Stop));
+        assert_eq!(controller.read_event(), None);
+    }
+
+    #[test]
+    fn haptic_actuator_events() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(HapticActuatorConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Get the waveform feature report
+        let mut feature = manager.host_get_feature(&mut usb_dev, &mut hid, 0x01, 28);
+        assert_eq!(feature.len(), 28);
+        assert_eq!(feature[1..3], [0x03, 0x10]);
+        assert_eq!(feature[23..27], [0x01, 0x00, 0x09, 0x00]);
+
+        // Lower the intensity
+        feature[27] = 40;
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0x01, &feature);
+
+        // Play a click
+        manager
+            .host_write_out(&[0x02, 0x03, 0x00, 100, 0, 0, 0])
+            .unwrap();
+        hid.tick().unwrap();
+
+        let device = hid.device();
+        assert_eq!(device.read_event(), Some(HapticEvent::Intensity(40)));
+        assert_eq!(
+            device.read_event(),
+            Some(HapticEvent::ManualTrigger(HapticTrigger {
+                waveform: Haptics::WaveformClick,
+                intensity: 100,
+                repeat_count: 0,
+                retrigger_period_ms: 0,
+            }))
+        );
+        assert_eq!(device.read_event(), None);
+    }
+}
@@ -0,0 +1,641 @@
+//! HF2 (HID Flashing Format) firmware update protocol
+//!
+//! Implements the framing and flashing commands of the HF2 protocol used by UF2 bootloaders on
+//! top of a [`RawHid`] interface, with flash access provided by an [`Hf2App`].
+//!
+//! See the [HF2 specification](https://github.com/microsoft/uf2/blob/master/hf2.md) for protocol
+//! detail
+use fugit::ExtU32;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use usb_device::bus::UsbBus;
+use usb_device::UsbError;
+
+use crate::device::raw::{RawHid, RawHidConfig, RawHidDescriptor};
+use crate::usb_class::prelude::*;
+
+/// Size of an HF2 report
+pub const REPORT_SIZE: usize = 64;
+/// Largest flash page supported by [`Hf2`]
+pub const MAX_PAGE_SIZE: usize = 1024;
+/// Largest command or response message, large enough for a [`Command::WriteFlashPage`] of
+/// [`MAX_PAGE_SIZE`]
+pub const MAX_MESSAGE_SIZE: usize = MAX_PAGE_SIZE + 16;
+
+const PACKET_TYPE_MASK: u8 = 0xC0;
+const PACKET_TYPE_INNER: u8 = 0x00;
+const PACKET_TYPE_FINAL: u8 = 0x40;
+const PACKET_LENGTH_MASK: u8 = 0x3F;
+const PACKET_DATA_SIZE: usize = REPORT_SIZE - 1;
+
+const COMMAND_HEADER_SIZE: usize = 8;
+const RESPONSE_HEADER_SIZE: usize = 4;
+
+/// HF2 raw HID report descriptor, vendor usage page 0xFF97 and usage 0x01 with 64 byte reports
+pub static HF2_DESCRIPTOR: RawHidDescriptor<InBytes64, OutBytes64> =
+    RawHidDescriptor::new(0xFF97, 0x01, 0);
+
+/// Raw HID interface used by [`Hf2`]
+pub type RawHf2<'a, B> = RawHid<'a, B, InBytes64, OutBytes64>;
+
+/// Raw HID configuration with the [`HF2_DESCRIPTOR`]
+#[must_use]
+pub fn hf2_config() -> RawHidConfig<'static, InBytes64, OutBytes64> {
+    RawHidConfig::with_interface(
+        unwrap!(
+            unwrap!(unwrap!(InterfaceBuilder::new(HF2_DESCRIPTOR.as_bytes()))
+                .description("HF2")
+                .in_endpoint(1.millis()))
+            .with_out_endpoint(1.millis())
+        )
+        .build(),
+        0,
+    )
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u32)]
+pub enum Command {
+    BinInfo = 0x0001,
+    Info = 0x0002,
+    ResetIntoApp = 0x0003,
+    ResetIntoBootloader = 0x0004,
+    StartFlash = 0x0005,
+    WriteFlashPage = 0x0006,
+    ChecksumPages = 0x0007,
+    ReadWords = 0x0008,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, IntoPrimitive)]
+#[repr(u8)]
+pub enum Status {
+    Success = 0x00,
+    InvalidCommand = 0x01,
+    ExecutionError = 0x02,
+}
+
+/// Operating mode reported by [`Command::BinInfo`]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, IntoPrimitive)]
+#[repr(u32)]
+pub enum Mode {
+    #[default]
+    Bootloader = 0x01,
+    User = 0x02,
+}
+
+/// Flash storage and device control used by [`Hf2`]
+pub trait Hf2App {
+    fn mode(&self) -> Mode {
+        Mode::Bootloader
+    }
+
+    /// Size of a flash page in bytes, at most [`MAX_PAGE_SIZE`]
+    fn page_size(&self) -> u32;
+
+    fn num_pages(&self) -> u32;
+
+    /// UF2 family ID of the device, 0 if not specified
+    fn family_id(&self) -> u32 {
+        0
+    }
+
+    /// Contents of the `INFO_UF2.TXT` file
+    #[allow(clippy::unnecessary_literal_bound)]
+    fn info(&self) -> &str {
+        ""
+    }
+
+    /// Called before a sequence of [`Hf2App::write_page`] calls
+    fn start_flash(&mut self) {}
+
+    /// Erase and write a page of flash, `data` is [`Hf2App::page_size`] bytes
+    ///
+    /// Returns `false` if the write failed
+    fn write_page(&mut self, address: u32, data: &[u8]) -> bool;
+
+    /// Read `data.len()` bytes starting at `address`
+    ///
+    /// Returns `false` if the address range cannot be read
+    fn read(&mut self, address: u32, data: &mut [u8]) -> bool;
+
+    /// Reset and start the application, the host does not wait for a response
+    fn reset_into_app(&mut self) {}
+
+    /// Reset into the bootloader, the host does not wait for a response
+    fn reset_into_bootloader(&mut self) {}
+}
+
+/// HF2 protocol handler for a [`RawHf2`] interface
+///
+/// **Note:** [`Hf2::poll()`] must be called after every USB poll.
+pub struct Hf2<A: Hf2App> {
+    app: A,
+    request: [u8; MAX_MESSAGE_SIZE],
+    request_len: usize,
+    /// The request is too large and is discarded until its final packet
+    overflow: bool,
+    response: [u8; MAX_MESSAGE_SIZE],
+    response_len: usize,
+    response_sent: Option<usize>,
+}
+
+impl<A: Hf2App> Hf2<A> {
+    #[must_use]
+    pub fn new(app: A) -> Self {
+        Self {
+            app,
+            request: [0; MAX_MESSAGE_SIZE],
+            request_len: 0,
+            overflow: false,
+            response: [0; MAX_MESSAGE_SIZE],
+            response_len: 0,
+            response_sent: None,
+        }
+    }
+
+    pub fn app(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    /// Read any packets received by `raw` and write any pending response packets to it
+    ///
+    /// A new request is not read until the response to the previous request has been written
+    pub fn poll<B: UsbBus>(&mut self, raw: &mut RawHf2<'_, B>) -> Result<(), UsbHidError> {
+        while self.response_sent.is_none() {
+            let mut report = [0u8; REPORT_SIZE];
+            match raw.read_report(&mut report) {
+                Ok(_) => self.handle_report(&report),
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+
+        while let Some(packet) = self.peek_output() {
+            match raw.write_report(&packet) {
+                Ok(()) => self.pop_output(),
+                //retry on the next poll
+                Err(UsbHidError::WouldBlock) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a packet received from the host
+    pub fn handle_report(&mut self, report: &[u8; REPORT_SIZE]) {
+        let len = usize::from(report[0] & PACKET_LENGTH_MASK).min(PACKET_DATA_SIZE);
+        let data = &report[1..=len];
+
+        match report[0] & PACKET_TYPE_MASK {
+            PACKET_TYPE_INNER | PACKET_TYPE_FINAL => {}
+            _ => {
+                // Serial packets are only sent by the device
+                warn!("Unexpected HF2 packet type {:X}", report[0]);
+                return;
+            }
+        }
+
+        if self.request_len + len > MAX_MESSAGE_SIZE {
+            self.overflow = true;
+        } else if !self.overflow {
+            self.request[self.request_len..self.request_len + len].copy_from_slice(data);
+            self.request_len += len;
+        }
+
+        if report[0] & PACKET_TYPE_MASK == PACKET_TYPE_FINAL {
+            if self.overflow {
+                warn!("HF2 message exceeds {} bytes, discarded", MAX_MESSAGE_SIZE);
+            } else {
+                self.handle_message();
+            }
+            self.request_len = 0;
+            self.overflow = false;
+        }
+    }
+
+    fn handle_message(&mut self) {
+        if self.request_len < COMMAND_HEADER_SIZE {
+            warn!("HF2 message too short");
+            return;
+        }
+        let id = u32::from_le_bytes([
+            self.request[0],
+            self.request[1],
+            self.request[2],
+            self.request[3],
+        ]);
+        // Echo the tag and clear the status
+        self.response[..2].copy_from_slice(&self.request[4..6]);
+        self.response[2..RESPONSE_HEADER_SIZE].fill(0);
+        self.response_len = RESPONSE_HEADER_SIZE;
+
+        let status = match Command::try_from(id) {
+            Ok(command) => self.handle_command(command),
+            Err(_) => Some(Status::InvalidCommand),
+        };
+
+        if let Some(status) = status {
+            self.response[2] = status.into();
+            if status != Status::Success {
+                self.response_len = RESPONSE_HEADER_SIZE;
+            }
+            self.response_sent = Some(0);
+        }
+    }
+
+    /// Handle a command, returning `None` if no response is sent
+    fn handle_command(&mut self, command: Command) -> Option<Status> {
+        let args = COMMAND_HEADER_SIZE..self.request_len;
+        let status = match command {
+            Command::BinInfo => {
+                let page_size = self.app.page_size();
+                #[allow(clippy::cast_possible_truncation)]
+                let info = [
+                    u32::from(self.app.mode()),
+                    page_size,
+                    self.app.num_pages(),
+                    MAX_MESSAGE_SIZE as u32,
+                    self.app.family_id(),
+                ];
+                for word in info {
+                    self.push_response(&word.to_le_bytes());
+                }
+                Status::Success
+            }
+            Command::Info => {
+                let info = self.app.info().as_bytes();
+                let len = info.len().min(MAX_MESSAGE_SIZE - RESPONSE_HEADER_SIZE);
+                self.response[RESPONSE_HEADER_SIZE..RESPONSE_HEADER_SIZE + len]
+                    .copy_from_slice(&info[..len]);
+                self.response_len += len;
+                Status::Success
+            }
+            Command::ResetIntoApp => {
+                self.app.reset_into_app();
+                return None;
+            }
+            Command::ResetIntoBootloader => {
+                self.app.reset_into_bootloader();
+                return None;
+            }
+            Command::StartFlash => {
+                self.app.start_flash();
+                Status::Success
+            }
+            Command::WriteFlashPage => {
+                let page_size = usize::try_from(self.app.page_size()).unwrap_or(usize::MAX);
+                if page_size.checked_add(4) != Some(args.len()) {
+                    return Some(Status::ExecutionError);
+                }
+                let address = self.request_word(0);
+                let data = &self.request[COMMAND_HEADER_SIZE + 4..self.request_len];
+                if self.app.write_page(address, data) {
+                    Status::Success
+                } else {
+                    Status::ExecutionError
+                }
+            }
+            Command::ChecksumPages => self.checksum_pages(args.len()),
+            Command::ReadWords => self.read_words(args.len()),
+        };
+        Some(status)
+    }
+
+    fn checksum_pages(&mut self, args_len: usize) -> Status {
+        if args_len < 8 {
+            return Status::ExecutionError;
+        }
+        let address = self.request_word(0);
+        let num_pages = self.request_word(1) as usize;
+        let page_size = self.app.page_size();
+        // One 16 bit checksum per page
+        if page_size as usize > MAX_PAGE_SIZE
+            || num_pages > (MAX_MESSAGE_SIZE - RESPONSE_HEADER_SIZE) / 2
+        {
+            return Status::ExecutionError;
+        }
+
+        let mut page = [0u8; MAX_PAGE_SIZE];
+        let page = &mut page[..page_size as usize];
+        let mut page_address = address;
+        for _ in 0..num_pages {
+            if !self.app.read(page_address, page) {
+                return Status::ExecutionError;
+            }
+            self.push_response(&crc16(page).to_le_bytes());
+            page_address = page_address.wrapping_add(page_size);
+        }
+        Status::Success
+    }
+
+    fn read_words(&mut self, args_len: usize) -> Status {
+        if args_len < 8 {
+            return Status::ExecutionError;
+        }
+        let address = self.request_word(0);
+        let Some(len) = (self.request_word(1) as usize)
+            .checked_mul(4)
+            .filter(|&len| len <= MAX_MESSAGE_SIZE - RESPONSE_HEADER_SIZE)
+        else {
+            return Status::ExecutionError;
+        };
+        let data = &mut self.response[RESPONSE_HEADER_SIZE..RESPONSE_HEADER_SIZE + len];
+        if !self.app.read(address, data) {
+            return Status::ExecutionError;
+        }
+        self.response_len += len;
+        Status::Success
+    }
+
+    /// Little endian word `index` of the command arguments
+    fn request_word(&self, index: usize) -> u32 {
+        let start = COMMAND_HEADER_SIZE + index * 4;
+        u32::from_le_bytes([
+            self.request[start],
+            self.request[start + 1],
+            self.request[start + 2],
+            self.request[start + 3],
+        ])
+    }
+
+    fn push_response(&mut self, data: &[u8]) {
+        self.response[self.response_len..self.response_len + data.len()].copy_from_slice(data);
+        self.response_len += data.len();
+    }
+
+    fn peek_output(&self) -> Option<[u8; REPORT_SIZE]> {
+        let sent = self.response_sent?;
+        let len = (self.response_len - sent).min(PACKET_DATA_SIZE);
+        let packet_type = if sent + len == self.response_len {
+            PACKET_TYPE_FINAL
+        } else {
+            PACKET_TYPE_INNER
+        };
+
+        let mut packet = [0u8; REPORT_SIZE];
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            packet[0] = packet_type | len as u8;
+        }
+        packet[1..=len].copy_from_slice(&self.response[sent..sent + len]);
+        Some(packet)
+    }
+
+    fn pop_output(&mut self) {
+        if let Some(sent) = self.response_sent {
+            let sent = sent + (self.response_len - sent).min(PACKET_DATA_SIZE);
+            self.response_sent = (sent < self.response_len).then_some(sent);
+        }
+    }
+}
+
+/// CRC-16-CCITT (XMODEM) checksum used by [`Command::ChecksumPages`]
+#[must_use]
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ (u16::from(b) << 8), |crc, _| {
+            if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    struct TestApp {
+        resets: u8,
+    }
+
+    impl Hf2App for TestApp {
+        fn page_size(&self) -> u32 {
+            256
+        }
+
+        fn num_pages(&self) -> u32 {
+            64
+        }
+
+        fn info(&self) -> &'static str {
+            "UF2 Bootloader v1.0.0\r\nModel: Test Board\r\nBoard-ID: Test-Board-v1\r\n"
+        }
+
+        fn write_page(&mut self, _address: u32, _data: &[u8]) -> bool {
+            true
+        }
+
+        fn read(&mut self, _address: u32, data: &mut [u8]) -> bool {
+            data.fill(0);
+            true
+        }
+
+        fn reset_into_app(&mut self) {
+            self.resets += 1;
+        }
+    }
+
+    fn hf2() -> Box<Hf2<TestApp>> {
+        Box::new(Hf2::new(TestApp { resets: 0 }))
+    }
+
+    fn command(hf2: &mut Hf2<TestApp>, id: u32, tag: u16) -> Vec<[u8; REPORT_SIZE]> {
+        command_with_args(hf2, id, tag, &[])
+    }
+
+    fn command_with_args(
+        hf2: &mut Hf2<TestApp>,
+        id: u32,
+        tag: u16,
+        args: &[u8],
+    ) -> Vec<[u8; REPORT_SIZE]> {
+        let mut report = [0u8; REPORT_SIZE];
+        report[0] = PACKET_TYPE_FINAL | (0x08 + u8::try_from(args.len()).unwrap());
+        report[1..5].copy_from_slice(&id.to_le_bytes());
+        report[5..7].copy_from_slice(&tag.to_le_bytes());
+        report[9..9 + args.len()].copy_from_slice(args);
+        hf2.handle_report(&report);
+
+        let mut packets = Vec::new();
+        while let Some(packet) = hf2.peek_output() {
+            hf2.pop_output();
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc16(&[0; 256]), 0);
+    }
+
+    #[test]
+    fn bininfo() {
+        let mut hf2 = hf2();
+        let packets = command(&mut hf2, 0x0001, 0x1234);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0][0], PACKET_TYPE_FINAL | 0x18);
+        assert_eq!(
+            packets[0][1..25],
+            [0x34, 0x12, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 64, 0, 0, 0, 0x10, 0x04, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn info_split_across_packets() {
+        let mut hf2 = hf2();
+        let packets = command(&mut hf2, 0x0002, 7);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0][0], PACKET_TYPE_INNER | 0x3F);
+        assert_eq!(packets[1][0], PACKET_TYPE_FINAL | 0x08);
+
+        let mut response = Vec::from(&packets[0][1..]);
+        response.extend_from_slice(&packets[1][1..9]);
+        assert_eq!(response[..4], [7, 0, 0, 0]);
+        assert_eq!(&response[4..], hf2.app().info().as_bytes());
+    }
+
+    #[test]
+    fn unknown_command_and_reset() {
+        let mut hf2 = hf2();
+        let packets = command(&mut hf2, 0x0042, 1);
+        assert_eq!(packets[0][..5], [PACKET_TYPE_FINAL | 0x04, 1, 0, 1, 0]);
+
+        assert!(command(&mut hf2, 0x0003, 2).is_empty());
+        assert_eq!(hf2.app().resets, 1);
+    }
+
+    struct RamFlash {
+        memory: [u8; 1024],
+    }
+
+    impl Hf2App for RamFlash {
+        fn page_size(&self) -> u32 {
+            256
+        }
+
+        fn num_pages(&self) -> u32 {
+            4
+        }
+
+        fn write_page(&mut self, address: u32, data: &[u8]) -> bool {
+            let address = address as usize;
+            self.memory[address..address + data.len()].copy_from_slice(data);
+            true
+        }
+
+        fn read(&mut self, address: u32, data: &mut [u8]) -> bool {
+            let address = address as usize;
+            match self.memory.get(address..address + data.len()) {
+                Some(memory) => {
+                    data.copy_from_slice(memory);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_arguments_rejected() {
+        let mut hf2 = hf2();
+
+        // WRITE FLASH PAGE without an address or page data
+        let packets = command(&mut hf2, 0x0006, 1);
+        assert_eq!(packets[0][..5], [PACKET_TYPE_FINAL | 4, 1, 0, 2, 0]);
+
+        // Page and word counts that overflow the response length
+        let mut args = Vec::from(0u32.to_le_bytes());
+        args.extend_from_slice(&u32::MAX.to_le_bytes());
+        let packets = command_with_args(&mut hf2, 0x0007, 2, &args);
+        assert_eq!(packets[0][..5], [PACKET_TYPE_FINAL | 4, 2, 0, 2, 0]);
+        let packets = command_with_args(&mut hf2, 0x0008, 3, &args);
+        assert_eq!(packets[0][..5], [PACKET_TYPE_FINAL | 4, 3, 0, 2, 0]);
+    }
+
+    #[test]
+    fn hf2_write_and_verify_flash() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(hf2_config())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        let mut hf2 = Hf2::new(RamFlash {
+            memory: [0xFF; 1024],
+        });
+
+        let mut transact = |command: u32, tag: u16, args: &[u8]| {
+            let mut message = Vec::from(command.to_le_bytes());
+            message.extend_from_slice(&tag.to_le_bytes());
+            message.extend_from_slice(&[0, 0]);
+            message.extend_from_slice(args);
+
+            let mut chunks = message.chunks(63).peekable();
+            while let Some(chunk) = chunks.next() {
+                let header = if chunks.peek().is_some() { 0x00 } else { 0x40 };
+                let mut packet = Vec::from([header | u8::try_from(chunk.len()).unwrap()]);
+                packet.extend_from_slice(chunk);
+                packet.resize(64, 0);
+                manager.host_write_out(&packet).unwrap();
+                hf2.poll(hid.device()).unwrap();
+            }
+
+            let mut response = Vec::new();
+            loop {
+                let packet = manager.host_read_in();
+                let len = usize::from(packet[0] & 0x3F);
+                response.extend_from_slice(&packet[1..=len]);
+                if packet[0] & 0xC0 == 0x40 {
+                    break;
+                }
+                hf2.poll(hid.device()).unwrap();
+            }
+            response
+        };
+
+        let page: Vec<u8> = (0..=255).collect();
+        let mut args = Vec::from(256u32.to_le_bytes());
+        args.extend_from_slice(&page);
+        assert_eq!(transact(0x0006, 1, &args), [1, 0, 0, 0]);
+
+        let mut args = Vec::from(0u32.to_le_bytes());
+        args.extend_from_slice(&2u32.to_le_bytes());
+        let response = transact(0x0007, 2, &args);
+        assert_eq!(response[..4], [2, 0, 0, 0]);
+        assert_eq!(
+            response[4..],
+            [
+                crc16(&[0xFF; 256]).to_le_bytes(),
+                crc16(&page).to_le_bytes()
+            ]
+            .concat()
+        );
+
+        let mut args = Vec::from(0x104u32.to_le_bytes());
+        args.extend_from_slice(&2u32.to_le_bytes());
+        assert_eq!(
+            transact(0x0008, 3, &args),
+            [3, 0, 0, 0, 4, 5, 6, 7, 8, 9, 10, 11]
+        );
+
+        let mut args = Vec::from(0x400u32.to_le_bytes());
+        args.extend_from_slice(&1u32.to_le_bytes());
+        assert_eq!(transact(0x0008, 4, &args), [4, 0, 2, 0]);
+    }
+}
@@ -0,0 +1,787 @@
+//!HID LampArray, as used by Windows Dynamic Lighting
+//!
+//! The layout, purpose and color capabilities of each lamp are supplied by the application. Color
+//! updates from the host are queued and read with [`LampArray::read_update`].
+//!
+//! See the [Microsoft LampArray documentation](https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/dynamic-lighting-devices)
+//! for protocol detail
+use fugit::ExtU32;
+use heapless::Deque;
+use num_enum::IntoPrimitive;
+#[allow(clippy::wildcard_imports)]
+use usb_device::class_prelude::*;
+
+use crate::page::Keyboard;
+use crate::usb_class::prelude::*;
+use crate::usb_class::{BuilderResult, UsbHidBuilderError};
+
+/// Maximum number of lamps updated by a single [`LampArrayUpdate::Multi`]
+pub const MAX_MULTI_UPDATE_LAMPS: usize = 8;
+
+const ATTRIBUTES_REPORT_ID: u8 = 0x01;
+const ATTRIBUTES_REQUEST_REPORT_ID: u8 = 0x02;
+const ATTRIBUTES_RESPONSE_REPORT_ID: u8 = 0x03;
+const MULTI_UPDATE_REPORT_ID: u8 = 0x04;
+const RANGE_UPDATE_REPORT_ID: u8 = 0x05;
+const CONTROL_REPORT_ID: u8 = 0x06;
+
+const ATTRIBUTES_REPORT_SIZE: usize = 23;
+const ATTRIBUTES_RESPONSE_REPORT_SIZE: usize = 29;
+const MULTI_UPDATE_REPORT_SIZE: usize = 51;
+const RANGE_UPDATE_REPORT_SIZE: usize = 10;
+const CONTROL_REPORT_SIZE: usize = 2;
+
+const LAMP_UPDATE_COMPLETE: u8 = 0x01;
+const UPDATE_QUEUE_SIZE: usize = 8;
+
+#[allow(clippy::doc_markdown)]
+///LampArray report descriptor
+///
+/// All reports are feature reports:
+/// * Report 1 - LampArrayAttributesReport, lamp count and bounding box of the array
+/// * Report 2 - LampAttributesRequestReport, selects the lamp returned by report 3
+/// * Report 3 - LampAttributesResponseReport, position and capabilities of a lamp
+/// * Report 4 - LampMultiUpdateReport, colors of up to 8 lamps
+/// * Report 5 - LampRangeUpdateReport, color of a range of lamps
+/// * Report 6 - LampArrayControlReport, autonomous mode
+#[rustfmt::skip]
+pub const LAMP_ARRAY_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x59, // Usage Page (Lighting And Illumination),
+    0x09, 0x01, // Usage (LampArray),
+    0xA1, 0x01, // Collection (Application),
+    0x85, ATTRIBUTES_REPORT_ID, //     Report ID (1),
+    0x09, 0x02, //     Usage (LampArrayAttributesReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x03, //         Usage (LampCount),
+    0x15, 0x00, //         Logical Minimum (0),
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //         Logical Maximum (65535),
+    0x75, 0x10, //         Report Size (16),
+    0x95, 0x01, //         Report Count (1),
+    0xB1, 0x03, //         Feature (Constant, Variable, Absolute),
+    0x09, 0x04, //         Usage (BoundingBoxWidthInMicrometers),
+    0x09, 0x05, //         Usage (BoundingBoxHeightInMicrometers),
+    0x09, 0x06, //         Usage (BoundingBoxDepthInMicrometers),
+    0x09, 0x07, //         Usage (LampArrayKind),
+    0x09, 0x08, //         Usage (MinUpdateIntervalInMicroseconds),
+    0x27, 0xFF, 0xFF, 0xFF, 0x7F, //         Logical Maximum (2147483647),
+    0x75, 0x20, //         Report Size (32),
+    0x95, 0x05, //         Report Count (5),
+    0xB1, 0x03, //         Feature (Constant, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0x85, ATTRIBUTES_REQUEST_REPORT_ID, //     Report ID (2),
+    0x09, 0x20, //     Usage (LampAttributesRequestReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x21, //         Usage (LampId),
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //         Logical Maximum (65535),
+    0x75, 0x10, //         Report Size (16),
+    0x95, 0x01, //         Report Count (1),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0x85, ATTRIBUTES_RESPONSE_REPORT_ID, //     Report ID (3),
+    0x09, 0x22, //     Usage (LampAttributesResponseReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x21, //         Usage (LampId),
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //         Logical Maximum (65535),
+    0x75, 0x10, //         Report Size (16),
+    0x95, 0x01, //         Report Count (1),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x23, //         Usage (PositionXInMicrometers),
+    0x09, 0x24, //         Usage (PositionYInMicrometers),
+    0x09, 0x25, //         Usage (PositionZInMicrometers),
+    0x09, 0x27, //         Usage (UpdateLatencyInMicroseconds),
+    0x09, 0x26, //         Usage (LampPurposes),
+    0x27, 0xFF, 0xFF, 0xFF, 0x7F, //         Logical Maximum (2147483647),
+    0x75, 0x20, //         Report Size (32),
+    0x95, 0x05, //         Report Count (5),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x28, //         Usage (RedLevelCount),
+    0x09, 0x29, //         Usage (GreenLevelCount),
+    0x09, 0x2A, //         Usage (BlueLevelCount),
+    0x09, 0x2B, //         Usage (IntensityLevelCount),
+    0x09, 0x2C, //         Usage (IsProgrammable),
+    0x09, 0x2D, //         Usage (InputBinding),
+    0x26, 0xFF, 0x00, //         Logical Maximum (255),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x06, //         Report Count (6),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0x85, MULTI_UPDATE_REPORT_ID, //     Report ID (4),
+    0x09, 0x50, //     Usage (LampMultiUpdateReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x03, //         Usage (LampCount),
+    0x09, 0x55, //         Usage (LampUpdateFlags),
+    0x25, 0x08, //         Logical Maximum (8),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x02, //         Report Count (2),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x21, //         Usage (LampId),
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //         Logical Maximum (65535),
+    0x75, 0x10, //         Report Size (16),
+    0x95, 0x08, //         Report Count (8),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x26, 0xFF, 0x00, //         Logical Maximum (255),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x20, //         Report Count (32),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0x85, RANGE_UPDATE_REPORT_ID, //     Report ID (5),
+    0x09, 0x60, //     Usage (LampRangeUpdateReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x55, //         Usage (LampUpdateFlags),
+    0x25, 0x08, //         Logical Maximum (8),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x01, //         Report Count (1),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x61, //         Usage (LampIdStart),
+    0x09, 0x62, //         Usage (LampIdEnd),
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //         Logical Maximum (65535),
+    0x75, 0x10, //         Report Size (16),
+    0x95, 0x02, //         Report Count (2),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0x09, 0x51, //         Usage (RedUpdateChannel),
+    0x09, 0x52, //         Usage (GreenUpdateChannel),
+    0x09, 0x53, //         Usage (BlueUpdateChannel),
+    0x09, 0x54, //         Usage (IntensityUpdateChannel),
+    0x26, 0xFF, 0x00, //         Logical Maximum (255),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x04, //         Report Count (4),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0x85, CONTROL_REPORT_ID, //     Report ID (6),
+    0x09, 0x70, //     Usage (LampArrayControlReport),
+    0xA1, 0x02, //     Collection (Logical),
+    0x09, 0x71, //         Usage (AutonomousMode),
+    0x25, 0x01, //         Logical Maximum (1),
+    0x75, 0x08, //         Report Size (8),
+    0x95, 0x01, //         Report Count (1),
+    0xB1, 0x02, //         Feature (Data, Variable, Absolute),
+    0xC0,       //     End Collection,
+    0xC0,       // End Collection
+];
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, IntoPrimitive)]
+#[repr(u32)]
+pub enum LampArrayKind {
+    #[default]
+    Undefined = 0x00,
+    Keyboard = 0x01,
+    Mouse = 0x02,
+    GameController = 0x03,
+    Peripheral = 0x04,
+    Scene = 0x05,
+    Notification = 0x06,
+    Chassis = 0x07,
+    Wearable = 0x08,
+    Furniture = 0x09,
+    Art = 0x0A,
+}
+
+/// Attributes of the whole lamp array
+///
+/// Dimensions are in micrometers and intervals in microseconds
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct LampArrayAttributes {
+    pub bounding_box_width: u32,
+    pub bounding_box_height: u32,
+    pub bounding_box_depth: u32,
+    pub kind: LampArrayKind,
+    /// Minimum interval between updates the lamp array supports
+    pub min_update_interval: u32,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct LampPurposes {
+    /// Lamp illuminates a control, e.g. a key
+    pub control: bool,
+    pub accent: bool,
+    pub branding: bool,
+    pub status: bool,
+    pub illumination: bool,
+    pub presentation: bool,
+}
+
+impl LampPurposes {
+    #[must_use]
+    pub fn bits(&self) -> u32 {
+        [
+            self.control,
+            self.accent,
+            self.branding,
+            self.status,
+            self.illumination,
+            self.presentation,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &set)| bits | (u32::from(set) << i))
+    }
+}
+
+/// Position and capabilities of a single lamp
+///
+/// Positions are in micrometers from the top left corner of the bounding box, latency is in
+/// microseconds
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LampAttributes {
+    pub position_x: u32,
+    pub position_y: u32,
+    pub position_z: u32,
+    pub update_latency: u32,
+    pub purposes: LampPurposes,
+    /// Number of distinct red levels the lamp supports, 1 for a lamp without red
+    pub red_level_count: u8,
+    pub green_level_count: u8,
+    pub blue_level_count: u8,
+    pub intensity_level_count: u8,
+    /// `false` if the lamp color is fixed and only the intensity can be changed
+    pub programmable: bool,
+    /// Key the lamp is associated with, [`Keyboard::NoEventIndicated`] if none
+    pub input_binding: Keyboard,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct LampColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub intensity: u8,
+}
+
+impl LampColor {
+    fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            red: data[0],
+            green: data[1],
+            blue: data[2],
+            intensity: data[3],
+        }
+    }
+}
+
+/// Color update received from the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LampArrayUpdate {
+    /// Individual colors for up to [`MAX_MULTI_UPDATE_LAMPS`] lamps
+    Multi {
+        count: u8,
+        lamp_ids: [u16; MAX_MULTI_UPDATE_LAMPS],
+        colors: [LampColor; MAX_MULTI_UPDATE_LAMPS],
+        complete: bool,
+    },
+    /// A single color for lamps `start` to `end` inclusive
+    Range {
+        start: u16,
+        end: u16,
+        color: LampColor,
+        complete: bool,
+    },
+}
+
+impl LampArrayUpdate {
+    /// `true` if this is the last update of a frame and the colors should be shown
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        match self {
+            Self::Multi { complete, .. } | Self::Range { complete, .. } => *complete,
+        }
+    }
+
+    /// Iterate over the lamp IDs and colors of the update
+    ///
+    /// A `count` above [`MAX_MULTI_UPDATE_LAMPS`] is limited to it and a range with `start`
+    /// after `end` has no lamps.
+    pub fn lamps(&self) -> impl Iterator<Item = (u16, LampColor)> + '_ {
+        let len = match self {
+            Self::Multi { count, .. } => usize::from(*count).min(MAX_MULTI_UPDATE_LAMPS),
+            Self::Range { start, end, .. } if start > end => 0,
+            Self::Range { start, end, .. } => usize::from(end - start) + 1,
+        };
+        (0..len).map(move |i| match self {
+            Self::Multi {
+                lamp_ids, colors, ..
+            } => (lamp_ids[i], colors[i]),
+            #[allow(clippy::cast_possible_truncation)]
+            Self::Range { start, color, .. } => (start + i as u16, *color),
+        })
+    }
+}
+
+/// Interface implementing a LampArray
+///
+/// The host takes control of the lamps by disabling autonomous mode, see
+/// [`LampArray::autonomous_mode`]. While in autonomous mode the device is free to show its own
+/// lighting effects.
+pub struct LampArray<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    attributes: LampArrayAttributes,
+    lamps: &'a [LampAttributes],
+    next_lamp_id: u16,
+    autonomous_mode: bool,
+    updates: Deque<LampArrayUpdate, UPDATE_QUEUE_SIZE>,
+    feature_report: [u8; ATTRIBUTES_RESPONSE_REPORT_SIZE],
+}
+
+impl<B: UsbBus> LampArray<'_, B> {
+    /// Read the next color update received from the host
+    pub fn read_update(&mut self) -> Option<LampArrayUpdate> {
+        self.updates.pop_front()
+    }
+
+    #[must_use]
+    pub fn autonomous_mode(&self) -> bool {
+        self.autonomous_mode
+    }
+
+    fn lamp_count(&self) -> u16 {
+        // limited by LampArrayConfig
+        unwrap!(u16::try_from(self.lamps.len()).ok())
+    }
+
+    fn pack_attributes(&mut self) {
+        let lamp_count = self.lamp_count();
+        let a = &self.attributes;
+        let report = &mut self.feature_report;
+        report[0] = ATTRIBUTES_REPORT_ID;
+        report[1..3].copy_from_slice(&lamp_count.to_le_bytes());
+        let words = [
+            a.bounding_box_width,
+            a.bounding_box_height,
+            a.bounding_box_depth,
+            a.kind.into(),
+            a.min_update_interval,
+        ];
+        for (chunk, word) in report[3..ATTRIBUTES_REPORT_SIZE]
+            .chunks_exact_mut(4)
+            .zip(words)
+        {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    /// Pack the attributes of the requested lamp and advance to the next lamp
+    fn pack_lamp_attributes(&mut self) -> Option<()> {
+        let lamp_id = self.next_lamp_id;
+        let lamp = self.lamps.get(usize::from(lamp_id))?;
+        self.next_lamp_id = if lamp_id + 1 < self.lamp_count() {
+            lamp_id + 1
+        } else {
+            0
+        };
+
+        let report = &mut self.feature_report;
+        report[0] = ATTRIBUTES_RESPONSE_REPORT_ID;
+        report[1..3].copy_from_slice(&lamp_id.to_le_bytes());
+        let words = [
+            lamp.position_x,
+            lamp.position_y,
+            lamp.position_z,
+            lamp.update_latency,
+            lamp.purposes.bits(),
+        ];
+        for (chunk, word) in report[3..23].chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        report[23..].copy_from_slice(&[
+            lamp.red_level_count,
+            lamp.green_level_count,
+            lamp.blue_level_count,
+            lamp.intensity_level_count,
+            lamp.programmable.into(),
+            lamp.input_binding.into(),
+        ]);
+        Some(())
+    }
+
+    fn parse_update(&self, report_id: u8, data: &[u8]) -> usb_device::Result<LampArrayUpdate> {
+        let lamp_count = self.lamp_count();
+        match report_id {
+            MULTI_UPDATE_REPORT_ID if data.len() == MULTI_UPDATE_REPORT_SIZE => {
+                let count = data[1];
+                let mut lamp_ids = [0; MAX_MULTI_UPDATE_LAMPS];
+                let mut colors = [LampColor::default(); MAX_MULTI_UPDATE_LAMPS];
+                if usize::from(count) > MAX_MULTI_UPDATE_LAMPS {
+                    return Err(UsbError::ParseError);
+                }
+                for i in 0..usize::from(count) {
+                    lamp_ids[i] = u16::from_le_bytes([data[3 + 2 * i], data[4 + 2 * i]]);
+                    colors[i] = LampColor::from_bytes(&data[19 + 4 * i..]);
+                    if lamp_ids[i] >= lamp_count {
+                        return Err(UsbError::ParseError);
+                    }
+                }
+                Ok(LampArrayUpdate::Multi {
+                    count,
+                    lamp_ids,
+                    colors,
+                    complete: data[2] & LAMP_UPDATE_COMPLETE != 0,
+                })
+            }
+            RANGE_UPDATE_REPORT_ID if data.len() == RANGE_UPDATE_REPORT_SIZE => {
+                let start = u16::from_le_bytes([data[2], data[3]]);
+                let end = u16::from_le_bytes([data[4], data[5]]);
+                if start > end || end >= lamp_count {
+                    return Err(UsbError::ParseError);
+                }
+                Ok(LampArrayUpdate::Range {
+                    start,
+                    end,
+                    color: LampColor::from_bytes(&data[6..]),
+                    complete: data[1] & LAMP_UPDATE_COMPLETE != 0,
+                })
+            }
+            _ => Err(UsbError::ParseError),
+        }
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for LampArray<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.next_lamp_id = 0;
+        self.autonomous_mode = true;
+        self.updates.clear();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        let len = match report_id {
+            ATTRIBUTES_REPORT_ID => {
+                self.pack_attributes();
+                ATTRIBUTES_REPORT_SIZE
+            }
+            ATTRIBUTES_RESPONSE_REPORT_ID => {
+                self.pack_lamp_attributes()?;
+                ATTRIBUTES_RESPONSE_REPORT_SIZE
+            }
+            CONTROL_REPORT_ID => {
+                self.feature_report[..CONTROL_REPORT_SIZE]
+                    .copy_from_slice(&[CONTROL_REPORT_ID, self.autonomous_mode.into()]);
+                CONTROL_REPORT_SIZE
+            }
+            _ => return None,
+        };
+        Some(FeatureReport::DynamicReport(&self.feature_report[..len]))
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        match report_id {
+            ATTRIBUTES_REQUEST_REPORT_ID if data.len() == 3 => {
+                let lamp_id = u16::from_le_bytes([data[1], data[2]]);
+                // Out of range requests restart from the first lamp
+                self.next_lamp_id = if lamp_id < self.lamp_count() {
+                    lamp_id
+                } else {
+                    0
+                };
+                Ok(())
+            }
+            CONTROL_REPORT_ID if data.len() == CONTROL_REPORT_SIZE => {
+                self.autonomous_mode = data[1] != 0;
+                Ok(())
+            }
+            _ => {
+                let update = self.parse_update(report_id, data)?;
+                self.updates.push_back(update).map_err(|_| {
+                    warn!("LampArray update queue full");
+                    UsbError::BufferOverflow
+                })
+            }
+        }
+    }
+}
+
+pub struct LampArrayConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    attributes: LampArrayAttributes,
+    lamps: &'a [LampAttributes],
+}
+
+impl<'a> LampArrayConfig<'a> {
+    /// Create a configuration for a lamp array with `lamps`, lamp IDs are indices into `lamps`
+    ///
+    /// Returns [`UsbHidBuilderError::SliceLengthOverflow`] for more than `u16::MAX` lamps
+    pub fn new(
+        attributes: LampArrayAttributes,
+        lamps: &'a [LampAttributes],
+    ) -> BuilderResult<Self> {
+        Self::with_interface(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                LAMP_ARRAY_REPORT_DESCRIPTOR
+            ))
+            .description("LampArray")
+            .in_endpoint(100.millis()))
+            .without_out_endpoint()
+            .build(),
+            attributes,
+            lamps,
+        )
+    }
+
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+        attributes: LampArrayAttributes,
+        lamps: &'a [LampAttributes],
+    ) -> BuilderResult<Self> {
+        if u16::try_from(lamps.len()).is_err() {
+            return Err(UsbHidBuilderError::SliceLengthOverflow);
+        }
+        Ok(Self {
+            interface,
+            attributes,
+            lamps,
+        })
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for LampArrayConfig<'a> {
+    type Allocated = LampArray<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            attributes: self.attributes,
+            lamps: self.lamps,
+            next_lamp_id: 0,
+            autonomous_mode: true,
+            updates: Deque::new(),
+            feature_report: [0; ATTRIBUTES_RESPONSE_REPORT_SIZE],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn lamp_purposes_bits() {
+        let purposes = LampPurposes {
+            control: true,
+            status: true,
+            ..Default::default()
+        };
+        assert_eq!(purposes.bits(), 0b1001);
+    }
+
+    #[test]
+    fn range_update_lamps() {
+        let color = LampColor {
+            red: 0xFF,
+            ..Default::default()
+        };
+        let update = LampArrayUpdate::Range {
+            start: 3,
+            end: 5,
+            color,
+            complete: true,
+        };
+        assert!(update.is_complete());
+        assert_eq!(
+            update.lamps().collect::<Vec<_>>(),
+            [(3, color), (4, color), (5, color)]
+        );
+    }
+
+    #[test]
+    fn malformed_updates_limited() {
+        let color = LampColor::default();
+        let reversed = LampArrayUpdate::Range {
+            start: 5,
+            end: 3,
+            color,
+            complete: false,
+        };
+        assert_eq!(reversed.lamps().count(), 0);
+
+        let multi = LampArrayUpdate::Multi {
+            count: u8::MAX,
+            lamp_ids: [7; MAX_MULTI_UPDATE_LAMPS],
+            colors: [color; MAX_MULTI_UPDATE_LAMPS],
+            complete: true,
+        };
+        assert_eq!(multi.lamps().count(), MAX_MULTI_UPDATE_LAMPS);
+        assert!(multi.lamps().all(|(id, _)| id == 7));
+    }
+
+    const LAMP: LampAttributes = LampAttributes {
+        position_x: 10_000,
+        position_y: 20_000,
+        position_z: 0,
+        update_latency: 1000,
+        purposes: LampPurposes {
+            control: true,
+            accent: false,
+            branding: false,
+            status: false,
+            illumination: false,
+            presentation: false,
+        },
+        red_level_count: 255,
+        green_level_count: 255,
+        blue_level_count: 255,
+        intensity_level_count: 1,
+        programmable: true,
+        input_binding: Keyboard::A,
+    };
+
+    static LAMPS: [LampAttributes; 2] = [
+        LAMP,
+        LampAttributes {
+            position_x: 30_000,
+            input_binding: Keyboard::B,
+            ..LAMP
+        },
+    ];
+
+    const ATTRIBUTES: LampArrayAttributes = LampArrayAttributes {
+        bounding_box_width: 40_000,
+        bounding_box_height: 30_000,
+        bounding_box_depth: 5_000,
+        kind: LampArrayKind::Keyboard,
+        min_update_interval: 10_000,
+    };
+
+    fn lamp_array_config() -> LampArrayConfig<'static> {
+        LampArrayConfig::new(ATTRIBUTES, &LAMPS).unwrap()
+    }
+
+    #[test]
+    fn lamp_count_limited_to_lamp_ids() {
+        let lamps = std::vec![LAMP; usize::from(u16::MAX) + 1];
+        assert!(matches!(
+            LampArrayConfig::new(ATTRIBUTES, &lamps),
+            Err(UsbHidBuilderError::SliceLengthOverflow)
+        ));
+        assert!(LampArrayConfig::new(ATTRIBUTES, &lamps[1..]).is_ok());
+    }
+
+    #[test]
+    fn lamp_array_attributes_and_updates() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(lamp_array_config())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        let attributes = manager.host_get_feature(&mut usb_dev, &mut hid, 0x01, 23);
+        assert_eq!(attributes[..7], [0x01, 2, 0, 0x40, 0x9C, 0x00, 0x00]);
+        assert_eq!(attributes[15..19], [1, 0, 0, 0]);
+
+        // Lamp attributes are returned in order after the first
+        let lamp = manager.host_get_feature(&mut usb_dev, &mut hid, 0x03, 29);
+        assert_eq!(lamp[..7], [0x03, 0, 0, 0x10, 0x27, 0x00, 0x00]);
+        assert_eq!(lamp[19..], [1, 0, 0, 0, 255, 255, 255, 1, 1, 0x04]);
+        let lamp = manager.host_get_feature(&mut usb_dev, &mut hid, 0x03, 29);
+        assert_eq!(lamp[..7], [0x03, 1, 0, 0x30, 0x75, 0x00, 0x00]);
+        assert_eq!(lamp[28], 0x05);
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x06, 2),
+            [0x06, 1]
+        );
+
+        // Disable autonomous mode
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0x06, &[0x06, 0]);
+
+        // Multi update of lamp 1
+        let mut multi = Vec::from([0x04, 1, 0x01, 1, 0]);
+        multi.resize(19, 0);
+        multi.extend_from_slice(&[0x10, 0x20, 0x30, 1]);
+        multi.resize(51, 0);
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0x04, &multi);
+
+        // Range update of both lamps
+        manager.host_set_feature(
+            &mut usb_dev,
+            &mut hid,
+            0x05,
+            &[0x05, 0x00, 0, 0, 1, 0, 0xFF, 0, 0, 1],
+        );
+
+        assert!(!hid.device().autonomous_mode());
+
+        let update = hid.device().read_update().unwrap();
+        assert!(update.is_complete());
+        assert_eq!(
+            update.lamps().collect::<Vec<_>>(),
+            [(
+                1,
+                LampColor {
+                    red: 0x10,
+                    green: 0x20,
+                    blue: 0x30,
+                    intensity: 1
+                }
+            )]
+        );
+        let color = LampColor {
+            red: 0xFF,
+            green: 0,
+            blue: 0,
+            intensity: 1,
+        };
+        assert_eq!(
+            hid.device().read_update(),
+            Some(LampArrayUpdate::Range {
+                start: 0,
+                end: 1,
+                color,
+                complete: false
+            })
+        );
+        assert_eq!(hid.device().read_update(), None);
+    }
+}
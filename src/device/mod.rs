@@ -1,18 +1,38 @@
 //! Concrete implementation of Human Interface Devices
 
-use crate::interface::InterfaceClass;
+use crate::interface::{FeatureReport, InterfaceClass};
 use crate::UsbHidError;
 use frunk::{HCons, HNil, ToMut};
 #[allow(clippy::wildcard_imports)]
 use usb_device::class_prelude::*;
 use usb_device::descriptor::lang_id::LangID;
 
+pub mod absolute;
+pub mod aux_display;
+pub mod barcode;
+pub mod battery;
+pub mod braille;
+pub mod camera;
+#[cfg(feature = "console")]
+pub mod console;
 pub mod consumer;
 pub mod fido;
+pub mod haptics;
+pub mod hf2;
 pub mod joystick;
 pub mod keyboard;
+pub mod lamp_array;
 pub mod mouse;
 pub mod multiaxis;
+pub mod power;
+pub mod radial;
+pub mod raw;
+pub mod sensor;
+pub mod system;
+pub mod telephony;
+pub mod touchpad;
+pub mod touchscreen;
+pub mod via;
 
 pub trait DeviceClass<'a> {
     type I: InterfaceClass<'a>;
@@ -21,6 +41,25 @@ pub trait DeviceClass<'a> {
     fn reset(&mut self);
     /// Called every 1ms
     fn tick(&mut self) -> Result<(), UsbHidError>;
+    /// Called when the host requests a feature report with `GET_REPORT`
+    ///
+    /// Returns `None` if the device does not support the requested report
+    fn get_feature_report(&mut self, _report_id: u8) -> Option<FeatureReport<'_>> {
+        None
+    }
+    /// Called when the host sends a feature report with `SET_REPORT`
+    ///
+    /// `data` includes the report ID as the first byte if the interface uses report IDs
+    fn set_feature_report(&mut self, _report_id: u8, _data: &[u8]) -> usb_device::Result<()> {
+        Err(UsbError::Unsupported)
+    }
+    /// Called when the host requests a string descriptor not used by the interface
+    ///
+    /// Devices referencing strings from their reports allocate the indices with
+    /// [`UsbBusAllocator::string()`]
+    fn get_string(&self, _index: StringIndex, _lang_id: LangID) -> Option<&'a str> {
+        None
+    }
 }
 
 pub trait DeviceHList<'a>: ToMut<'a> {
@@ -29,6 +68,8 @@ pub trait DeviceHList<'a>: ToMut<'a> {
     fn write_descriptors(&mut self, writer: &mut DescriptorWriter) -> usb_device::Result<()>;
     fn get_string(&mut self, index: StringIndex, lang_id: LangID) -> Option<&'a str>;
     fn tick(&mut self) -> Result<(), UsbHidError>;
+    fn get_feature_report(&mut self, id: u8, report_id: u8) -> Option<FeatureReport<'_>>;
+    fn set_feature_report(&mut self, id: u8, report_id: u8, data: &[u8]) -> usb_device::Result<()>;
 }
 
 impl<'a> DeviceHList<'a> for HNil {
@@ -49,6 +90,14 @@ impl<'a> DeviceHList<'a> for HNil {
     fn tick(&mut self) -> Result<(), UsbHidError> {
         Ok(())
     }
+
+    fn get_feature_report(&mut self, _: u8, _: u8) -> Option<FeatureReport<'_>> {
+        None
+    }
+
+    fn set_feature_report(&mut self, _: u8, _: u8, _: &[u8]) -> usb_device::Result<()> {
+        Err(UsbError::Unsupported)
+    }
 }
 
 impl<'a, Head: DeviceClass<'a> + 'a, Tail: DeviceHList<'a>> DeviceHList<'a> for HCons<Head, Tail> {
@@ -72,16 +121,31 @@ impl<'a, Head: DeviceClass<'a> + 'a, Tail: DeviceHList<'a>> DeviceHList<'a> for
     }
 
     fn get_string(&mut self, index: StringIndex, lang_id: LangID) -> Option<&'a str> {
-        let s = self.head.interface().get_string(index, lang_id);
-        if s.is_some() {
-            s
-        } else {
-            self.tail.get_string(index, lang_id)
-        }
+        self.head
+            .interface()
+            .get_string(index, lang_id)
+            .or_else(|| self.head.get_string(index, lang_id))
+            .or_else(|| self.tail.get_string(index, lang_id))
     }
 
     fn tick(&mut self) -> Result<(), UsbHidError> {
         self.head.tick()?;
         self.tail.tick()
     }
+
+    fn get_feature_report(&mut self, id: u8, report_id: u8) -> Option<FeatureReport<'_>> {
+        if id == u8::from(self.head.interface().id()) {
+            self.head.get_feature_report(report_id)
+        } else {
+            self.tail.get_feature_report(id, report_id)
+        }
+    }
+
+    fn set_feature_report(&mut self, id: u8, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        if id == u8::from(self.head.interface().id()) {
+            self.head.set_feature_report(report_id, data)
+        } else {
+            self.tail.set_feature_report(id, report_id, data)
+        }
+    }
 }
@@ -3,8 +3,10 @@ use crate::usb_class::prelude::*;
 use core::default::Default;
 use fugit::ExtU32;
 use packed_struct::prelude::*;
+use packed_struct::{PackedStructInfo, PackingResult};
 use usb_device::bus::UsbBus;
 use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
 
 /// HID Mouse report descriptor conforming to the Boot specification
 ///
@@ -111,6 +113,285 @@ pub struct WheelMouseReport {
     pub horizontal_wheel: i8,
 }
 
+/// Wheel units per detent once the host enables the resolution multiplier
+pub const RESOLUTION_MULTIPLIER: i16 = 120;
+
+/// Boot compatible mouse with eight buttons and high-resolution 16-bit wheel and pan
+///
+/// The wheel and pan each have a Resolution Multiplier feature control, when the host sets it
+/// wheel values are in [`RESOLUTION_MULTIPLIER`] units per detent, otherwise in whole detents.
+/// The feature report is a single byte, bits 0-1 for the wheel and bits 2-3 for pan.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/enhanced-wheel-support>
+#[rustfmt::skip]
+pub const HI_RES_WHEEL_MOUSE_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop),
+    0x09, 0x02,        // Usage (Mouse),
+    0xA1, 0x01,        // Collection (Application),
+    0x09, 0x01,        //   Usage (Pointer),
+    0xA1, 0x00,        //   Collection (Physical),
+    0x95, 0x08,        //     Report Count (8),
+    0x75, 0x01,        //     Report Size (1),
+    0x05, 0x09,        //     Usage Page (Buttons),
+    0x19, 0x01,        //     Usage Minimum (1),
+    0x29, 0x08,        //     Usage Maximum (8),
+    0x15, 0x00,        //     Logical Minimum (0),
+    0x25, 0x01,        //     Logical Maximum (1),
+    0x81, 0x02,        //     Input (Data, Variable, Absolute),
+
+    0x75, 0x08,        //     Report Size (8),
+    0x95, 0x02,        //     Report Count (2),
+    0x05, 0x01,        //     Usage Page (Generic Desktop),
+    0x09, 0x30,        //     Usage (X),
+    0x09, 0x31,        //     Usage (Y),
+    0x15, 0x81,        //     Logical Minimum (-127),
+    0x25, 0x7F,        //     Logical Maximum (127),
+    0x81, 0x06,        //     Input (Data, Variable, Relative),
+
+    0xA1, 0x02,        //     Collection (Logical),
+    0x09, 0x48,        //       Usage (Resolution Multiplier),
+    0x15, 0x00,        //       Logical Minimum (0),
+    0x25, 0x01,        //       Logical Maximum (1),
+    0x35, 0x01,        //       Physical Minimum (1),
+    0x45, 0x78,        //       Physical Maximum (120),
+    0x75, 0x02,        //       Report Size (2),
+    0x95, 0x01,        //       Report Count (1),
+    0xB1, 0x02,        //       Feature (Data, Variable, Absolute),
+    0x35, 0x00,        //       Physical Minimum (0),
+    0x45, 0x00,        //       Physical Maximum (0),
+    0x09, 0x38,        //       Usage (Wheel),
+    0x16, 0x01, 0x80,  //       Logical Minimum (-32767),
+    0x26, 0xFF, 0x7F,  //       Logical Maximum (32767),
+    0x75, 0x10,        //       Report Size (16),
+    0x81, 0x06,        //       Input (Data, Variable, Relative),
+    0xC0,              //     End Collection,
+
+    0xA1, 0x02,        //     Collection (Logical),
+    0x09, 0x48,        //       Usage (Resolution Multiplier),
+    0x15, 0x00,        //       Logical Minimum (0),
+    0x25, 0x01,        //       Logical Maximum (1),
+    0x35, 0x01,        //       Physical Minimum (1),
+    0x45, 0x78,        //       Physical Maximum (120),
+    0x75, 0x02,        //       Report Size (2),
+    0xB1, 0x02,        //       Feature (Data, Variable, Absolute),
+    0x35, 0x00,        //       Physical Minimum (0),
+    0x45, 0x00,        //       Physical Maximum (0),
+    0x05, 0x0C,        //       Usage Page (Consumer),
+    0x0A, 0x38, 0x02,  //       Usage (AC Pan),
+    0x16, 0x01, 0x80,  //       Logical Minimum (-32767),
+    0x26, 0xFF, 0x7F,  //       Logical Maximum (32767),
+    0x75, 0x10,        //       Report Size (16),
+    0x81, 0x06,        //       Input (Data, Variable, Relative),
+    0xC0,              //     End Collection,
+
+    0x75, 0x04,        //     Report Size (4),
+    0xB1, 0x03,        //     Feature (Constant, Variable, Absolute),
+    0xC0,              //   End Collection
+    0xC0,              // End Collection
+];
+
+/// High-resolution wheel mouse report
+///
+/// `vertical_wheel` and `horizontal_wheel` are always in [`RESOLUTION_MULTIPLIER`] units per
+/// detent, [`HiResWheelMouse`] scales them to whole detents if the host has not enabled the
+/// resolution multiplier.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", size_bytes = "7")]
+pub struct HiResWheelMouseReport {
+    #[packed_field]
+    pub buttons: u8,
+    #[packed_field]
+    pub x: i8,
+    #[packed_field]
+    pub y: i8,
+    #[packed_field]
+    pub vertical_wheel: i16,
+    #[packed_field]
+    pub horizontal_wheel: i16,
+}
+
+/// Mouse button, numbered as in the HID Button usage page
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(u8)]
+pub enum MouseButton {
+    Left = 1,
+    Right = 2,
+    Middle = 3,
+    Back = 4,
+    Forward = 5,
+    Button6 = 6,
+    Button7 = 7,
+    Button8 = 8,
+    Button9 = 9,
+    Button10 = 10,
+    Button11 = 11,
+    Button12 = 12,
+    Button13 = 13,
+    Button14 = 14,
+    Button15 = 15,
+    Button16 = 16,
+}
+
+impl MouseButton {
+    const fn bit(self) -> u16 {
+        1 << (self as u8 - 1)
+    }
+}
+
+/// Set of pressed [`MouseButton`]s
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, Hash)]
+pub struct MouseButtons(u16);
+
+impl MouseButtons {
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Create a set from a bitmap, bit 0 is [`MouseButton::Left`]
+    #[must_use]
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    #[must_use]
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Copy of the set with `button` pressed
+    #[must_use]
+    pub const fn with(self, button: MouseButton) -> Self {
+        Self(self.0 | button.bit())
+    }
+
+    pub fn insert(&mut self, button: MouseButton) {
+        self.0 |= button.bit();
+    }
+
+    pub fn remove(&mut self, button: MouseButton) {
+        self.0 &= !button.bit();
+    }
+
+    pub fn set(&mut self, button: MouseButton, pressed: bool) {
+        if pressed {
+            self.insert(button);
+        } else {
+            self.remove(button);
+        }
+    }
+
+    #[must_use]
+    pub const fn contains(self, button: MouseButton) -> bool {
+        self.0 & button.bit() != 0
+    }
+}
+
+impl From<MouseButton> for MouseButtons {
+    fn from(button: MouseButton) -> Self {
+        Self::empty().with(button)
+    }
+}
+
+impl FromIterator<MouseButton> for MouseButtons {
+    fn from_iter<T: IntoIterator<Item = MouseButton>>(iter: T) -> Self {
+        iter.into_iter().fold(Self::empty(), Self::with)
+    }
+}
+
+impl PackedStruct for MouseButtons {
+    type ByteArray = [u8; 2];
+
+    fn pack(&self) -> PackingResult<Self::ByteArray> {
+        Ok(self.0.to_le_bytes())
+    }
+
+    fn unpack(src: &Self::ByteArray) -> PackingResult<Self> {
+        Ok(Self(u16::from_le_bytes(*src)))
+    }
+}
+
+impl PackedStructInfo for MouseButtons {
+    fn packed_bits() -> usize {
+        16
+    }
+}
+
+/// Relative mouse with sixteen buttons, 16-bit X and Y, wheel and pan
+///
+/// Suited to high-DPI sensors that overflow 8-bit motion. Boot mouse reports are sent while the
+/// host has selected [`HidProtocol::Boot`].
+#[rustfmt::skip]
+pub const RELATIVE_MOUSE_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop),
+    0x09, 0x02,        // Usage (Mouse),
+    0xA1, 0x01,        // Collection (Application),
+    0x09, 0x01,        //   Usage (Pointer),
+    0xA1, 0x00,        //   Collection (Physical),
+    0x95, 0x10,        //     Report Count (16),
+    0x75, 0x01,        //     Report Size (1),
+    0x05, 0x09,        //     Usage Page (Buttons),
+    0x19, 0x01,        //     Usage Minimum (1),
+    0x29, 0x10,        //     Usage Maximum (16),
+    0x15, 0x00,        //     Logical Minimum (0),
+    0x25, 0x01,        //     Logical Maximum (1),
+    0x81, 0x02,        //     Input (Data, Variable, Absolute),
+
+    0x75, 0x10,        //     Report Size (16),
+    0x95, 0x02,        //     Report Count (2),
+    0x05, 0x01,        //     Usage Page (Generic Desktop),
+    0x09, 0x30,        //     Usage (X),
+    0x09, 0x31,        //     Usage (Y),
+    0x16, 0x01, 0x80,  //     Logical Minimum (-32767),
+    0x26, 0xFF, 0x7F,  //     Logical Maximum (32767),
+    0x81, 0x06,        //     Input (Data, Variable, Relative),
+
+    0x15, 0x81,        //     Logical Minimum (-127)
+    0x25, 0x7F,        //     Logical Maximum (127)
+    0x09, 0x38,        //     Usage (Wheel)
+    0x75, 0x08,        //     Report Size (8)
+    0x95, 0x01,        //     Report Count (1)
+    0x81, 0x06,        //     Input (Data,Var,Rel,No Wrap,Linear,Preferred State,No Null Position)
+    0x05, 0x0C,        //     Usage Page (Consumer)
+    0x0A, 0x38, 0x02,  //     Usage (AC Pan)
+    0x95, 0x01,        //     Report Count (1)
+    0x81, 0x06,        //     Input (Data,Var,Rel,No Wrap,Linear,Preferred State,No Null Position)
+    0xC0,              //   End Collection
+    0xC0,              // End Collection
+];
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", size_bytes = "8")]
+pub struct RelativeMouseReport {
+    #[packed_field(size_bytes = "2")]
+    pub buttons: MouseButtons,
+    #[packed_field]
+    pub x: i16,
+    #[packed_field]
+    pub y: i16,
+    #[packed_field]
+    pub vertical_wheel: i8,
+    #[packed_field]
+    pub horizontal_wheel: i8,
+}
+
+impl RelativeMouseReport {
+    /// Boot protocol report, buttons 1-3 with motion limited to the 8-bit boot range
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn boot_report(&self) -> BootMouseReport {
+        BootMouseReport {
+            buttons: (self.buttons.bits() & 0x07) as u8,
+            x: self.x.clamp(-127, 127) as i8,
+            y: self.y.clamp(-127, 127) as i8,
+        }
+    }
+}
+
 /// Absolute mouse with wheel and eight buttons
 ///
 /// Note - absolute pointer support is relatively uncommon. This has been tested on Windows 11
@@ -173,6 +454,82 @@ pub struct AbsoluteWheelMouseReport {
     pub wheel: i8,
 }
 
+/// Pointer motion waiting to be sent to the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct Motion {
+    pub x: i32,
+    pub y: i32,
+    pub wheel: i32,
+    pub pan: i32,
+}
+
+/// Sums pointer deltas so that none are lost to busy endpoints or report field limits
+///
+/// Motion is taken in chunks limited to the range of the report fields with [`Self::peek`] and
+/// removed with [`Self::consume`] once the report has been sent, the remainder is carried over
+/// to following reports.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct MotionAccumulator {
+    pending: Motion,
+}
+
+impl MotionAccumulator {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pending: Motion {
+                x: 0,
+                y: 0,
+                wheel: 0,
+                pan: 0,
+            },
+        }
+    }
+
+    pub fn add_motion(&mut self, dx: i32, dy: i32, wheel: i32, pan: i32) {
+        self.pending.x = self.pending.x.saturating_add(dx);
+        self.pending.y = self.pending.y.saturating_add(dy);
+        self.pending.wheel = self.pending.wheel.saturating_add(wheel);
+        self.pending.pan = self.pending.pan.saturating_add(pan);
+    }
+
+    /// The next chunk of motion, with X and Y limited to `±pointer_limit` and wheel and pan to
+    /// `±wheel_limit`
+    #[must_use]
+    pub fn peek(&self, pointer_limit: i32, wheel_limit: i32) -> Motion {
+        Motion {
+            x: self.pending.x.clamp(-pointer_limit, pointer_limit),
+            y: self.pending.y.clamp(-pointer_limit, pointer_limit),
+            wheel: self.pending.wheel.clamp(-wheel_limit, wheel_limit),
+            pan: self.pending.pan.clamp(-wheel_limit, wheel_limit),
+        }
+    }
+
+    /// Remove a chunk returned by [`Self::peek`] once it has been sent
+    pub fn consume(&mut self, chunk: Motion) {
+        self.pending.x -= chunk.x;
+        self.pending.y -= chunk.y;
+        self.pending.wheel -= chunk.wheel;
+        self.pending.pan -= chunk.pan;
+    }
+
+    #[must_use]
+    pub fn pending(&self) -> Motion {
+        self.pending
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending == Motion::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending = Motion::default();
+    }
+}
+
 pub struct BootMouse<'a, B: UsbBus> {
     interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
 }
@@ -238,8 +595,16 @@ impl<'a, B: UsbBus> DeviceClass<'a> for BootMouse<'a, B> {
     }
 }
 
+/// Interface implementing a wheel mouse
+///
+/// Reports can be written directly with [`Self::write_report`], or motion added with
+/// [`Self::add_motion`] and sent in chunks from [`UsbHidClass::tick()`], which must then be
+/// called every 1ms.
 pub struct WheelMouse<'a, B: UsbBus> {
     interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    motion: MotionAccumulator,
+    buttons: u8,
+    buttons_changed: bool,
 }
 
 impl<B: UsbBus> WheelMouse<'_, B> {
@@ -253,7 +618,19 @@ impl<B: UsbBus> WheelMouse<'_, B> {
             .map(|_| ())
             .map_err(UsbHidError::from)
     }
+
+    /// Add motion to be sent from [`UsbHidClass::tick()`]
+    pub fn add_motion(&mut self, dx: i32, dy: i32, wheel: i32, pan: i32) {
+        self.motion.add_motion(dx, dy, wheel, pan);
+    }
+
+    /// Set the buttons sent from [`UsbHidClass::tick()`]
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.buttons_changed |= self.buttons != buttons;
+        self.buttons = buttons;
+    }
 }
+
 pub struct WheelMouseConfig<'a> {
     interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
 }
@@ -286,6 +663,9 @@ impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for WheelMouseConfig<'a> {
     fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
         WheelMouse {
             interface: self.interface.allocate(usb_alloc),
+            motion: MotionAccumulator::new(),
+            buttons: 0,
+            buttons_changed: false,
         }
     }
 }
@@ -297,11 +677,365 @@ impl<'a, B: UsbBus> DeviceClass<'a> for WheelMouse<'a, B> {
         &mut self.interface
     }
 
-    fn reset(&mut self) {}
+    fn reset(&mut self) {
+        self.motion.clear();
+        self.buttons_changed = false;
+    }
 
     fn tick(&mut self) -> Result<(), UsbHidError> {
+        if self.motion.is_empty() && !self.buttons_changed {
+            return Ok(());
+        }
+
+        let limit = i32::from(i8::MAX);
+        let chunk = self.motion.peek(limit, limit);
+        #[allow(clippy::cast_possible_truncation)]
+        let report = WheelMouseReport {
+            buttons: self.buttons,
+            x: chunk.x as i8,
+            y: chunk.y as i8,
+            vertical_wheel: chunk.wheel as i8,
+            horizontal_wheel: chunk.pan as i8,
+        };
+
+        match self.write_report(&report) {
+            Ok(()) => {
+                self.motion.consume(chunk);
+                self.buttons_changed = false;
+                Ok(())
+            }
+            //retry on the next tick
+            Err(UsbHidError::WouldBlock) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Resolution multiplier state and low-resolution remainder of a wheel
+#[derive(Clone, Copy, Debug, Default)]
+struct WheelScaler {
+    hi_res: bool,
+    /// Movement not yet sent while the multiplier is disabled, in hi-res units
+    remainder: i16,
+}
+
+impl WheelScaler {
+    /// Returns the value to report and the remainder to carry
+    fn scale(self, value: i16) -> (i16, i16) {
+        let total = i32::from(value) + i32::from(self.remainder);
+        let multiplier = i32::from(RESOLUTION_MULTIPLIER);
+        #[allow(clippy::cast_possible_truncation)]
+        if self.hi_res {
+            (total.clamp(-0x7FFF, 0x7FFF) as i16, 0)
+        } else {
+            ((total / multiplier) as i16, (total % multiplier) as i16)
+        }
+    }
+}
+
+/// Interface implementing a mouse with high-resolution wheel and pan
+///
+/// Wheel movement is always supplied in [`RESOLUTION_MULTIPLIER`] units per detent. Until the
+/// host enables the resolution multiplier, movement is sent as whole detents and the remainder
+/// carried over to the next report.
+///
+/// Motion added with [`Self::add_motion`] is sent in chunks from [`UsbHidClass::tick()`], which
+/// must then be called every 1ms.
+pub struct HiResWheelMouse<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    vertical: WheelScaler,
+    horizontal: WheelScaler,
+    feature_report: [u8; 1],
+    motion: MotionAccumulator,
+    buttons: u8,
+    buttons_changed: bool,
+}
+
+impl<B: UsbBus> HiResWheelMouse<'_, B> {
+    pub fn write_report(&mut self, report: &HiResWheelMouseReport) -> Result<(), UsbHidError> {
+        let (vertical_wheel, vertical_remainder) = self.vertical.scale(report.vertical_wheel);
+        let (horizontal_wheel, horizontal_remainder) =
+            self.horizontal.scale(report.horizontal_wheel);
+
+        let data = HiResWheelMouseReport {
+            vertical_wheel,
+            horizontal_wheel,
+            ..*report
+        }
+        .pack()
+        .map_err(|_| {
+            error!("Error packing HiResWheelMouseReport");
+            UsbHidError::SerializationError
+        })?;
+        self.interface
+            .write_report(&data)
+            .map_err(UsbHidError::from)?;
+
+        self.vertical.remainder = vertical_remainder;
+        self.horizontal.remainder = horizontal_remainder;
         Ok(())
     }
+
+    /// Add motion to be sent from [`UsbHidClass::tick()`], wheel and pan in
+    /// [`RESOLUTION_MULTIPLIER`] units per detent
+    pub fn add_motion(&mut self, dx: i32, dy: i32, wheel: i32, pan: i32) {
+        self.motion.add_motion(dx, dy, wheel, pan);
+    }
+
+    /// Set the buttons sent from [`UsbHidClass::tick()`]
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.buttons_changed |= self.buttons != buttons;
+        self.buttons = buttons;
+    }
+
+    /// True if the host has enabled the vertical wheel resolution multiplier
+    #[must_use]
+    pub fn vertical_hi_res(&self) -> bool {
+        self.vertical.hi_res
+    }
+
+    /// True if the host has enabled the horizontal wheel resolution multiplier
+    #[must_use]
+    pub fn horizontal_hi_res(&self) -> bool {
+        self.horizontal.hi_res
+    }
+}
+
+pub struct HiResWheelMouseConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+}
+
+impl<'a> HiResWheelMouseConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
+        Self { interface }
+    }
+}
+
+impl Default for HiResWheelMouseConfig<'_> {
+    fn default() -> Self {
+        HiResWheelMouseConfig::new(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                HI_RES_WHEEL_MOUSE_REPORT_DESCRIPTOR
+            ))
+            .boot_device(InterfaceProtocol::Mouse)
+            .description("Hi-Res Wheel Mouse")
+            .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for HiResWheelMouseConfig<'a> {
+    type Allocated = HiResWheelMouse<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        HiResWheelMouse {
+            interface: self.interface.allocate(usb_alloc),
+            vertical: WheelScaler::default(),
+            horizontal: WheelScaler::default(),
+            feature_report: [0],
+            motion: MotionAccumulator::new(),
+            buttons: 0,
+            buttons_changed: false,
+        }
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for HiResWheelMouse<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        // the host sets the multiplier again after enumeration
+        self.vertical = WheelScaler::default();
+        self.horizontal = WheelScaler::default();
+        self.motion.clear();
+        self.buttons_changed = false;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        if self.motion.is_empty() && !self.buttons_changed {
+            return Ok(());
+        }
+
+        let chunk = self
+            .motion
+            .peek(i32::from(i8::MAX), i32::from(RESOLUTION_MULTIPLIER));
+        #[allow(clippy::cast_possible_truncation)]
+        let report = HiResWheelMouseReport {
+            buttons: self.buttons,
+            x: chunk.x as i8,
+            y: chunk.y as i8,
+            vertical_wheel: chunk.wheel as i16,
+            horizontal_wheel: chunk.pan as i16,
+        };
+
+        match self.write_report(&report) {
+            Ok(()) => {
+                self.motion.consume(chunk);
+                self.buttons_changed = false;
+                Ok(())
+            }
+            //retry on the next tick
+            Err(UsbHidError::WouldBlock) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        if report_id != 0 {
+            return None;
+        }
+        self.feature_report =
+            [u8::from(self.vertical.hi_res) | u8::from(self.horizontal.hi_res) << 2];
+        Some(FeatureReport::DynamicReport(&self.feature_report))
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        let [multipliers] = data else {
+            return Err(UsbError::ParseError);
+        };
+        if report_id != 0 {
+            return Err(UsbError::Unsupported);
+        }
+        self.vertical.hi_res = multipliers & 0x03 != 0;
+        self.horizontal.hi_res = multipliers & 0x0C != 0;
+        Ok(())
+    }
+}
+
+/// Interface implementing a 16-bit relative mouse
+///
+/// While the host has selected [`HidProtocol::Boot`] boot mouse reports are sent, as expected
+/// by BIOS and other reduced functionality hosts.
+///
+/// Motion added with [`Self::add_motion`] is sent in chunks from [`UsbHidClass::tick()`], which
+/// must then be called every 1ms.
+pub struct RelativeMouse<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    motion: MotionAccumulator,
+    buttons: MouseButtons,
+    buttons_changed: bool,
+}
+
+impl<B: UsbBus> RelativeMouse<'_, B> {
+    pub fn write_report(&mut self, report: &RelativeMouseReport) -> Result<(), UsbHidError> {
+        let result = if self.interface.protocol() == HidProtocol::Boot {
+            let data = report.boot_report().pack().map_err(|_| {
+                error!("Error packing BootMouseReport");
+                UsbHidError::SerializationError
+            })?;
+            self.interface.write_report(&data)
+        } else {
+            let data = report.pack().map_err(|_| {
+                error!("Error packing RelativeMouseReport");
+                UsbHidError::SerializationError
+            })?;
+            self.interface.write_report(&data)
+        };
+        result.map(|_| ()).map_err(UsbHidError::from)
+    }
+
+    /// Add motion to be sent from [`UsbHidClass::tick()`]
+    pub fn add_motion(&mut self, dx: i32, dy: i32, wheel: i32, pan: i32) {
+        self.motion.add_motion(dx, dy, wheel, pan);
+    }
+
+    /// Set the buttons sent from [`UsbHidClass::tick()`]
+    pub fn set_buttons(&mut self, buttons: MouseButtons) {
+        self.buttons_changed |= self.buttons != buttons;
+        self.buttons = buttons;
+    }
+}
+
+pub struct RelativeMouseConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+}
+
+impl<'a> RelativeMouseConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
+        Self { interface }
+    }
+}
+
+impl Default for RelativeMouseConfig<'_> {
+    fn default() -> Self {
+        RelativeMouseConfig::new(
+            unwrap!(
+                unwrap!(InterfaceBuilder::new(RELATIVE_MOUSE_REPORT_DESCRIPTOR))
+                    .boot_device(InterfaceProtocol::Mouse)
+                    .description("Mouse")
+                    .in_endpoint(1.millis())
+            )
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for RelativeMouseConfig<'a> {
+    type Allocated = RelativeMouse<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        RelativeMouse {
+            interface: self.interface.allocate(usb_alloc),
+            motion: MotionAccumulator::new(),
+            buttons: MouseButtons::empty(),
+            buttons_changed: false,
+        }
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for RelativeMouse<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.motion.clear();
+        self.buttons_changed = false;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        if self.motion.is_empty() && !self.buttons_changed {
+            return Ok(());
+        }
+
+        // limit to the boot range while the host uses the boot protocol
+        let pointer_limit = if self.interface.protocol() == HidProtocol::Boot {
+            i32::from(i8::MAX)
+        } else {
+            i32::from(i16::MAX)
+        };
+        let chunk = self.motion.peek(pointer_limit, i32::from(i8::MAX));
+        #[allow(clippy::cast_possible_truncation)]
+        let report = RelativeMouseReport {
+            buttons: self.buttons,
+            x: chunk.x as i16,
+            y: chunk.y as i16,
+            vertical_wheel: chunk.wheel as i8,
+            horizontal_wheel: chunk.pan as i8,
+        };
+
+        match self.write_report(&report) {
+            Ok(()) => {
+                self.motion.consume(chunk);
+                self.buttons_changed = false;
+                Ok(())
+            }
+            //retry on the next tick
+            Err(UsbHidError::WouldBlock) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 pub struct AbsoluteWheelMouse<'a, B: UsbBus> {
@@ -369,3 +1103,207 @@ impl<'a, B: UsbBus> DeviceClass<'a> for AbsoluteWheelMouse<'a, B> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::descriptor::{HidProtocol, HidRequest};
+    use crate::test_util::{test_usb, test_usb_device, UsbRequest};
+    use crate::usb_class::UsbHidClassBuilder;
+    use usb_device::control::{Recipient, RequestType};
+    use usb_device::UsbDirection;
+
+    #[test]
+    fn wheel_scaler_carries_low_resolution_remainder() {
+        let scaler = WheelScaler::default();
+        assert_eq!(scaler.scale(100), (0, 100));
+        assert_eq!(scaler.scale(-250), (-2, -10));
+
+        let scaler = WheelScaler {
+            hi_res: false,
+            remainder: 100,
+        };
+        assert_eq!(scaler.scale(40), (1, 20));
+
+        let scaler = WheelScaler {
+            hi_res: true,
+            remainder: 100,
+        };
+        assert_eq!(scaler.scale(40), (140, 0));
+        assert_eq!(scaler.scale(i16::MAX), (i16::MAX, 0));
+    }
+
+    #[test]
+    fn relative_mouse_report_packs_buttons_and_motion() {
+        let report = RelativeMouseReport {
+            buttons: [MouseButton::Left, MouseButton::Button16]
+                .into_iter()
+                .collect(),
+            x: 1000,
+            y: -200,
+            vertical_wheel: -1,
+            horizontal_wheel: 2,
+        };
+        assert_eq!(
+            report.pack().unwrap(),
+            [0x01, 0x80, 0xE8, 0x03, 0x38, 0xFF, 0xFF, 0x02]
+        );
+        assert_eq!(
+            report.boot_report(),
+            BootMouseReport {
+                buttons: 0x01,
+                x: 127,
+                y: -127
+            }
+        );
+    }
+
+    #[test]
+    fn mouse_buttons_set() {
+        let mut buttons = MouseButtons::from(MouseButton::Middle);
+        buttons.set(MouseButton::Forward, true);
+        assert_eq!(buttons.bits(), 0x14);
+        assert!(buttons.contains(MouseButton::Forward));
+
+        buttons.remove(MouseButton::Middle);
+        assert!(!buttons.contains(MouseButton::Middle));
+        assert_eq!(buttons, MouseButtons::from_bits(0x10));
+    }
+
+    #[test]
+    fn motion_accumulator_carries_remainder() {
+        let mut motion = MotionAccumulator::new();
+        motion.add_motion(300, -5, -2, 0);
+        motion.add_motion(10, -5, 0, 1);
+
+        let chunk = motion.peek(127, 1);
+        assert_eq!(
+            chunk,
+            Motion {
+                x: 127,
+                y: -10,
+                wheel: -1,
+                pan: 1
+            }
+        );
+        motion.consume(chunk);
+        assert_eq!(
+            motion.pending(),
+            Motion {
+                x: 183,
+                y: 0,
+                wheel: -1,
+                pan: 0
+            }
+        );
+
+        motion.consume(motion.peek(127, 1));
+        motion.consume(motion.peek(127, 1));
+        assert!(motion.is_empty());
+    }
+
+    #[test]
+    fn hi_res_wheel_scaled_until_multiplier_set() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(HiResWheelMouseConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        let report = HiResWheelMouseReport {
+            vertical_wheel: 80,
+            horizontal_wheel: -40,
+            ..Default::default()
+        };
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), [0, 0, 0, 0, 0, 0, 0]);
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), [0, 0, 0, 0x01, 0, 0, 0]);
+
+        // Enable the vertical wheel multiplier
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0x01]);
+        assert!(hid.device().vertical_hi_res());
+        assert!(!hid.device().horizontal_hi_res());
+
+        // Vertical remainder is flushed, horizontal carries on in whole detents
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), [0, 0, 0, 0x78, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn relative_mouse_sends_boot_report_in_boot_protocol() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(RelativeMouseConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        let report = RelativeMouseReport {
+            buttons: MouseButton::Right.into(),
+            x: 300,
+            y: -5,
+            ..Default::default()
+        };
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(
+            manager.host_read_in(),
+            [0x02, 0x00, 0x2C, 0x01, 0xFB, 0xFF, 0x00, 0x00]
+        );
+
+        // Set protocol to boot
+        manager
+            .host_write_setup(
+                &UsbRequest {
+                    direction: UsbDirection::In != UsbDirection::In,
+                    request_type: RequestType::Class as u8,
+                    recipient: Recipient::Interface as u8,
+                    request: HidRequest::SetProtocol.into(),
+                    value: HidProtocol::Boot as u16,
+                    index: 0x0,
+                    length: 0x0,
+                }
+                .pack()
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(usb_dev.poll(&mut [&mut hid]));
+
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), [0x02, 0x7F, 0xFB]);
+    }
+
+    #[test]
+    fn wheel_mouse_motion_sent_in_chunks() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(WheelMouseConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device().add_motion(300, -2, 1, 0);
+        hid.tick().unwrap();
+
+        // endpoint busy, the motion is kept for the next tick
+        hid.device().add_motion(0, -3, 0, 0);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0, 0x7F, 0xFE, 0x01, 0]);
+
+        hid.device().set_buttons(0x01);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x01, 0x7F, 0xFD, 0, 0]);
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x01, 0x2E, 0, 0, 0]);
+
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty());
+    }
+}
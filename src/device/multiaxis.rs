@@ -5,6 +5,7 @@ use fugit::ExtU32;
 use packed_struct::prelude::*;
 use usb_device::bus::UsbBus;
 use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
 
 #[rustfmt::skip]
 pub const MULTIAXIS_DESCRIPTOR: &[u8] = &[
@@ -120,3 +121,371 @@ impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for MultiaxisConfig<'a> {
         }
     }
 }
+
+/// Six-axis controller with 16-bit axes and 32 buttons in a single report, e.g. a 3D mouse
+///
+/// * Bytes 0-5 - X, Y and Z translation, -32767 to 32767 micrometers
+/// * Bytes 6-11 - Rx, Ry and Rz rotation, -32767 to 32767 hundredths of a degree
+/// * Bytes 12-15 - Buttons 1-32
+#[rustfmt::skip]
+pub const SIX_AXIS_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop)
+    0x09, 0x08,        // Usage (Multi-axis Controller)
+    0xA1, 0x01,        // Collection (Application)
+    0x09, 0x01,        //   Usage (Pointer)
+    0xA1, 0x00,        //   Collection (Physical)
+    0x16, 0x01, 0x80,  //     Logical Minimum (-32767)
+    0x26, 0xFF, 0x7F,  //     Logical Maximum (32767)
+    0x36, 0x01, 0x80,  //     Physical Minimum (-32767)
+    0x46, 0xFF, 0x7F,  //     Physical Maximum (32767)
+    0x55, 0x0C,        //     Unit Exponent (-4)
+    0x65, 0x11,        //     Unit (Centimeter, SI Linear)
+    0x09, 0x30,        //     Usage (X)
+    0x09, 0x31,        //     Usage (Y)
+    0x09, 0x32,        //     Usage (Z)
+    0x75, 0x10,        //     Report Size (16)
+    0x95, 0x03,        //     Report Count (3)
+    0x81, 0x02,        //     Input (Data, Variable, Absolute)
+    0x55, 0x0E,        //     Unit Exponent (-2)
+    0x65, 0x14,        //     Unit (Degrees, English Rotation)
+    0x09, 0x33,        //     Usage (Rx)
+    0x09, 0x34,        //     Usage (Ry)
+    0x09, 0x35,        //     Usage (Rz)
+    0x81, 0x02,        //     Input (Data, Variable, Absolute)
+    0x55, 0x00,        //     Unit Exponent (0)
+    0x65, 0x00,        //     Unit (None)
+    0xC0,              //   End Collection
+    0x05, 0x09,        //   Usage Page (Button)
+    0x19, 0x01,        //   Usage Minimum (1)
+    0x29, 0x20,        //   Usage Maximum (32)
+    0x15, 0x00,        //   Logical Minimum (0)
+    0x25, 0x01,        //   Logical Maximum (1)
+    0x35, 0x00,        //   Physical Minimum (0)
+    0x45, 0x00,        //   Physical Maximum (0)
+    0x75, 0x01,        //   Report Size (1)
+    0x95, 0x20,        //   Report Count (32)
+    0x81, 0x02,        //   Input (Data, Variable, Absolute)
+    0xC0,              // End Collection
+];
+
+/// Report ID of the translation report of [`SIX_AXIS_SPLIT_REPORT_DESCRIPTOR`]
+pub const TRANSLATION_REPORT_ID: u8 = 0x01;
+/// Report ID of the rotation report of [`SIX_AXIS_SPLIT_REPORT_DESCRIPTOR`]
+pub const ROTATION_REPORT_ID: u8 = 0x02;
+/// Report ID of the buttons report of [`SIX_AXIS_SPLIT_REPORT_DESCRIPTOR`]
+pub const BUTTONS_REPORT_ID: u8 = 0x03;
+
+/// Six-axis controller with separate translation, rotation and button reports, the layout used
+/// by 3Dconnexion devices and expected by 3D modelling software
+///
+/// * Report ID 1 - X, Y and Z translation, -32767 to 32767 micrometers
+/// * Report ID 2 - Rx, Ry and Rz rotation, -32767 to 32767 hundredths of a degree
+/// * Report ID 3 - Buttons 1-32
+#[rustfmt::skip]
+pub const SIX_AXIS_SPLIT_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,        // Usage Page (Generic Desktop)
+    0x09, 0x08,        // Usage (Multi-axis Controller)
+    0xA1, 0x01,        // Collection (Application)
+    0x16, 0x01, 0x80,  //   Logical Minimum (-32767)
+    0x26, 0xFF, 0x7F,  //   Logical Maximum (32767)
+    0x36, 0x01, 0x80,  //   Physical Minimum (-32767)
+    0x46, 0xFF, 0x7F,  //   Physical Maximum (32767)
+    0x75, 0x10,        //   Report Size (16)
+    0x95, 0x03,        //   Report Count (3)
+    0xA1, 0x00,        //   Collection (Physical)
+    0x85, 0x01,        //     Report ID (1)
+    0x55, 0x0C,        //     Unit Exponent (-4)
+    0x65, 0x11,        //     Unit (Centimeter, SI Linear)
+    0x09, 0x30,        //     Usage (X)
+    0x09, 0x31,        //     Usage (Y)
+    0x09, 0x32,        //     Usage (Z)
+    0x81, 0x02,        //     Input (Data, Variable, Absolute)
+    0xC0,              //   End Collection
+    0xA1, 0x00,        //   Collection (Physical)
+    0x85, 0x02,        //     Report ID (2)
+    0x55, 0x0E,        //     Unit Exponent (-2)
+    0x65, 0x14,        //     Unit (Degrees, English Rotation)
+    0x09, 0x33,        //     Usage (Rx)
+    0x09, 0x34,        //     Usage (Ry)
+    0x09, 0x35,        //     Usage (Rz)
+    0x81, 0x02,        //     Input (Data, Variable, Absolute)
+    0xC0,              //   End Collection
+    0xA1, 0x02,        //   Collection (Logical)
+    0x85, 0x03,        //     Report ID (3)
+    0x55, 0x00,        //     Unit Exponent (0)
+    0x65, 0x00,        //     Unit (None)
+    0x05, 0x09,        //     Usage Page (Button)
+    0x19, 0x01,        //     Usage Minimum (1)
+    0x29, 0x20,        //     Usage Maximum (32)
+    0x15, 0x00,        //     Logical Minimum (0)
+    0x25, 0x01,        //     Logical Maximum (1)
+    0x35, 0x00,        //     Physical Minimum (0)
+    0x45, 0x00,        //     Physical Maximum (0)
+    0x75, 0x01,        //     Report Size (1)
+    0x95, 0x20,        //     Report Count (32)
+    0x81, 0x02,        //     Input (Data, Variable, Absolute)
+    0xC0,              //   End Collection
+    0xC0,              // End Collection
+];
+
+/// Report layout of a [`SixAxis`] device
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum SixAxisLayout {
+    /// A single report, [`SIX_AXIS_REPORT_DESCRIPTOR`]
+    #[default]
+    Combined,
+    /// Separate translation, rotation and button reports, [`SIX_AXIS_SPLIT_REPORT_DESCRIPTOR`]
+    Split,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", size_bytes = "16")]
+pub struct SixAxisReport {
+    #[packed_field]
+    pub x: i16,
+    #[packed_field]
+    pub y: i16,
+    #[packed_field]
+    pub z: i16,
+    #[packed_field]
+    pub rx: i16,
+    #[packed_field]
+    pub ry: i16,
+    #[packed_field]
+    pub rz: i16,
+    #[packed_field]
+    pub buttons: u32,
+}
+
+impl SixAxisReport {
+    fn translation(&self) -> [i16; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn rotation(&self) -> [i16; 3] {
+        [self.rx, self.ry, self.rz]
+    }
+}
+
+const TRANSLATION_PENDING: u8 = 0x01;
+const ROTATION_PENDING: u8 = 0x02;
+const BUTTONS_PENDING: u8 = 0x04;
+
+/// Interface implementing a six-axis controller
+///
+/// **Note:** With [`SixAxisLayout::Split`] only the reports that changed are sent, one per
+/// transfer. Reports that do not fit in the current transfer are sent from
+/// [`UsbHidClass::tick()`], which must then be called every 1ms.
+pub struct SixAxis<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes16, OutNone, ReportSingle>,
+    layout: SixAxisLayout,
+    report: SixAxisReport,
+    pending: u8,
+}
+
+impl<B: UsbBus> SixAxis<'_, B> {
+    pub fn write_report(&mut self, report: &SixAxisReport) -> Result<(), UsbHidError> {
+        match self.layout {
+            SixAxisLayout::Combined => {
+                let data = report.pack().map_err(|_| {
+                    error!("Error packing SixAxisReport");
+                    UsbHidError::SerializationError
+                })?;
+                self.interface
+                    .write_report(&data)
+                    .map(|_| ())
+                    .map_err(UsbHidError::from)
+            }
+            SixAxisLayout::Split => {
+                if report.translation() != self.report.translation() {
+                    self.pending |= TRANSLATION_PENDING;
+                }
+                if report.rotation() != self.report.rotation() {
+                    self.pending |= ROTATION_PENDING;
+                }
+                if report.buttons != self.report.buttons {
+                    self.pending |= BUTTONS_PENDING;
+                }
+                self.report = *report;
+                self.send_pending()
+            }
+        }
+    }
+
+    /// Send pending split reports until the endpoint is busy
+    fn send_pending(&mut self) -> Result<(), UsbHidError> {
+        while self.pending != 0 {
+            let (flag, data, len) = if self.pending & TRANSLATION_PENDING != 0 {
+                let (data, len) = axes_report(TRANSLATION_REPORT_ID, self.report.translation());
+                (TRANSLATION_PENDING, data, len)
+            } else if self.pending & ROTATION_PENDING != 0 {
+                let (data, len) = axes_report(ROTATION_REPORT_ID, self.report.rotation());
+                (ROTATION_PENDING, data, len)
+            } else {
+                let [b0, b1, b2, b3] = self.report.buttons.to_le_bytes();
+                (
+                    BUTTONS_PENDING,
+                    [BUTTONS_REPORT_ID, b0, b1, b2, b3, 0, 0],
+                    5,
+                )
+            };
+
+            match self.interface.write_report(&data[..len]) {
+                Ok(_) => self.pending &= !flag,
+                //retry on the next tick
+                Err(UsbError::WouldBlock) => return Ok(()),
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn axes_report(report_id: u8, axes: [i16; 3]) -> ([u8; 7], usize) {
+    let [a0, a1] = axes[0].to_le_bytes();
+    let [b0, b1] = axes[1].to_le_bytes();
+    let [c0, c1] = axes[2].to_le_bytes();
+    ([report_id, a0, a1, b0, b1, c0, c1], 7)
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for SixAxis<'a, B> {
+    type I = Interface<'a, B, InBytes16, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.report = SixAxisReport::default();
+        self.pending = 0;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.send_pending()
+    }
+}
+
+pub struct SixAxisConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>,
+    layout: SixAxisLayout,
+}
+
+impl Default for SixAxisConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(InterfaceBuilder::new(SIX_AXIS_REPORT_DESCRIPTOR))
+                .description("Six-axis Controller")
+                .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+            SixAxisLayout::Combined,
+        )
+    }
+}
+
+impl<'a> SixAxisConfig<'a> {
+    /// Create a configuration from an interface using [`SIX_AXIS_REPORT_DESCRIPTOR`] for
+    /// [`SixAxisLayout::Combined`] or [`SIX_AXIS_SPLIT_REPORT_DESCRIPTOR`] for
+    /// [`SixAxisLayout::Split`]
+    #[must_use]
+    pub fn new(
+        interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>,
+        layout: SixAxisLayout,
+    ) -> Self {
+        Self { interface, layout }
+    }
+
+    /// Configuration with separate translation, rotation and button reports
+    #[must_use]
+    pub fn split() -> Self {
+        Self::new(
+            unwrap!(
+                unwrap!(InterfaceBuilder::new(SIX_AXIS_SPLIT_REPORT_DESCRIPTOR))
+                    .description("Six-axis Controller")
+                    .in_endpoint(10.millis())
+            )
+            .without_out_endpoint()
+            .build(),
+            SixAxisLayout::Split,
+        )
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for SixAxisConfig<'a> {
+    type Allocated = SixAxis<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            layout: self.layout,
+            report: SixAxisReport::default(),
+            pending: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn six_axis_report_packs_16_bit_axes() {
+        let report = SixAxisReport {
+            x: 1,
+            y: -1,
+            z: 0x1234,
+            rx: -32767,
+            ry: 0,
+            rz: 2,
+            buttons: 0x8000_0001,
+        };
+        assert_eq!(
+            report.pack().unwrap(),
+            [
+                0x01, 0x00, 0xFF, 0xFF, 0x34, 0x12, 0x01, 0x80, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00,
+                0x00, 0x80
+            ]
+        );
+        assert_eq!(
+            axes_report(ROTATION_REPORT_ID, report.rotation()),
+            ([0x02, 0x01, 0x80, 0x00, 0x00, 0x02, 0x00], 7)
+        );
+    }
+
+    #[test]
+    fn six_axis_split_sends_changed_reports() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(SixAxisConfig::split())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        let report = SixAxisReport {
+            z: -300,
+            buttons: 0x0001_0000,
+            ..Default::default()
+        };
+        hid.device().write_report(&report).unwrap();
+        assert_eq!(manager.host_read_in(), [0x01, 0, 0, 0, 0, 0xD4, 0xFE]);
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x03, 0, 0, 0x01, 0]);
+
+        hid.device().write_report(&report).unwrap();
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty());
+
+        hid.device()
+            .write_report(&SixAxisReport { rx: 1, ..report })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x02, 0x01, 0, 0, 0, 0, 0]);
+    }
+}
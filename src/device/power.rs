@@ -0,0 +1,414 @@
+//!HID power devices (uninterruptible power supplies)
+use crate::usb_class::prelude::*;
+use crate::usb_class::{BuilderResult, UsbHidBuilderError};
+use fugit::ExtU32;
+use heapless::Deque;
+use packed_struct::prelude::*;
+use usb_device::bus::{StringIndex, UsbBus};
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::descriptor::lang_id::LangID;
+use usb_device::UsbError;
+
+const REMAINING_CAPACITY_REPORT_ID: u8 = 0x01;
+const RUN_TIME_TO_EMPTY_REPORT_ID: u8 = 0x02;
+const PRESENT_STATUS_REPORT_ID: u8 = 0x03;
+const CAPACITY_REPORT_ID: u8 = 0x04;
+const MANUFACTURER_DATE_REPORT_ID: u8 = 0x05;
+const DEVICE_CHEMISTRY_REPORT_ID: u8 = 0x06;
+
+/// `CapacityMode` value indicating capacities are reported as a percentage
+const CAPACITY_MODE_PERCENT: u8 = 2;
+const FULL_CAPACITY: u8 = 100;
+
+#[allow(clippy::doc_markdown)]
+/// UPS report descriptor, implementing the HID Power Device class
+///
+/// All values are within a `PowerSummary` collection, as expected by NUT and Windows. Capacities
+/// are reported as a percentage.
+/// * Report 1 - Remaining Capacity, input and feature
+/// * Report 2 - Run Time To Empty in seconds, input and feature
+/// * Report 3 - Present Status flags, input and feature
+/// * Report 4 - Capacity Mode, Design Capacity, Full Charge Capacity, Warning Capacity Limit and
+///   Remaining Capacity Limit, feature
+/// * Report 5 - Manufacturer Date, feature
+/// * Report 6 - iDeviceChemistry string index, feature
+///
+/// Reference: <https://www.usb.org/sites/default/files/pdcv11.pdf>
+#[rustfmt::skip]
+pub const UPS_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x84,                   // Usage Page (Power Device)
+    0x09, 0x04,                   // Usage (UPS)
+    0xA1, 0x01,                   // Collection (Application)
+    0x09, 0x24,                   //   Usage (Power Summary)
+    0xA1, 0x00,                   //   Collection (Physical)
+    0x05, 0x85,                   //     Usage Page (Battery System)
+    0x15, 0x00,                   //     Logical Minimum (0)
+    0x95, 0x01,                   //     Report Count (1)
+    0x85, 0x01,                   //     Report ID (1)
+    0x09, 0x66,                   //     Usage (Remaining Capacity)
+    0x25, 0x64,                   //     Logical Maximum (100)
+    0x75, 0x08,                   //     Report Size (8)
+    0x81, 0xA2,                   //     Input (Data, Variable, Absolute, No Preferred State, Volatile)
+    0x09, 0x66,                   //     Usage (Remaining Capacity)
+    0xB1, 0xA2,                   //     Feature (Data, Variable, Absolute, No Preferred State, Volatile)
+    0x85, 0x02,                   //     Report ID (2)
+    0x09, 0x68,                   //     Usage (Run Time To Empty)
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //     Logical Maximum (65535)
+    0x75, 0x10,                   //     Report Size (16)
+    0x66, 0x01, 0x10,             //     Unit (Seconds)
+    0x55, 0x00,                   //     Unit Exponent (0)
+    0x81, 0xA2,                   //     Input (Data, Variable, Absolute, No Preferred State, Volatile)
+    0x09, 0x68,                   //     Usage (Run Time To Empty)
+    0xB1, 0xA2,                   //     Feature (Data, Variable, Absolute, No Preferred State, Volatile)
+    0x65, 0x00,                   //     Unit (None)
+    0x85, 0x03,                   //     Report ID (3)
+    0x0B, 0x02, 0x00, 0x84, 0x00, //     Usage (Power Device: Present Status)
+    0xA1, 0x02,                   //     Collection (Logical)
+    0x25, 0x01,                   //       Logical Maximum (1)
+    0x75, 0x01,                   //       Report Size (1)
+    0x95, 0x08,                   //       Report Count (8)
+    0x09, 0x44,                   //       Usage (Charging)
+    0x09, 0x45,                   //       Usage (Discharging)
+    0x09, 0xD0,                   //       Usage (AC Present)
+    0x09, 0xD1,                   //       Usage (Battery Present)
+    0x09, 0x42,                   //       Usage (Below Remaining Capacity Limit)
+    0x09, 0x46,                   //       Usage (Fully Charged)
+    0x09, 0x4B,                   //       Usage (Need Replacement)
+    0x0B, 0x69, 0x00, 0x84, 0x00, //       Usage (Power Device: Shutdown Imminent)
+    0x81, 0xA2,                   //       Input (Data, Variable, Absolute, No Preferred State, Volatile)
+    0x09, 0x44,                   //       Usage (Charging)
+    0x09, 0x45,                   //       Usage (Discharging)
+    0x09, 0xD0,                   //       Usage (AC Present)
+    0x09, 0xD1,                   //       Usage (Battery Present)
+    0x09, 0x42,                   //       Usage (Below Remaining Capacity Limit)
+    0x09, 0x46,                   //       Usage (Fully Charged)
+    0x09, 0x4B,                   //       Usage (Need Replacement)
+    0x0B, 0x69, 0x00, 0x84, 0x00, //       Usage (Power Device: Shutdown Imminent)
+    0xB1, 0xA2,                   //       Feature (Data, Variable, Absolute, No Preferred State, Volatile)
+    0xC0,                         //     End Collection
+    0x85, 0x04,                   //     Report ID (4)
+    0x09, 0x2C,                   //     Usage (Capacity Mode)
+    0x09, 0x83,                   //     Usage (Design Capacity)
+    0x09, 0x67,                   //     Usage (Full Charge Capacity)
+    0x09, 0x8C,                   //     Usage (Warning Capacity Limit)
+    0x09, 0x29,                   //     Usage (Remaining Capacity Limit)
+    0x26, 0xFF, 0x00,             //     Logical Maximum (255)
+    0x75, 0x08,                   //     Report Size (8)
+    0x95, 0x05,                   //     Report Count (5)
+    0xB1, 0x02,                   //     Feature (Data, Variable, Absolute)
+    0x85, 0x05,                   //     Report ID (5)
+    0x09, 0x85,                   //     Usage (Manufacturer Date)
+    0x27, 0xFF, 0xFF, 0x00, 0x00, //     Logical Maximum (65535)
+    0x75, 0x10,                   //     Report Size (16)
+    0x95, 0x01,                   //     Report Count (1)
+    0xB1, 0x02,                   //     Feature (Data, Variable, Absolute)
+    0x85, 0x06,                   //     Report ID (6)
+    0x09, 0x89,                   //     Usage (iDeviceChemistry)
+    0x26, 0xFF, 0x00,             //     Logical Maximum (255)
+    0x75, 0x08,                   //     Report Size (8)
+    0xB1, 0x02,                   //     Feature (Data, Variable, Absolute)
+    0xC0,                         //   End Collection
+    0xC0,                         // End Collection
+];
+
+/// UPS Present Status flags
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct PresentStatus {
+    #[packed_field(bits = "0")]
+    pub charging: bool,
+    #[packed_field(bits = "1")]
+    pub discharging: bool,
+    #[packed_field(bits = "2")]
+    pub ac_present: bool,
+    #[packed_field(bits = "3")]
+    pub battery_present: bool,
+    #[packed_field(bits = "4")]
+    pub below_remaining_capacity_limit: bool,
+    #[packed_field(bits = "5")]
+    pub fully_charged: bool,
+    #[packed_field(bits = "6")]
+    pub need_replacement: bool,
+    #[packed_field(bits = "7")]
+    pub shutdown_imminent: bool,
+}
+
+/// Current state of the UPS
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct UpsStatus {
+    /// Remaining battery capacity, 0-100%
+    pub remaining_capacity: u8,
+    /// Estimated run time remaining on battery, in seconds
+    pub run_time_to_empty: u16,
+    pub present_status: PresentStatus,
+}
+
+/// Interface implementing a HID Power Device class UPS
+///
+/// Static battery information and the current [`UpsStatus`] are served as feature reports. Changes
+/// to the status are also sent as input reports.
+///
+/// **Note:** Changes are sent one report at a time, [`UsbHidClass::tick()`] must be called every
+/// 1ms to send the remainder of a status update.
+pub struct Ups<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    status: UpsStatus,
+    pending: Deque<u8, 3>,
+    warning_capacity_limit: u8,
+    remaining_capacity_limit: u8,
+    manufacturer_date: u16,
+    device_chemistry: &'a str,
+    device_chemistry_index: StringIndex,
+    feature_report: [u8; 6],
+}
+
+impl<B: UsbBus> Ups<'_, B> {
+    /// Update the UPS status, sending input reports for any changed values
+    pub fn write_status(&mut self, status: &UpsStatus) -> Result<(), UsbHidError> {
+        let old = core::mem::replace(&mut self.status, *status);
+        let changes = [
+            (
+                REMAINING_CAPACITY_REPORT_ID,
+                old.remaining_capacity != status.remaining_capacity,
+            ),
+            (
+                RUN_TIME_TO_EMPTY_REPORT_ID,
+                old.run_time_to_empty != status.run_time_to_empty,
+            ),
+            (
+                PRESENT_STATUS_REPORT_ID,
+                old.present_status != status.present_status,
+            ),
+        ];
+        for (report_id, _) in changes.iter().filter(|(_, changed)| *changed) {
+            if !self.pending.iter().any(|id| id == report_id) {
+                unwrap!(self.pending.push_back(*report_id).ok());
+            }
+        }
+        self.flush()
+    }
+
+    #[must_use]
+    pub fn status(&self) -> UpsStatus {
+        self.status
+    }
+
+    fn flush(&mut self) -> Result<(), UsbHidError> {
+        while let Some(&report_id) = self.pending.front() {
+            let len = self.pack_report(report_id)?;
+            let data = self.feature_report;
+            match self.interface.write_report(&data[..len]) {
+                Ok(_) => {
+                    self.pending.pop_front();
+                }
+                //retry on the next tick
+                Err(UsbError::WouldBlock) => return Ok(()),
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pack a report, including the report ID, into `feature_report` returning its length
+    fn pack_report(&mut self, report_id: u8) -> Result<usize, UsbHidError> {
+        let feature_report = &mut self.feature_report;
+        let mut write = |report: &[u8]| {
+            feature_report[0] = report_id;
+            feature_report[1..=report.len()].copy_from_slice(report);
+            report.len() + 1
+        };
+        let len = match report_id {
+            REMAINING_CAPACITY_REPORT_ID => write(&[self.status.remaining_capacity]),
+            RUN_TIME_TO_EMPTY_REPORT_ID => write(&self.status.run_time_to_empty.to_le_bytes()),
+            PRESENT_STATUS_REPORT_ID => {
+                write(&self.status.present_status.pack().map_err(|_| {
+                    error!("Error packing PresentStatus");
+                    UsbHidError::SerializationError
+                })?)
+            }
+            CAPACITY_REPORT_ID => write(&[
+                CAPACITY_MODE_PERCENT,
+                FULL_CAPACITY,
+                FULL_CAPACITY,
+                self.warning_capacity_limit,
+                self.remaining_capacity_limit,
+            ]),
+            MANUFACTURER_DATE_REPORT_ID => write(&self.manufacturer_date.to_le_bytes()),
+            DEVICE_CHEMISTRY_REPORT_ID => write(&[u8::from(self.device_chemistry_index)]),
+            _ => return Err(UsbHidError::UsbError(UsbError::Unsupported)),
+        };
+        Ok(len)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for Ups<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.flush()
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        let len = self.pack_report(report_id).ok()?;
+        Some(FeatureReport::DynamicReport(&self.feature_report[..len]))
+    }
+
+    fn get_string(&self, index: StringIndex, _lang_id: LangID) -> Option<&'a str> {
+        (index == self.device_chemistry_index).then_some(self.device_chemistry)
+    }
+}
+
+pub struct UpsConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    warning_capacity_limit: u8,
+    remaining_capacity_limit: u8,
+    manufacturer_date: u16,
+    device_chemistry: &'a str,
+}
+
+impl Default for UpsConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                UPS_REPORT_DESCRIPTOR
+            ))
+            .description("UPS")
+            .in_endpoint(100.millis()))
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a> UpsConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>) -> Self {
+        Self {
+            interface,
+            warning_capacity_limit: 20,
+            remaining_capacity_limit: 10,
+            manufacturer_date: 0,
+            device_chemistry: "PbAc",
+        }
+    }
+
+    /// Set the remaining capacity percentages at which the host warns of, and acts on, a low battery
+    pub fn capacity_limits(mut self, warning: u8, remaining: u8) -> BuilderResult<Self> {
+        if warning > FULL_CAPACITY || remaining > warning {
+            return Err(UsbHidBuilderError::ValueOverflow);
+        }
+        self.warning_capacity_limit = warning;
+        self.remaining_capacity_limit = remaining;
+        Ok(self)
+    }
+
+    /// Set the battery manufacture date, `year` must be between 1980 and 2107
+    pub fn manufacturer_date(mut self, year: u16, month: u8, day: u8) -> BuilderResult<Self> {
+        if !(1980..=2107).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day)
+        {
+            return Err(UsbHidBuilderError::ValueOverflow);
+        }
+        self.manufacturer_date = ((year - 1980) << 9) | (u16::from(month) << 5) | u16::from(day);
+        Ok(self)
+    }
+
+    /// Set the battery chemistry, e.g. `PbAc` or `LION`
+    #[must_use]
+    pub fn device_chemistry(mut self, device_chemistry: &'a str) -> Self {
+        self.device_chemistry = device_chemistry;
+        self
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for UpsConfig<'a> {
+    type Allocated = Ups<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            status: UpsStatus::default(),
+            pending: Deque::new(),
+            warning_capacity_limit: self.warning_capacity_limit,
+            remaining_capacity_limit: self.remaining_capacity_limit,
+            manufacturer_date: self.manufacturer_date,
+            device_chemistry: self.device_chemistry,
+            device_chemistry_index: usb_alloc.string(),
+            feature_report: [0; 6],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn present_status_packing() {
+        let status = PresentStatus {
+            charging: true,
+            ac_present: true,
+            shutdown_imminent: true,
+            ..Default::default()
+        };
+        assert_eq!(status.pack().unwrap(), [0b1000_0101]);
+    }
+
+    #[test]
+    fn manufacturer_date_encoding() {
+        let config = UpsConfig::default().manufacturer_date(2024, 3, 15).unwrap();
+        assert_eq!(config.manufacturer_date, (44 << 9) | (3 << 5) | 15);
+        assert!(UpsConfig::default().manufacturer_date(1979, 1, 1).is_err());
+    }
+
+    #[test]
+    fn ups_status_reports() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(UpsConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_status(&UpsStatus {
+                remaining_capacity: 80,
+                run_time_to_empty: 0x0E10,
+                present_status: PresentStatus {
+                    discharging: true,
+                    battery_present: true,
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+
+        // Changed values are sent as input reports, one per tick
+        assert_eq!(manager.host_read_in(), [0x01, 80]);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x02, 0x10, 0x0E]);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x03, 0b1010]);
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+
+        // Get Present Status feature report
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x03, 0x2),
+            [0x03, 0b1010]
+        );
+    }
+}
@@ -0,0 +1,533 @@
+//!HID radial controller, e.g. a Surface Dial style rotary knob
+//!
+//! Rotation is reported in tenths of a degree once the host enables the Resolution Multiplier
+//! feature, otherwise in whole degrees. Encoder detents are accumulated and sent on the next
+//! [`UsbHidClass::tick()`], rotation that does not fit in a single report, that is less than the
+//! reported resolution or that could not be sent is carried over to following ticks.
+use crate::usb_class::prelude::*;
+use crate::usb_class::{BuilderResult, UsbHidBuilderError};
+use fugit::ExtU32;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Largest dial value of a single report, 360° once the host enables the Resolution Multiplier
+pub const MAX_DIAL_DELTA: i16 = 3600;
+/// Largest on-screen position or contact width
+pub const MAX_CONTACT_VALUE: u16 = 0x7FFF;
+
+/// Radial controller report descriptor
+///
+/// * Byte 0, Bit 0 - Button
+/// * Byte 0, Bit 1-7 and Byte 1 - Dial, signed relative rotation in degrees, or tenths of a degree
+///   with the Resolution Multiplier
+///
+/// The dial has a Resolution Multiplier feature control, a single byte feature report with the
+/// multiplier in bits 0-1. The dial is declared at one degree per unit, setting the multiplier
+/// scales it by 10 to a tenth of a degree per unit.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/radial-controller-sample-report-descriptors>
+#[rustfmt::skip]
+pub const RADIAL_CONTROLLER_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,           // Usage Page (Generic Desktop)
+    0x09, 0x0E,           // Usage (System Multi-Axis Controller)
+    0xA1, 0x01,           // Collection (Application)
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x21,           //   Usage (Puck)
+    0xA1, 0x00,           //   Collection (Physical)
+    0x05, 0x09,           //     Usage Page (Button)
+    0x09, 0x01,           //     Usage (Button 1)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0xA1, 0x02,           //     Collection (Logical)
+    0x09, 0x48,           //       Usage (Resolution Multiplier)
+    0x35, 0x01,           //       Physical Minimum (1)
+    0x45, 0x0A,           //       Physical Maximum (10)
+    0x75, 0x02,           //       Report Size (2)
+    0xB1, 0x02,           //       Feature (Data, Variable, Absolute)
+    0x75, 0x06,           //       Report Size (6)
+    0xB1, 0x03,           //       Feature (Constant, Variable, Absolute)
+    0x09, 0x37,           //       Usage (Dial)
+    0x55, 0x00,           //       Unit Exponent (0)
+    0x65, 0x14,           //       Unit (Degrees, English Rotation)
+    0x36, 0xF0, 0xF1,     //       Physical Minimum (-3600)
+    0x46, 0x10, 0x0E,     //       Physical Maximum (3600)
+    0x16, 0xF0, 0xF1,     //       Logical Minimum (-3600)
+    0x26, 0x10, 0x0E,     //       Logical Maximum (3600)
+    0x75, 0x0F,           //       Report Size (15)
+    0x81, 0x06,           //       Input (Data, Variable, Relative)
+    0xC0,                 //     End Collection
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+];
+
+/// Radial controller report descriptor with an on-screen contact
+///
+/// * Byte 0, Bit 0 - Button
+/// * Byte 0, Bit 1-7 and Byte 1 - Dial, signed relative rotation in degrees, or tenths of a degree
+///   with the Resolution Multiplier
+/// * Bytes 2-3 - X, 0-32767 across a 24 inch wide screen
+/// * Bytes 4-5 - Y, 0-32767 across a 14 inch high screen
+/// * Bytes 6-7 - Width of the device on screen, same units as X
+///
+/// The dial has the same Resolution Multiplier feature control as
+/// [`RADIAL_CONTROLLER_REPORT_DESCRIPTOR`].
+#[rustfmt::skip]
+pub const RADIAL_CONTROLLER_CONTACT_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01,           // Usage Page (Generic Desktop)
+    0x09, 0x0E,           // Usage (System Multi-Axis Controller)
+    0xA1, 0x01,           // Collection (Application)
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x21,           //   Usage (Puck)
+    0xA1, 0x00,           //   Collection (Physical)
+    0x05, 0x09,           //     Usage Page (Button)
+    0x09, 0x01,           //     Usage (Button 1)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0xA1, 0x02,           //     Collection (Logical)
+    0x09, 0x48,           //       Usage (Resolution Multiplier)
+    0x35, 0x01,           //       Physical Minimum (1)
+    0x45, 0x0A,           //       Physical Maximum (10)
+    0x75, 0x02,           //       Report Size (2)
+    0xB1, 0x02,           //       Feature (Data, Variable, Absolute)
+    0x75, 0x06,           //       Report Size (6)
+    0xB1, 0x03,           //       Feature (Constant, Variable, Absolute)
+    0x09, 0x37,           //       Usage (Dial)
+    0x55, 0x00,           //       Unit Exponent (0)
+    0x65, 0x14,           //       Unit (Degrees, English Rotation)
+    0x36, 0xF0, 0xF1,     //       Physical Minimum (-3600)
+    0x46, 0x10, 0x0E,     //       Physical Maximum (3600)
+    0x16, 0xF0, 0xF1,     //       Logical Minimum (-3600)
+    0x26, 0x10, 0x0E,     //       Logical Maximum (3600)
+    0x75, 0x0F,           //       Report Size (15)
+    0x81, 0x06,           //       Input (Data, Variable, Relative)
+    0xC0,                 //     End Collection
+    0x09, 0x30,           //     Usage (X)
+    0x55, 0x0D,           //     Unit Exponent (-3)
+    0x65, 0x13,           //     Unit (Inch, English Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xC0, 0x5D,     //     Physical Maximum (24000)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x26, 0xFF, 0x7F,     //     Logical Maximum (32767)
+    0x75, 0x10,           //     Report Size (16)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x09, 0x31,           //     Usage (Y)
+    0x46, 0xB0, 0x36,     //     Physical Maximum (14000)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x46, 0xC0, 0x5D,     //     Physical Maximum (24000)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+];
+
+/// Position of the controller on screen, only reported by a controller configured with
+/// [`RadialControllerConfig::with_contact`]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub struct RadialContact {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+}
+
+/// Interface implementing a radial controller
+///
+/// **Note:** Reports are sent from [`UsbHidClass::tick()`], which must be called every 1ms.
+pub struct RadialController<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+    resolution: i16,
+    has_contact: bool,
+    hi_res: bool,
+    feature_report: [u8; 1],
+    /// Rotation not yet sent, in tenths of a degree
+    rotation: i32,
+    button: bool,
+    contact: RadialContact,
+    changed: bool,
+}
+
+impl<B: UsbBus> RadialController<'_, B> {
+    /// Add encoder detents to the rotation sent on the next tick, positive values are clockwise
+    pub fn rotate(&mut self, detents: i32) {
+        self.rotation = self
+            .rotation
+            .saturating_add(detents.saturating_mul(i32::from(self.resolution)));
+    }
+
+    pub fn set_button(&mut self, pressed: bool) {
+        self.changed |= self.button != pressed;
+        self.button = pressed;
+    }
+
+    /// Set the on-screen position of the controller, values are limited to [`MAX_CONTACT_VALUE`]
+    ///
+    /// Ignored unless the controller was configured with [`RadialControllerConfig::with_contact`]
+    pub fn set_contact(&mut self, contact: RadialContact) {
+        let contact = RadialContact {
+            x: contact.x.min(MAX_CONTACT_VALUE),
+            y: contact.y.min(MAX_CONTACT_VALUE),
+            width: contact.width.min(MAX_CONTACT_VALUE),
+        };
+        self.changed |= self.has_contact && self.contact != contact;
+        self.contact = contact;
+    }
+
+    /// Rotation waiting to be sent, in tenths of a degree
+    #[must_use]
+    pub fn pending_rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Whether the host has enabled the dial Resolution Multiplier
+    #[must_use]
+    pub fn hi_res(&self) -> bool {
+        self.hi_res
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn pack(&self, dial: i16) -> ([u8; 8], usize) {
+        let [b0, b1] = ((dial as u16) << 1 | u16::from(self.button)).to_le_bytes();
+        let [x0, x1] = self.contact.x.to_le_bytes();
+        let [y0, y1] = self.contact.y.to_le_bytes();
+        let [w0, w1] = self.contact.width.to_le_bytes();
+        let len = if self.has_contact { 8 } else { 2 };
+        ([b0, b1, x0, x1, y0, y1, w0, w1], len)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for RadialController<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        // the host sets the multiplier again after enumeration
+        self.hi_res = false;
+        self.rotation = 0;
+        self.changed = false;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        let scale = if self.hi_res { 1 } else { 10 };
+        let units = self.rotation / scale;
+        if units == 0 && !self.changed {
+            return Ok(());
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let dial = units.clamp(i32::from(-MAX_DIAL_DELTA), i32::from(MAX_DIAL_DELTA)) as i16;
+        let (report, len) = self.pack(dial);
+
+        match self.interface.write_report(&report[..len]) {
+            Ok(_) => {
+                self.rotation -= i32::from(dial) * scale;
+                self.changed = false;
+                Ok(())
+            }
+            //retry on the next tick
+            Err(UsbError::WouldBlock) => Ok(()),
+            Err(e) => Err(UsbHidError::UsbError(e)),
+        }
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        if report_id != 0 {
+            return None;
+        }
+        self.feature_report = [u8::from(self.hi_res)];
+        Some(FeatureReport::DynamicReport(&self.feature_report))
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        let [multiplier] = data else {
+            return Err(UsbError::ParseError);
+        };
+        if report_id != 0 {
+            return Err(UsbError::Unsupported);
+        }
+        self.hi_res = multiplier & 0x03 != 0;
+        Ok(())
+    }
+}
+
+pub struct RadialControllerConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+    resolution: i16,
+    has_contact: bool,
+}
+
+impl Default for RadialControllerConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(
+                unwrap!(InterfaceBuilder::new(RADIAL_CONTROLLER_REPORT_DESCRIPTOR))
+                    .description("Radial Controller")
+                    .in_endpoint(10.millis())
+            )
+            .without_out_endpoint()
+            .build(),
+            false,
+        )
+    }
+}
+
+impl<'a> RadialControllerConfig<'a> {
+    /// Create a configuration from an interface using [`RADIAL_CONTROLLER_REPORT_DESCRIPTOR`],
+    /// or [`RADIAL_CONTROLLER_CONTACT_REPORT_DESCRIPTOR`] if `has_contact` is set
+    #[must_use]
+    pub fn new(
+        interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
+        has_contact: bool,
+    ) -> Self {
+        Self {
+            interface,
+            resolution: 150,
+            has_contact,
+        }
+    }
+
+    /// Configuration reporting the on-screen position of the controller
+    #[must_use]
+    pub fn with_contact() -> Self {
+        Self::new(
+            unwrap!(unwrap!(InterfaceBuilder::new(
+                RADIAL_CONTROLLER_CONTACT_REPORT_DESCRIPTOR
+            ))
+            .description("Radial Controller")
+            .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+            true,
+        )
+    }
+
+    /// Set the rotation of one encoder detent in tenths of a degree, defaults to 150 (15°) for a
+    /// 24 detent encoder
+    pub fn resolution(mut self, tenths_of_degree: u16) -> BuilderResult<Self> {
+        if tenths_of_degree == 0 || tenths_of_degree > MAX_DIAL_DELTA.unsigned_abs() {
+            return Err(UsbHidBuilderError::ValueOverflow);
+        }
+        self.resolution = unwrap!(i16::try_from(tenths_of_degree).ok());
+        Ok(self)
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for RadialControllerConfig<'a> {
+    type Allocated = RadialController<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            resolution: self.resolution,
+            has_contact: self.has_contact,
+            hi_res: false,
+            feature_report: [0],
+            rotation: 0,
+            button: false,
+            contact: RadialContact::default(),
+            changed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    /// Thousandths of a degree per dial unit declared by `descriptor`, without and with the
+    /// Resolution Multiplier set
+    fn dial_millidegrees_per_unit(descriptor: &[u8]) -> (i64, i64) {
+        let (mut logical, mut physical, mut exponent) = ((0, 0), (0, 0), 0);
+        let mut usage = None;
+        let mut multiplier = 1;
+        let mut dial = None;
+
+        let mut items = descriptor;
+        while let [prefix, rest @ ..] = items {
+            let size = [0, 1, 2, 4][usize::from(prefix & 0x03)];
+            let (data, rest) = rest.split_at(size);
+            items = rest;
+            let value = match *data {
+                [] => 0,
+                [b0] => i64::from(i8::from_le_bytes([b0])),
+                [b0, b1] => i64::from(i16::from_le_bytes([b0, b1])),
+                [b0, b1, b2, b3] => i64::from(i32::from_le_bytes([b0, b1, b2, b3])),
+                _ => unreachable!(),
+            };
+            let unsigned = data.iter().rev().fold(0, |v, &b| v << 8 | i64::from(b));
+
+            match prefix & 0xFC {
+                0x14 => logical.0 = value,
+                0x24 => logical.1 = value,
+                0x34 => physical.0 = value,
+                0x44 => physical.1 = value,
+                // 4-bit signed nibble
+                0x54 => exponent = (unsigned ^ 0x08) - 0x08,
+                0x08 => usage = usage.or(Some(unsigned)),
+                // Feature
+                0xB0 if usage == Some(0x48) => multiplier = physical.1 / physical.0,
+                // Input
+                0x80 if usage == Some(0x37) => dial = Some((logical, physical, exponent)),
+                _ => {}
+            }
+            if prefix & 0x0C == 0x00 {
+                // main items end the local usages
+                usage = None;
+            }
+        }
+
+        let ((lmin, lmax), (pmin, pmax), exponent) = dial.unwrap();
+        let scale = 10_i64.pow(exponent.unsigned_abs().try_into().unwrap());
+        let millidegrees = (pmax - pmin) * 1000 / (lmax - lmin);
+        let millidegrees = if exponent < 0 {
+            millidegrees / scale
+        } else {
+            millidegrees * scale
+        };
+        (millidegrees, millidegrees / multiplier)
+    }
+
+    #[test]
+    fn descriptor_dial_units_match_reports() {
+        for (descriptor, config) in [
+            (
+                RADIAL_CONTROLLER_REPORT_DESCRIPTOR,
+                RadialControllerConfig::default(),
+            ),
+            (
+                RADIAL_CONTROLLER_CONTACT_REPORT_DESCRIPTOR,
+                RadialControllerConfig::with_contact(),
+            ),
+        ] {
+            let (low_res, hi_res) = dial_millidegrees_per_unit(descriptor);
+            assert_eq!((low_res, hi_res), (1000, 100));
+
+            let (manager, usb_alloc) = test_usb();
+            let mut hid = UsbHidClassBuilder::new()
+                .add_device(config)
+                .build(usb_alloc);
+            let mut usb_dev = test_usb_device(usb_alloc);
+
+            let dial = || {
+                let report = manager.host_read_in();
+                i64::from(i16::from_le_bytes([report[0], report[1]]) >> 1)
+            };
+
+            // One 15° detent in each mode
+            hid.device().rotate(1);
+            hid.tick().unwrap();
+            assert_eq!(dial() * low_res, 15_000);
+
+            manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0x01]);
+            hid.device().rotate(1);
+            hid.tick().unwrap();
+            assert_eq!(dial() * hi_res, 15_000);
+        }
+    }
+
+    #[test]
+    fn radial_controller_accumulates_detents() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(RadialControllerConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0x01]);
+        assert!(hid.device().hi_res());
+
+        hid.device().rotate(3);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x84, 0x03], "Expected 45 degrees");
+
+        // Rotation while the endpoint is busy is combined into the next report
+        hid.device().rotate(-1);
+        hid.tick().unwrap();
+        hid.device().rotate(-2);
+        hid.tick().unwrap();
+        hid.device().rotate(-1);
+        assert_eq!(manager.host_read_in(), [0xD4, 0xFE], "Expected -15 degrees");
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x7C, 0xFC], "Expected -45 degrees");
+
+        // Rotation beyond a single report is split across ticks
+        hid.device().rotate(30);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x20, 0x1C], "Expected 360 degrees");
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x08, 0x07], "Expected 90 degrees");
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+
+        hid.device().set_button(true);
+        hid.tick().unwrap();
+        assert_eq!(
+            manager.host_read_in(),
+            [0x01, 0x00],
+            "Expected button press"
+        );
+    }
+
+    #[test]
+    fn radial_controller_whole_degrees_until_multiplier_set() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(
+                RadialControllerConfig::with_contact()
+                    .resolution(25)
+                    .unwrap(),
+            )
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 1),
+            [0x00]
+        );
+
+        // 2.5 degrees is sent as 2 degrees, the remainder is carried over
+        hid.device().rotate(1);
+        hid.tick().unwrap();
+        assert_eq!(
+            manager.host_read_in(),
+            [0x04, 0x00, 0, 0, 0, 0, 0, 0],
+            "Expected 2 degrees"
+        );
+        assert_eq!(hid.device().pending_rotation(), 5);
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0x01]);
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 1),
+            [0x01]
+        );
+
+        hid.tick().unwrap();
+        assert_eq!(
+            manager.host_read_in(),
+            [0x0A, 0x00, 0, 0, 0, 0, 0, 0],
+            "Expected 0.5 degrees"
+        );
+    }
+}
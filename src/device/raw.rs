@@ -0,0 +1,340 @@
+//! Generic vendor-defined raw HID
+use crate::descriptor::append;
+use crate::interface::{InSize, OutSize, ReportBuffer};
+use crate::usb_class::prelude::*;
+use core::marker::PhantomData;
+use fugit::ExtU32;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Largest feature report supported by [`RawHid`]
+pub const MAX_FEATURE_REPORT_SIZE: u8 = 64;
+
+const MAX_DESCRIPTOR_LEN: usize = 34;
+
+/// Raw HID report descriptor for a vendor-defined usage page and usage
+///
+/// The input and output report sizes are those of the `I` and `O` interface buffers. An input,
+/// output or feature report with a size of zero is omitted from the descriptor.
+///
+/// Built at compile time, for example:
+/// ```
+/// # use usbd_human_interface_device::device::raw::RawHidDescriptor;
+/// # use usbd_human_interface_device::usb_class::prelude::*;
+/// static DESCRIPTOR: RawHidDescriptor<InBytes32, OutBytes32> = RawHidDescriptor::new(0xFF00, 0x01, 8);
+/// ```
+pub struct RawHidDescriptor<I, O> {
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+    feature_size: u8,
+    marker: PhantomData<(I, O)>,
+}
+
+impl<I: InSize, O: OutSize> RawHidDescriptor<I, O> {
+    /// Create a descriptor, `feature_size` is limited to [`MAX_FEATURE_REPORT_SIZE`]
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn new(usage_page: u16, usage: u16, feature_size: u8) -> Self {
+        let feature_size = if feature_size > MAX_FEATURE_REPORT_SIZE {
+            MAX_FEATURE_REPORT_SIZE
+        } else {
+            feature_size
+        };
+        let [page_lo, page_hi] = usage_page.to_le_bytes();
+        let [usage_lo, usage_hi] = usage.to_le_bytes();
+
+        let mut bytes = [0u8; MAX_DESCRIPTOR_LEN];
+        let mut len = 0;
+        #[rustfmt::skip]
+        let header = [
+            0x06, page_lo, page_hi, // Usage Page (Vendor Defined),
+            0x0A, usage_lo, usage_hi, // Usage (Vendor Usage),
+            0xA1, 0x01, // Collection (Application),
+            0x15, 0x00, //     Logical Minimum (0),
+            0x26, 0xFF, 0x00, //     Logical Maximum (255),
+            0x75, 0x08, //     Report Size (8),
+        ];
+        (bytes, len) = append(bytes, len, &header);
+
+        // Usage, Report Count and main item for each of the input, output and feature reports
+        let reports = [
+            (0x01, I::Buffer::CAPACITY as u8, 0x81),
+            (0x02, O::Buffer::CAPACITY as u8, 0x91),
+            (0x03, feature_size, 0xB1),
+        ];
+        let mut i = 0;
+        while i < reports.len() {
+            let (usage, count, main_item) = reports[i];
+            if count > 0 {
+                (bytes, len) = append(bytes, len, &[0x09, usage, 0x95, count, main_item, 0x02]);
+            }
+            i += 1;
+        }
+
+        (bytes, len) = append(bytes, len, &[0xC0]); // End Collection
+
+        Self {
+            bytes,
+            len,
+            feature_size,
+            marker: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+}
+
+/// Interface implementing a vendor-defined raw HID device, e.g. for WebHID or configuration
+/// utilities
+pub struct RawHid<'a, B: UsbBus, I: InSize, O: OutSize> {
+    interface: Interface<'a, B, I, O, ReportSingle>,
+    /// Feature report returned to the host
+    feature_in: [u8; MAX_FEATURE_REPORT_SIZE as usize],
+    /// Feature report last set by the host
+    feature_out: [u8; MAX_FEATURE_REPORT_SIZE as usize],
+    feature_size: usize,
+    feature_report_set: bool,
+}
+
+impl<B: UsbBus, I: InSize, O: OutSize> RawHid<'_, B, I, O> {
+    /// Write an input report, padding `data` with zeros to the input report size
+    pub fn write_report(&mut self, data: &[u8]) -> Result<(), UsbHidError> {
+        let size = usize::from(I::Buffer::CAPACITY);
+        if data.len() > size {
+            return Err(UsbHidError::UsbError(UsbError::BufferOverflow));
+        }
+        let mut report = [0u8; 64];
+        report[..data.len()].copy_from_slice(data);
+        self.interface
+            .write_report(&report[..size])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+
+    pub fn read_report(&mut self, data: &mut [u8]) -> usb_device::Result<usize> {
+        self.interface.read_report(data)
+    }
+
+    /// Set the feature report returned to the host, padding `data` with zeros to the feature
+    /// report size
+    pub fn write_feature_report(&mut self, data: &[u8]) -> Result<(), UsbHidError> {
+        if self.feature_size == 0 {
+            return Err(UsbHidError::UsbError(UsbError::Unsupported));
+        }
+        if data.len() > self.feature_size {
+            return Err(UsbHidError::UsbError(UsbError::BufferOverflow));
+        }
+        self.feature_in.fill(0);
+        self.feature_in[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Read the feature report last set by the host
+    ///
+    /// Returns [`UsbError::WouldBlock`] if the host has not set the feature report since the last
+    /// read
+    pub fn read_feature_report(&mut self, data: &mut [u8]) -> usb_device::Result<usize> {
+        if !self.feature_report_set {
+            return Err(UsbError::WouldBlock);
+        }
+        if data.len() < self.feature_size {
+            return Err(UsbError::BufferOverflow);
+        }
+        data[..self.feature_size].copy_from_slice(&self.feature_out[..self.feature_size]);
+        self.feature_report_set = false;
+        Ok(self.feature_size)
+    }
+}
+
+impl<'a, B: UsbBus, I: InSize, O: OutSize> DeviceClass<'a> for RawHid<'a, B, I, O> {
+    type I = Interface<'a, B, I, O, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {}
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        (report_id == 0 && self.feature_size > 0).then_some(FeatureReport::DynamicReport(
+            &self.feature_in[..self.feature_size],
+        ))
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        if report_id != 0 || self.feature_size == 0 {
+            return Err(UsbError::Unsupported);
+        }
+        if data.len() > self.feature_size {
+            return Err(UsbError::BufferOverflow);
+        }
+        self.feature_out.fill(0);
+        self.feature_out[..data.len()].copy_from_slice(data);
+        self.feature_report_set = true;
+        Ok(())
+    }
+}
+
+pub struct RawHidConfig<'a, I: InSize, O: OutSize> {
+    interface: InterfaceConfig<'a, I, O, ReportSingle>,
+    feature_size: u8,
+}
+
+impl Default for RawHidConfig<'_, InBytes64, OutBytes64> {
+    fn default() -> Self {
+        static DESCRIPTOR: RawHidDescriptor<InBytes64, OutBytes64> =
+            RawHidDescriptor::new(0xFF00, 0x01, 0);
+        Self::new(&DESCRIPTOR)
+    }
+}
+
+impl<'a, I: InSize, O: OutSize> RawHidConfig<'a, I, O> {
+    /// Create a configuration using `descriptor`, with an output endpoint if `O` is not [`OutNone`]
+    #[must_use]
+    pub fn new(descriptor: &'a RawHidDescriptor<I, O>) -> Self {
+        let builder = unwrap!(unwrap!(InterfaceBuilder::new(descriptor.as_bytes()))
+            .description("Raw HID")
+            .in_endpoint(5.millis()));
+        let builder = if O::Buffer::CAPACITY > 0 {
+            unwrap!(builder.with_out_endpoint(5.millis()))
+        } else {
+            builder.without_out_endpoint()
+        };
+        Self::with_interface(builder.build(), descriptor.feature_size)
+    }
+
+    /// Create a configuration from an interface built with a [`RawHidDescriptor`] with a feature
+    /// report of `feature_size` bytes
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, I, O, ReportSingle>,
+        feature_size: u8,
+    ) -> Self {
+        Self {
+            interface,
+            feature_size: feature_size.min(MAX_FEATURE_REPORT_SIZE),
+        }
+    }
+}
+
+impl<'a, B: UsbBus + 'a, I: InSize + 'a, O: OutSize + 'a> UsbAllocatable<'a, B>
+    for RawHidConfig<'a, I, O>
+{
+    type Allocated = RawHid<'a, B, I, O>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            feature_in: [0; MAX_FEATURE_REPORT_SIZE as usize],
+            feature_out: [0; MAX_FEATURE_REPORT_SIZE as usize],
+            feature_size: usize::from(self.feature_size),
+            feature_report_set: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn descriptor_omits_empty_reports() {
+        let descriptor = RawHidDescriptor::<InBytes32, OutNone>::new(0xFF60, 0x61, 0);
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x06, 0x60, 0xFF,
+            0x0A, 0x61, 0x00,
+            0xA1, 0x01,
+            0x15, 0x00,
+            0x26, 0xFF, 0x00,
+            0x75, 0x08,
+            0x09, 0x01, 0x95, 0x20, 0x81, 0x02,
+            0xC0,
+        ];
+        assert_eq!(descriptor.as_bytes(), expected);
+    }
+
+    #[test]
+    fn descriptor_with_all_reports() {
+        let descriptor = RawHidDescriptor::<InBytes64, OutBytes64>::new(0xFF00, 0x01, 200);
+        assert_eq!(descriptor.as_bytes().len(), MAX_DESCRIPTOR_LEN);
+        assert_eq!(
+            &descriptor.as_bytes()[15..],
+            &[
+                0x09, 0x01, 0x95, 0x40, 0x81, 0x02, 0x09, 0x02, 0x95, 0x40, 0x91, 0x02, 0x09, 0x03,
+                0x95, 0x40, 0xB1, 0x02, 0xC0
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_hid_feature_report() {
+        static DESCRIPTOR: RawHidDescriptor<InBytes8, OutNone> =
+            RawHidDescriptor::new(0xFF00, 0x01, 4);
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(RawHidConfig::new(&DESCRIPTOR))
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        hid.device().write_report(&[0x12, 0x34]).unwrap();
+        assert_eq!(manager.host_read_in(), [0x12, 0x34, 0, 0, 0, 0, 0, 0]);
+
+        // Set feature report
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0xAB, 0xCD]);
+
+        let mut data = [0u8; 4];
+        assert_eq!(hid.device().read_feature_report(&mut data), Ok(4));
+        assert_eq!(data, [0xAB, 0xCD, 0, 0]);
+        assert_eq!(
+            hid.device().read_feature_report(&mut data),
+            Err(UsbError::WouldBlock)
+        );
+    }
+
+    #[test]
+    fn feature_report_directions_independent() {
+        static DESCRIPTOR: RawHidDescriptor<InBytes8, OutNone> =
+            RawHidDescriptor::new(0xFF00, 0x01, 4);
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(RawHidConfig::new(&DESCRIPTOR))
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_feature_report(&[0x01, 0x02, 0x03])
+            .unwrap();
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0, &[0xAB, 0xCD]);
+
+        // The host's report does not replace the device's
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 4),
+            [0x01, 0x02, 0x03, 0x00]
+        );
+
+        // Nor does the device's report replace the host's before it is read
+        hid.device().write_feature_report(&[0x04]).unwrap();
+        let mut data = [0u8; 4];
+        assert_eq!(hid.device().read_feature_report(&mut data), Ok(4));
+        assert_eq!(data, [0xAB, 0xCD, 0, 0]);
+    }
+}
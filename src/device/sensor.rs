@@ -0,0 +1,657 @@
+//!HID sensors, e.g. accelerometers and ambient light sensors
+//!
+//! Implements the Sensor usage page (0x20) as used by the Windows sensor class driver and the
+//! Linux `hid-sensor-hub` driver. Each sensor is a separate interface with a property feature
+//! report and a data input report:
+//!
+//! Feature report:
+//! * Byte 0 - Reporting State, [`ReportingState`]
+//! * Byte 1 - Power State, [`PowerState`]
+//! * Byte 2 - Sensor State, [`SensorState`]
+//! * Bytes 3-6 - Report Interval in milliseconds
+//! * Bytes 7-8 - Change Sensitivity Absolute, in units of the first data field
+//!
+//! Input report:
+//! * Byte 0 - Sensor State, [`SensorState`]
+//! * Byte 1 - Sensor Event, [`SensorEvent`]
+//! * Bytes 2.. - Data fields, 16 bit signed values
+//!
+//! Concrete sensors are [`Accelerometer3D`], [`AmbientLight`] and [`Temperature`], others can be
+//! added by implementing [`Sensor`] with a [`SensorDescriptor`].
+//!
+//! See [HID Usage Tables FOR Universal Serial Bus (USB) Version 1.5](<https://usb.org/sites/default/files/hut1_5.pdf>), Sensors Page
+use crate::descriptor::append;
+use crate::usb_class::prelude::*;
+use core::marker::PhantomData;
+use fugit::ExtU32;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Largest number of data fields in a sensor input report
+pub const MAX_SENSOR_FIELDS: usize = 4;
+
+const MAX_DESCRIPTOR_LEN: usize = 288;
+const FEATURE_REPORT_SIZE: usize = 9;
+const INPUT_REPORT_SIZE: usize = 2 + 2 * MAX_SENSOR_FIELDS;
+
+/// Modifier applied to a data field usage to form its Change Sensitivity Absolute usage
+const CHANGE_SENSITIVITY_ABSOLUTE: u16 = 0x1000;
+
+/// Selectors of the Reporting State property, when the sensor sends input reports
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum ReportingState {
+    NoEvents = 0,
+    #[default]
+    AllEvents = 1,
+    /// Only when a data field changes by more than the change sensitivity
+    ThresholdEvents = 2,
+    NoEventsWake = 3,
+    AllEventsWake = 4,
+    ThresholdEventsWake = 5,
+}
+
+/// Selectors of the Power State property, requested by the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum PowerState {
+    Undefined = 0,
+    #[default]
+    FullPower = 1,
+    LowPower = 2,
+    StandbyWithWake = 3,
+    SleepWithWake = 4,
+    PowerOff = 5,
+}
+
+/// Selectors of the Sensor State, set by the device
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum SensorState {
+    Unknown = 0,
+    #[default]
+    Ready = 1,
+    NotAvailable = 2,
+    NoData = 3,
+    Initializing = 4,
+    AccessDenied = 5,
+    Error = 6,
+}
+
+/// Selectors of the Sensor Event, the reason an input report was sent
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum SensorEvent {
+    Unknown = 0,
+    StateChanged = 1,
+    PropertyChanged = 2,
+    #[default]
+    DataUpdated = 3,
+    PollResponse = 4,
+    ChangeSensitivity = 5,
+}
+
+/// Data field of a sensor input report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SensorField {
+    /// Sensor page data field usage, e.g. 0x0453 Acceleration Axis X
+    pub usage: u16,
+    pub logical_min: i16,
+    pub logical_max: i16,
+    /// Power of ten applied to values in the unit implied by the usage, -8 to 7
+    pub unit_exponent: i8,
+}
+
+impl SensorField {
+    #[must_use]
+    pub const fn new(usage: u16, logical_min: i16, logical_max: i16, unit_exponent: i8) -> Self {
+        Self {
+            usage,
+            logical_min,
+            logical_max,
+            unit_exponent,
+        }
+    }
+}
+
+/// Sensor report descriptor
+///
+/// Built at compile time from the sensor type usage, the data field whose Change Sensitivity
+/// Absolute property is reported and up to [`MAX_SENSOR_FIELDS`] data fields.
+pub struct SensorDescriptor {
+    bytes: [u8; MAX_DESCRIPTOR_LEN],
+    len: usize,
+    field_count: usize,
+}
+
+impl SensorDescriptor {
+    /// Create a descriptor, data fields beyond [`MAX_SENSOR_FIELDS`] are ignored
+    #[must_use]
+    pub const fn new(sensor_type: u8, sensitivity_usage: u16, fields: &[SensorField]) -> Self {
+        let field_count = if fields.len() > MAX_SENSOR_FIELDS {
+            MAX_SENSOR_FIELDS
+        } else {
+            fields.len()
+        };
+        let sensitivity_exponent = if field_count > 0 {
+            fields[0].unit_exponent
+        } else {
+            0
+        };
+
+        let mut bytes = [0u8; MAX_DESCRIPTOR_LEN];
+        let mut len = 0;
+
+        #[rustfmt::skip]
+        let header = [
+            0x05, 0x20,         // Usage Page (Sensor),
+            0x09, sensor_type,  // Usage (Sensor Type),
+            0xA1, 0x01,         // Collection (Application),
+            0x75, 0x08,         //   Report Size (8),
+            0x95, 0x01,         //   Report Count (1),
+            0x15, 0x00,         //   Logical Minimum (0),
+        ];
+        (bytes, len) = append(bytes, len, &header);
+
+        // Properties
+        (bytes, len) = append_selectors(bytes, len, 0x0316, 0x0840, 6, 0xB1); // Reporting State
+        (bytes, len) = append_selectors(bytes, len, 0x0319, 0x0850, 6, 0xB1); // Power State
+        (bytes, len) = append_selectors(bytes, len, 0x0201, 0x0800, 7, 0xB1); // Sensor State
+
+        #[rustfmt::skip]
+        let report_interval = [
+            0x0A, 0x0E, 0x03,               //   Usage (Report Interval),
+            0x27, 0xFF, 0xFF, 0xFF, 0x7F,   //   Logical Maximum (2147483647),
+            0x75, 0x20,                     //   Report Size (32),
+            0x55, 0x0D,                     //   Unit Exponent (-3),
+            0x66, 0x01, 0x10,               //   Unit (Seconds),
+            0xB1, 0x02,                     //   Feature (Data, Variable, Absolute),
+            0x65, 0x00,                     //   Unit (None),
+        ];
+        (bytes, len) = append(bytes, len, &report_interval);
+
+        let [usage_lo, usage_hi] = (sensitivity_usage | CHANGE_SENSITIVITY_ABSOLUTE).to_le_bytes();
+        #[rustfmt::skip]
+        let sensitivity = [
+            0x0A, usage_lo, usage_hi,                       //   Usage (Change Sensitivity Absolute),
+            0x26, 0xFF, 0x7F,                               //   Logical Maximum (32767),
+            0x75, 0x10,                                     //   Report Size (16),
+            0x55, exponent(sensitivity_exponent),           //   Unit Exponent,
+            0xB1, 0x02,                                     //   Feature (Data, Variable, Absolute),
+            0x55, 0x00,                                     //   Unit Exponent (0),
+            0x75, 0x08,                                     //   Report Size (8),
+        ];
+        (bytes, len) = append(bytes, len, &sensitivity);
+
+        // Input state, event and data fields
+        (bytes, len) = append_selectors(bytes, len, 0x0201, 0x0800, 7, 0x81); // Sensor State
+        (bytes, len) = append_selectors(bytes, len, 0x0202, 0x0810, 6, 0x81); // Sensor Event
+
+        let mut i = 0;
+        while i < field_count {
+            let field = &fields[i];
+            let [usage_lo, usage_hi] = field.usage.to_le_bytes();
+            let [min_lo, min_hi] = field.logical_min.to_le_bytes();
+            let [max_lo, max_hi] = field.logical_max.to_le_bytes();
+            #[rustfmt::skip]
+            let data = [
+                0x0A, usage_lo, usage_hi,           //   Usage (Data Field),
+                0x16, min_lo, min_hi,               //   Logical Minimum,
+                0x26, max_lo, max_hi,               //   Logical Maximum,
+                0x75, 0x10,                         //   Report Size (16),
+                0x55, exponent(field.unit_exponent),//   Unit Exponent,
+                0x81, 0x02,                         //   Input (Data, Variable, Absolute),
+            ];
+            (bytes, len) = append(bytes, len, &data);
+            i += 1;
+        }
+
+        (bytes, len) = append(bytes, len, &[0x55, 0x00, 0xC0]); // Unit Exponent (0), End Collection
+
+        Self {
+            bytes,
+            len,
+            field_count,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+}
+
+/// Unit exponent item data, a 4 bit two's complement value
+#[allow(clippy::cast_sign_loss)]
+const fn exponent(value: i8) -> u8 {
+    (value as u8) & 0x0F
+}
+
+/// Append an 8 bit named array of `count` selectors starting at `first_selector`
+#[allow(clippy::cast_possible_truncation)]
+const fn append_selectors(
+    mut bytes: [u8; MAX_DESCRIPTOR_LEN],
+    mut len: usize,
+    usage: u16,
+    first_selector: u16,
+    count: u8,
+    main_item: u8,
+) -> ([u8; MAX_DESCRIPTOR_LEN], usize) {
+    let [usage_lo, usage_hi] = usage.to_le_bytes();
+    #[rustfmt::skip]
+    let header = [
+        0x0A, usage_lo, usage_hi,   //   Usage,
+        0x25, count - 1,            //   Logical Maximum,
+        0xA1, 0x02,                 //   Collection (Logical),
+    ];
+    (bytes, len) = append(bytes, len, &header);
+
+    let mut i = 0;
+    while i < count {
+        let [selector_lo, selector_hi] = (first_selector + i as u16).to_le_bytes();
+        (bytes, len) = append(bytes, len, &[0x0A, selector_lo, selector_hi]); //     Usage (Selector),
+        i += 1;
+    }
+
+    // Feature or Input (Data, Array, Absolute), End Collection
+    append(bytes, len, &[main_item, 0x00, 0xC0])
+}
+
+/// A type of sensor and its data
+pub trait Sensor: Copy {
+    /// Descriptor of the sensor
+    fn descriptor() -> &'static SensorDescriptor;
+    /// Values of the data fields, in the order of the descriptor fields
+    fn values(&self) -> [i16; MAX_SENSOR_FIELDS];
+}
+
+/// 3D accelerometer, acceleration in hundredths of a G
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Accelerometer3D {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+/// Descriptor of [`Accelerometer3D`], ±16G in hundredths of a G
+pub static ACCELEROMETER_3D_DESCRIPTOR: SensorDescriptor = SensorDescriptor::new(
+    0x73,   // Motion: Accelerometer 3D
+    0x0452, // Acceleration
+    &[
+        SensorField::new(0x0453, -1600, 1600, -2), // Acceleration Axis X
+        SensorField::new(0x0454, -1600, 1600, -2), // Acceleration Axis Y
+        SensorField::new(0x0455, -1600, 1600, -2), // Acceleration Axis Z
+    ],
+);
+
+impl Sensor for Accelerometer3D {
+    fn descriptor() -> &'static SensorDescriptor {
+        &ACCELEROMETER_3D_DESCRIPTOR
+    }
+
+    fn values(&self) -> [i16; MAX_SENSOR_FIELDS] {
+        [self.x, self.y, self.z, 0]
+    }
+}
+
+/// Ambient light sensor, illuminance in lux
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct AmbientLight {
+    pub illuminance: i16,
+}
+
+/// Descriptor of [`AmbientLight`], 0-32767 lux
+pub static AMBIENT_LIGHT_DESCRIPTOR: SensorDescriptor = SensorDescriptor::new(
+    0x41,                                        // Light: Ambient Light
+    0x04D1,                                      // Illuminance
+    &[SensorField::new(0x04D1, 0, i16::MAX, 0)], // Illuminance
+);
+
+impl Sensor for AmbientLight {
+    fn descriptor() -> &'static SensorDescriptor {
+        &AMBIENT_LIGHT_DESCRIPTOR
+    }
+
+    fn values(&self) -> [i16; MAX_SENSOR_FIELDS] {
+        [self.illuminance, 0, 0, 0]
+    }
+}
+
+/// Temperature sensor, temperature in hundredths of a degree Celsius
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Temperature {
+    pub temperature: i16,
+}
+
+/// Descriptor of [`Temperature`], -100 to 200 °C in hundredths of a degree
+pub static TEMPERATURE_DESCRIPTOR: SensorDescriptor = SensorDescriptor::new(
+    0x33,                                           // Environmental: Temperature
+    0x0434,                                         // Environmental: Temperature
+    &[SensorField::new(0x0434, -10000, 20000, -2)], // Environmental: Temperature
+);
+
+impl Sensor for Temperature {
+    fn descriptor() -> &'static SensorDescriptor {
+        &TEMPERATURE_DESCRIPTOR
+    }
+
+    fn values(&self) -> [i16; MAX_SENSOR_FIELDS] {
+        [self.temperature, 0, 0, 0]
+    }
+}
+
+/// Interface implementing a sensor
+///
+/// The host sets the reporting state, power state, report interval and change sensitivity
+/// properties. [`Self::write_data`] only sends data the reporting state asks for, the power
+/// state and report interval are for the application to act on.
+pub struct HidSensor<'a, B: UsbBus, S: Sensor> {
+    interface: Interface<'a, B, InBytes16, OutNone, ReportSingle>,
+    field_count: usize,
+    reporting_state: ReportingState,
+    power_state: PowerState,
+    sensor_state: SensorState,
+    report_interval_ms: u32,
+    sensitivity: u16,
+    last_values: Option<[i16; MAX_SENSOR_FIELDS]>,
+    properties_changed: bool,
+    feature_report: [u8; FEATURE_REPORT_SIZE],
+    marker: PhantomData<S>,
+}
+
+impl<B: UsbBus, S: Sensor> HidSensor<'_, B, S> {
+    /// Send a data update if the reporting state allows it
+    ///
+    /// With [`ReportingState::ThresholdEvents`] data is only sent once a field has changed by
+    /// at least the change sensitivity since the last data sent.
+    pub fn write_data(&mut self, data: &S) -> Result<(), UsbHidError> {
+        let values = data.values();
+        let send = match self.reporting_state {
+            ReportingState::NoEvents | ReportingState::NoEventsWake => false,
+            ReportingState::AllEvents | ReportingState::AllEventsWake => true,
+            ReportingState::ThresholdEvents | ReportingState::ThresholdEventsWake => {
+                match self.last_values {
+                    None => true,
+                    Some(last) => last
+                        .iter()
+                        .zip(values.iter())
+                        .take(self.field_count)
+                        .any(|(a, b)| a.abs_diff(*b) >= self.sensitivity),
+                }
+            }
+        };
+        if !send {
+            return Ok(());
+        }
+
+        self.write_input(SensorEvent::DataUpdated, values)?;
+        self.last_values = Some(values);
+        Ok(())
+    }
+
+    /// Set the sensor state, the host is notified with the last data sent
+    pub fn set_state(&mut self, state: SensorState) -> Result<(), UsbHidError> {
+        if state == self.sensor_state {
+            return Ok(());
+        }
+        self.sensor_state = state;
+        self.write_input(
+            SensorEvent::StateChanged,
+            self.last_values.unwrap_or_default(),
+        )
+    }
+
+    #[must_use]
+    pub fn reporting_state(&self) -> ReportingState {
+        self.reporting_state
+    }
+
+    #[must_use]
+    pub fn power_state(&self) -> PowerState {
+        self.power_state
+    }
+
+    #[must_use]
+    pub fn sensor_state(&self) -> SensorState {
+        self.sensor_state
+    }
+
+    /// Interval between data updates requested by the host, 0 for the sensor default
+    #[must_use]
+    pub fn report_interval_ms(&self) -> u32 {
+        self.report_interval_ms
+    }
+
+    /// Smallest change of a data field reported with [`ReportingState::ThresholdEvents`]
+    #[must_use]
+    pub fn sensitivity(&self) -> u16 {
+        self.sensitivity
+    }
+
+    /// Returns `true` once after the host has changed a property
+    pub fn properties_changed(&mut self) -> bool {
+        core::mem::take(&mut self.properties_changed)
+    }
+
+    fn write_input(
+        &mut self,
+        event: SensorEvent,
+        values: [i16; MAX_SENSOR_FIELDS],
+    ) -> Result<(), UsbHidError> {
+        let mut report = [0u8; INPUT_REPORT_SIZE];
+        report[0] = self.sensor_state.into();
+        report[1] = event.into();
+        for (i, value) in values.iter().take(self.field_count).enumerate() {
+            report[2 + i * 2..4 + i * 2].copy_from_slice(&value.to_le_bytes());
+        }
+        self.interface
+            .write_report(&report[..2 + 2 * self.field_count])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, B: UsbBus, S: Sensor> DeviceClass<'a> for HidSensor<'a, B, S> {
+    type I = Interface<'a, B, InBytes16, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.reporting_state = ReportingState::default();
+        self.power_state = PowerState::default();
+        self.report_interval_ms = 0;
+        self.sensitivity = 0;
+        self.last_values = None;
+        self.properties_changed = false;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        if report_id != 0 {
+            return None;
+        }
+        let [i0, i1, i2, i3] = self.report_interval_ms.to_le_bytes();
+        let [s0, s1] = self.sensitivity.to_le_bytes();
+        self.feature_report = [
+            self.reporting_state.into(),
+            self.power_state.into(),
+            self.sensor_state.into(),
+            i0,
+            i1,
+            i2,
+            i3,
+            s0,
+            s1,
+        ];
+        Some(FeatureReport::DynamicReport(&self.feature_report))
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        if report_id != 0 {
+            return Err(UsbError::Unsupported);
+        }
+        let [reporting_state, power_state, _sensor_state, i0, i1, i2, i3, s0, s1] = *data else {
+            return Err(UsbError::ParseError);
+        };
+        self.reporting_state =
+            ReportingState::try_from(reporting_state).map_err(|_| UsbError::ParseError)?;
+        self.power_state = PowerState::try_from(power_state).map_err(|_| UsbError::ParseError)?;
+        self.report_interval_ms = u32::from_le_bytes([i0, i1, i2, i3]);
+        self.sensitivity = u16::from_le_bytes([s0, s1]);
+        self.properties_changed = true;
+        Ok(())
+    }
+}
+
+pub struct HidSensorConfig<'a, S: Sensor> {
+    interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>,
+    field_count: usize,
+    marker: PhantomData<S>,
+}
+
+impl<S: Sensor> Default for HidSensorConfig<'_, S> {
+    fn default() -> Self {
+        Self::with_interface(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                S::descriptor().as_bytes()
+            ))
+            .description("Sensor")
+            .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a, S: Sensor> HidSensorConfig<'a, S> {
+    /// Create a configuration from an interface using the descriptor of `S`
+    #[must_use]
+    pub fn with_interface(
+        interface: InterfaceConfig<'a, InBytes16, OutNone, ReportSingle>,
+    ) -> Self {
+        Self {
+            interface,
+            field_count: S::descriptor().field_count,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, B: UsbBus + 'a, S: Sensor + 'a> UsbAllocatable<'a, B> for HidSensorConfig<'a, S> {
+    type Allocated = HidSensor<'a, B, S>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            field_count: self.field_count,
+            reporting_state: ReportingState::default(),
+            power_state: PowerState::default(),
+            sensor_state: SensorState::default(),
+            report_interval_ms: 0,
+            sensitivity: 0,
+            last_values: None,
+            properties_changed: false,
+            feature_report: [0; FEATURE_REPORT_SIZE],
+            marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn descriptor_data_fields() {
+        let bytes = TEMPERATURE_DESCRIPTOR.as_bytes();
+        assert!(bytes.len() <= MAX_DESCRIPTOR_LEN);
+        assert_eq!(bytes[..6], [0x05, 0x20, 0x09, 0x33, 0xA1, 0x01]);
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x0A, 0x34, 0x04,
+            0x16, 0xF0, 0xD8,
+            0x26, 0x20, 0x4E,
+            0x75, 0x10,
+            0x55, 0x0E,
+            0x81, 0x02,
+            0x55, 0x00,
+            0xC0,
+        ];
+        assert!(bytes.ends_with(expected));
+        // Change Sensitivity Absolute of the temperature
+        assert!(bytes.windows(3).any(|w| w == [0x0A, 0x34, 0x14]));
+
+        let accelerometer = ACCELEROMETER_3D_DESCRIPTOR.as_bytes();
+        assert_eq!(ACCELEROMETER_3D_DESCRIPTOR.field_count, 3);
+        assert!(accelerometer.len() <= MAX_DESCRIPTOR_LEN);
+        assert_eq!(accelerometer.last(), Some(&0xC0));
+    }
+
+    #[test]
+    fn temperature_sensor_threshold_events() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(HidSensorConfig::<Temperature>::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Get the property feature report
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0, 9),
+            [1, 1, 1, 0, 0, 0, 0, 0, 0]
+        );
+
+        // Threshold events, 1s interval, 0.5 °C sensitivity
+        manager.host_set_feature(
+            &mut usb_dev,
+            &mut hid,
+            0,
+            &[2, 1, 1, 0xE8, 0x03, 0, 0, 50, 0],
+        );
+
+        let sensor = hid.device();
+        assert!(sensor.properties_changed());
+        assert_eq!(sensor.reporting_state(), ReportingState::ThresholdEvents);
+        assert_eq!(sensor.report_interval_ms(), 1000);
+        assert_eq!(sensor.sensitivity(), 50);
+
+        // First reading is always sent
+        sensor
+            .write_data(&Temperature { temperature: 2150 })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x01, 0x03, 0x66, 0x08]);
+
+        // Below the sensitivity
+        sensor
+            .write_data(&Temperature { temperature: 2180 })
+            .unwrap();
+        sensor
+            .write_data(&Temperature { temperature: 2200 })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0x01, 0x03, 0x98, 0x08]);
+
+        sensor.set_state(SensorState::Error).unwrap();
+        assert_eq!(manager.host_read_in(), [0x06, 0x01, 0x98, 0x08]);
+    }
+}
@@ -0,0 +1,195 @@
+//!HID system control (power, sleep and wake)
+
+use fugit::ExtU32;
+use packed_struct::prelude::*;
+#[allow(clippy::wildcard_imports)]
+use usb_device::class_prelude::*;
+
+use crate::page::Desktop;
+use crate::usb_class::prelude::*;
+
+///System control report descriptor - A single `u8` Generic Desktop usage code as an array (1 byte)
+///
+/// Supports the usages `SystemPowerDown` (0x81) through `SystemWarmRestart` (0x8F), any other
+/// value is treated as no control pressed
+#[rustfmt::skip]
+pub const SYSTEM_CONTROL_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop),
+    0x09, 0x80, // Usage (System Control),
+    0xA1, 0x01, // Collection (Application),
+    0x19, 0x81, //     Usage Minimum (System Power Down)
+    0x29, 0x8F, //     Usage Maximum (System Warm Restart)
+    0x16, 0x81, 0x00, //     Logical Minimum (0x81)
+    0x26, 0x8F, 0x00, //     Logical Maximum (0x8F)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x01, //     Report Count (1)
+    0x81, 0x00, //     Input (Data, Array, Absolute)
+    0xC0, // End Collection
+];
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "1")]
+pub struct SystemControlReport {
+    #[packed_field(bytes = "0", ty = "enum")]
+    pub usage: Desktop,
+}
+
+impl SystemControlReport {
+    #[must_use]
+    pub fn new(usage: Desktop) -> Self {
+        Self { usage }
+    }
+}
+
+/// Interface implementing a HID system control, e.g. dedicated power, sleep and wake keys
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, it repeats the pressed control
+/// at the host's idle rate and sends the release of [`SystemControl::tap()`].
+pub struct SystemControl<'a, B: UsbBus> {
+    interface: ManagedIdleInterface<'a, B, SystemControlReport, InBytes8, OutNone>,
+}
+
+impl<B: UsbBus> SystemControl<'_, B> {
+    /// Set the currently pressed control, [`Desktop::Undefined`] releases it
+    pub fn write_report(&mut self, usage: Desktop) -> Result<(), UsbHidError> {
+        self.interface
+            .write_report(&SystemControlReport::new(usage))
+    }
+
+    /// Press and release a control
+    ///
+    /// The release is sent from a later call to [`UsbHidClass::tick()`], so the host never
+    /// sees a stuck key. Returns [`UsbHidError::Duplicate`] without sending anything if the
+    /// control is already held.
+    pub fn tap(&mut self, usage: Desktop) -> Result<(), UsbHidError> {
+        self.interface
+            .write_momentary_report(&SystemControlReport::new(usage))
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for SystemControl<'a, B> {
+    type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        self.interface.interface()
+    }
+
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
+    }
+}
+
+pub struct SystemControlConfig<'a> {
+    interface: ManagedIdleInterfaceConfig<'a, SystemControlReport, InBytes8, OutNone>,
+}
+
+impl<'a> SystemControlConfig<'a> {
+    #[must_use]
+    pub fn new(
+        interface: ManagedIdleInterfaceConfig<'a, SystemControlReport, InBytes8, OutNone>,
+    ) -> Self {
+        Self { interface }
+    }
+}
+
+impl Default for SystemControlConfig<'_> {
+    fn default() -> Self {
+        Self::new(ManagedIdleInterfaceConfig::new(
+            unwrap!(unwrap!(
+                unwrap!(InterfaceBuilder::new(SYSTEM_CONTROL_REPORT_DESCRIPTOR))
+                    .description("System Control")
+                    .idle_default(500.millis())
+            )
+            .in_endpoint(10.millis()))
+            .without_out_endpoint()
+            .build(),
+        ))
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for SystemControlConfig<'a> {
+    type Allocated = SystemControl<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: self.interface.allocate(usb_alloc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn system_control_report_packs_usage() {
+        let report = SystemControlReport::new(Desktop::SystemSleep);
+        assert_eq!(report.pack().unwrap(), [0x82]);
+        assert_eq!(SystemControlReport::default().pack().unwrap(), [0x00]);
+    }
+
+    #[test]
+    fn momentary_report_released_on_tick() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(SystemControlConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device().tap(Desktop::SystemSleep).unwrap();
+        assert!(matches!(
+            hid.device().tap(Desktop::SystemSleep),
+            Err(UsbHidError::WouldBlock)
+        ));
+        assert_eq!(manager.host_read_in(), [0x82], "Expected press");
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x00], "Expected release");
+
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+    }
+
+    #[test]
+    fn momentary_report_retried_when_endpoint_busy() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(SystemControlConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device().write_report(Desktop::SystemPowerDown).unwrap();
+        hid.device().tap(Desktop::SystemSleep).unwrap();
+        assert_eq!(manager.host_read_in(), [0x81], "Expected held report");
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0x82], "Expected press");
+
+        hid.tick().unwrap();
+        assert_eq!(
+            manager.host_read_in(),
+            [0x81],
+            "Expected held report restored"
+        );
+
+        assert!(matches!(
+            hid.device().tap(Desktop::SystemPowerDown),
+            Err(UsbHidError::Duplicate)
+        ));
+        hid.tick().unwrap();
+        assert!(manager.host_read_in().is_empty(), "Unexpected report");
+    }
+}
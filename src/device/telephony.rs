@@ -0,0 +1,292 @@
+//!HID telephony headsets
+
+use fugit::ExtU32;
+use packed_struct::prelude::*;
+#[allow(clippy::wildcard_imports)]
+use usb_device::class_prelude::*;
+use usb_device::UsbError;
+
+use crate::usb_class::prelude::*;
+
+#[allow(clippy::doc_markdown)]
+///Telephony headset report descriptor
+///
+/// Single bit packed `u8` input report
+/// * Bit 0 - Hook Switch (absolute, set while off-hook)
+/// * Bit 1 - Phone Mute (absolute, toggles mute when pressed)
+/// * Bit 2 - Flash (absolute)
+/// * Bit 3 - Redial (absolute)
+/// * Bit 4-7 - Reserved
+///
+/// Single bit packed `u8` output report
+/// * Bit 0 - Off-Hook LED
+/// * Bit 1 - Mute LED
+/// * Bit 2 - Ring LED
+/// * Bit 3-7 - Reserved
+#[rustfmt::skip]
+pub const HEADSET_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0B, // Usage Page (Telephony),
+    0x09, 0x05, // Usage (Headset),
+    0xA1, 0x01, // Collection (Application),
+    0x15, 0x00, //     Logical Minimum (0),
+    0x25, 0x01, //     Logical Maximum (1),
+    0x75, 0x01, //     Report Size (1),
+    0x95, 0x01, //     Report Count (1),
+    0x09, 0x20, //     Usage (Hook Switch),
+    0x81, 0x22, //     Input (Data, Variable, Absolute, No Preferred State),
+    0x95, 0x03, //     Report Count (3),
+    0x09, 0x2F, //     Usage (Phone Mute),
+    0x09, 0x21, //     Usage (Flash),
+    0x09, 0x24, //     Usage (Redial),
+    0x81, 0x02, //     Input (Data, Variable, Absolute),
+    0x95, 0x04, //     Report Count (4),
+    0x81, 0x01, //     Input (Constant),
+    0x05, 0x08, //     Usage Page (LEDs),
+    0x95, 0x03, //     Report Count (3),
+    0x09, 0x17, //     Usage (Off-Hook),
+    0x09, 0x09, //     Usage (Mute),
+    0x09, 0x18, //     Usage (Ring),
+    0x91, 0x22, //     Output (Data, Variable, Absolute, No Preferred State),
+    0x95, 0x05, //     Report Count (5),
+    0x91, 0x01, //     Output (Constant),
+    0xC0,       // End Collection
+];
+
+/// Report indicating the currently pressed headset controls
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct HeadsetReport {
+    #[packed_field(bits = "0")]
+    pub hook_switch: bool,
+    #[packed_field(bits = "1")]
+    pub phone_mute: bool,
+    #[packed_field(bits = "2")]
+    pub flash: bool,
+    #[packed_field(bits = "3")]
+    pub redial: bool,
+}
+
+/// Report indicating the headset LEDs lit by the host
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "lsb0", size_bytes = "1")]
+pub struct HeadsetLedsReport {
+    #[packed_field(bits = "0")]
+    pub off_hook: bool,
+    #[packed_field(bits = "1")]
+    pub mute: bool,
+    #[packed_field(bits = "2")]
+    pub ring: bool,
+}
+
+/// Momentary headset control, acted on by the host when it changes to pressed
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeadsetButton {
+    PhoneMute,
+    Flash,
+    Redial,
+}
+
+/// Interface implementing a HID telephony headset
+///
+/// The host reports call and mute state using the LED output report, read with [`Headset::read_report`]
+/// to keep the device in sync with the host.
+///
+/// Phone Mute, Flash and Redial are momentary controls: the host acts when they change to
+/// pressed, so each must be released before it can be pressed again. [`Headset::tap`] sends the
+/// press and release.
+///
+/// **Note:** [`UsbHidClass::tick()`] must be called every 1ms, it repeats the hook switch and
+/// held controls at the host's idle rate and sends the release of [`Headset::tap()`].
+pub struct Headset<'a, B: UsbBus> {
+    interface: ManagedIdleInterface<'a, B, HeadsetReport, InBytes8, OutBytes8>,
+}
+
+impl<B> Headset<'_, B>
+where
+    B: UsbBus,
+{
+    pub fn write_report(&mut self, report: &HeadsetReport) -> Result<(), UsbHidError> {
+        self.interface.write_report(report)
+    }
+
+    /// Press and release a control, any held controls stay pressed
+    ///
+    /// The release is sent from a later call to [`UsbHidClass::tick()`]. Returns
+    /// [`UsbHidError::Duplicate`] without sending anything if the control is already held.
+    pub fn tap(&mut self, button: HeadsetButton) -> Result<(), UsbHidError> {
+        let mut report = self.interface.last_report().unwrap_or_default();
+        match button {
+            HeadsetButton::PhoneMute => report.phone_mute = true,
+            HeadsetButton::Flash => report.flash = true,
+            HeadsetButton::Redial => report.redial = true,
+        }
+        self.interface.write_momentary_report(&report)
+    }
+
+    pub fn read_report(&mut self) -> usb_device::Result<HeadsetLedsReport> {
+        let data = &mut [0];
+        match self.interface.read_report(data) {
+            Err(e) => Err(e),
+            Ok(_) => match HeadsetLedsReport::unpack(data) {
+                Ok(r) => Ok(r),
+                Err(_) => Err(UsbError::ParseError),
+            },
+        }
+    }
+}
+
+impl<'a, B> DeviceClass<'a> for Headset<'a, B>
+where
+    B: UsbBus,
+{
+    type I = Interface<'a, B, InBytes8, OutBytes8, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        self.interface.interface()
+    }
+
+    fn reset(&mut self) {
+        self.interface.reset();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.interface.tick()
+    }
+}
+
+pub struct HeadsetConfig<'a> {
+    interface: ManagedIdleInterfaceConfig<'a, HeadsetReport, InBytes8, OutBytes8>,
+}
+
+impl Default for HeadsetConfig<'_> {
+    fn default() -> Self {
+        Self::new(ManagedIdleInterfaceConfig::new(
+            unwrap!(unwrap!(
+                unwrap!(unwrap!(InterfaceBuilder::new(HEADSET_REPORT_DESCRIPTOR))
+                    .description("Headset")
+                    .idle_default(500.millis()))
+                .in_endpoint(10.millis())
+            )
+            .with_out_endpoint(100.millis()))
+            .build(),
+        ))
+    }
+}
+
+impl<'a> HeadsetConfig<'a> {
+    #[must_use]
+    pub fn new(
+        interface: ManagedIdleInterfaceConfig<'a, HeadsetReport, InBytes8, OutBytes8>,
+    ) -> Self {
+        Self { interface }
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for HeadsetConfig<'a> {
+    type Allocated = Headset<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: self.interface.allocate(usb_alloc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    #[test]
+    fn headset_report_packing() {
+        let report = HeadsetReport {
+            hook_switch: true,
+            phone_mute: false,
+            flash: false,
+            redial: true,
+        };
+        assert_eq!(report.pack().unwrap(), [0b1001]);
+    }
+
+    #[test]
+    fn leds_off_hook_muted() {
+        assert_eq!(
+            HeadsetLedsReport::unpack(&[0b011]),
+            Ok(HeadsetLedsReport {
+                off_hook: true,
+                mute: true,
+                ring: false,
+            })
+        );
+
+        assert_eq!(
+            HeadsetLedsReport::unpack(&[0b100]),
+            Ok(HeadsetLedsReport {
+                off_hook: false,
+                mute: false,
+                ring: true,
+            })
+        );
+    }
+
+    #[test]
+    fn descriptor_controls_absolute() {
+        let controls = [
+            0x09, 0x2F, 0x09, 0x21, 0x09, 0x24, // Phone Mute, Flash, Redial
+            0x81, 0x02, // Input (Data, Variable, Absolute)
+        ];
+        assert!(HEADSET_REPORT_DESCRIPTOR
+            .windows(controls.len())
+            .any(|w| w == controls));
+    }
+
+    #[test]
+    fn tap_releases_momentary_controls() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(HeadsetConfig::default())
+            .build(usb_alloc);
+
+        let _usb_dev = test_usb_device(usb_alloc);
+
+        hid.device()
+            .write_report(&HeadsetReport {
+                hook_switch: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0b0001]);
+
+        hid.device().tap(HeadsetButton::PhoneMute).unwrap();
+        assert_eq!(manager.host_read_in(), [0b0011]);
+
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0b0001], "Expected mute released");
+
+        // Held controls stay pressed
+        hid.device()
+            .write_report(&HeadsetReport {
+                hook_switch: true,
+                flash: true,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0b0101]);
+        hid.device().tap(HeadsetButton::Redial).unwrap();
+        assert_eq!(manager.host_read_in(), [0b1101]);
+        hid.tick().unwrap();
+        assert_eq!(manager.host_read_in(), [0b0101], "Expected redial released");
+
+        assert!(matches!(
+            hid.device().tap(HeadsetButton::Flash),
+            Err(UsbHidError::Duplicate)
+        ));
+    }
+}
@@ -0,0 +1,818 @@
+//!HID precision touchpad
+use crate::usb_class::prelude::*;
+use core::default::Default;
+use fugit::ExtU32;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use packed_struct::prelude::*;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Number of contacts reported by [`Touchpad`]
+pub const TOUCHPAD_MAX_CONTACTS: usize = 5;
+/// Report ID of the Device Certification Status feature report
+pub const CERTIFICATION_STATUS_REPORT_ID: u8 = 0xC5;
+
+const TOUCHPAD_REPORT_ID: u8 = 0x01;
+const CAPABILITIES_REPORT_ID: u8 = 0x02;
+const INPUT_MODE_REPORT_ID: u8 = 0x03;
+const SELECTIVE_REPORTING_REPORT_ID: u8 = 0x04;
+const MOUSE_REPORT_ID: u8 = 0x05;
+
+/// Device Certification Status feature report for a device that has not been certified
+///
+/// Windows will use an uncertified touchpad as a Precision Touchpad but reports it as
+/// uncertified. Certified devices should supply the blob issued by Microsoft with
+/// [`TouchpadConfig::certification_status`].
+pub const UNCERTIFIED_STATUS: [u8; 257] = {
+    let mut status = [0; 257];
+    status[0] = CERTIFICATION_STATUS_REPORT_ID;
+    status
+};
+
+/// Windows Precision Touchpad report descriptor
+///
+/// Includes the touchpad, configuration and mouse top level collections.
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/touchpad-required-hid-top-level-collections>
+#[rustfmt::skip]
+pub const TOUCHPAD_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0D,           // Usage Page (Digitizer)
+    0x09, 0x05,           // Usage (Touch Pad)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x01,           //   Report ID (1)
+    // contact 1
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xE8, 0x03,     //     Physical Maximum (1000)
+    0x09, 0x30,           //     Usage (X)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x46, 0x8A, 0x02,     //     Physical Maximum (650)
+    0x09, 0x31,           //     Usage (Y)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 2
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xE8, 0x03,     //     Physical Maximum (1000)
+    0x09, 0x30,           //     Usage (X)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x46, 0x8A, 0x02,     //     Physical Maximum (650)
+    0x09, 0x31,           //     Usage (Y)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 3
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xE8, 0x03,     //     Physical Maximum (1000)
+    0x09, 0x30,           //     Usage (X)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x46, 0x8A, 0x02,     //     Physical Maximum (650)
+    0x09, 0x31,           //     Usage (Y)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 4
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xE8, 0x03,     //     Physical Maximum (1000)
+    0x09, 0x30,           //     Usage (X)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x46, 0x8A, 0x02,     //     Physical Maximum (650)
+    0x09, 0x31,           //     Usage (Y)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 5
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xE8, 0x03,     //     Physical Maximum (1000)
+    0x09, 0x30,           //     Usage (X)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x46, 0x8A, 0x02,     //     Physical Maximum (650)
+    0x09, 0x31,           //     Usage (Y)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    0xA4,                 //   Push
+    0x55, 0x0C,           //   Unit Exponent (-4)
+    0x66, 0x01, 0x10,     //   Unit (Seconds, SI Linear)
+    0x47, 0xFF, 0xFF, 0x00, 0x00, // Physical Maximum (65535)
+    0x27, 0xFF, 0xFF, 0x00, 0x00, // Logical Maximum (65535)
+    0x75, 0x10,           //   Report Size (16)
+    0x95, 0x01,           //   Report Count (1)
+    0x09, 0x56,           //   Usage (Scan Time)
+    0x81, 0x02,           //   Input (Data, Variable, Absolute)
+    0xB4,                 //   Pop
+    0x09, 0x54,           //   Usage (Contact Count)
+    0x25, 0x7F,           //   Logical Maximum (127)
+    0x75, 0x08,           //   Report Size (8)
+    0x95, 0x01,           //   Report Count (1)
+    0x81, 0x02,           //   Input (Data, Variable, Absolute)
+    0x05, 0x09,           //   Usage Page (Buttons)
+    0x09, 0x01,           //   Usage (Button 1)
+    0x25, 0x01,           //   Logical Maximum (1)
+    0x75, 0x01,           //   Report Size (1)
+    0x95, 0x01,           //   Report Count (1)
+    0x81, 0x02,           //   Input (Data, Variable, Absolute)
+    0x95, 0x07,           //   Report Count (7)
+    0x81, 0x03,           //   Input (Constant, Variable, Absolute)
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x85, 0x02,           //   Report ID (2)
+    0x09, 0x55,           //   Usage (Contact Count Maximum)
+    0x09, 0x59,           //   Usage (Button Type)
+    0x25, 0x0F,           //   Logical Maximum (15)
+    0x75, 0x04,           //   Report Size (4)
+    0x95, 0x02,           //   Report Count (2)
+    0xB1, 0x02,           //   Feature (Data, Variable, Absolute)
+    0x06, 0x00, 0xFF,     //   Usage Page (Vendor Defined 0xFF00)
+    0x85, 0xC5,           //   Report ID (0xC5)
+    0x09, 0xC5,           //   Usage (Device Certification Status)
+    0x26, 0xFF, 0x00,     //   Logical Maximum (255)
+    0x75, 0x08,           //   Report Size (8)
+    0x96, 0x00, 0x01,     //   Report Count (256)
+    0xB1, 0x02,           //   Feature (Data, Variable, Absolute)
+    0xC0,                 // End Collection
+
+    0x05, 0x0D,           // Usage Page (Digitizer)
+    0x09, 0x0E,           // Usage (Device Configuration)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x03,           //   Report ID (3)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x52,           //     Usage (Input Mode)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x0A,           //     Logical Maximum (10)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0xB1, 0x02,           //     Feature (Data, Variable, Absolute)
+    0xC0,                 //   End Collection
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x00,           //   Collection (Physical)
+    0x85, 0x04,           //     Report ID (4)
+    0x09, 0x57,           //     Usage (Surface Switch)
+    0x09, 0x58,           //     Usage (Button Switch)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0xB1, 0x02,           //     Feature (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0xB1, 0x03,           //     Feature (Constant, Variable, Absolute)
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+
+    0x05, 0x01,           // Usage Page (Generic Desktop)
+    0x09, 0x02,           // Usage (Mouse)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x05,           //   Report ID (5)
+    0x09, 0x01,           //   Usage (Pointer)
+    0xA1, 0x00,           //   Collection (Physical)
+    0x05, 0x09,           //     Usage Page (Buttons)
+    0x19, 0x01,           //     Usage Minimum (1)
+    0x29, 0x02,           //     Usage Maximum (2)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x15, 0x81,           //     Logical Minimum (-127)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x06,           //     Input (Data, Variable, Relative)
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+];
+
+/// A single touchpad contact
+///
+/// `x` and `y` are in the range 0-4095 over a 100mm x 65mm surface
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "6")]
+pub struct TouchpadContact {
+    #[packed_field(bits = "7")]
+    pub confidence: bool,
+    #[packed_field(bits = "6")]
+    pub tip_switch: bool,
+    #[packed_field(bytes = "1")]
+    pub contact_id: u8,
+    #[packed_field(bytes = "2..=3")]
+    pub x: u16,
+    #[packed_field(bytes = "4..=5")]
+    pub y: u16,
+}
+
+/// Precision touchpad report, excluding the leading report ID
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "34")]
+pub struct TouchpadReport {
+    #[packed_field(bytes = "0..=29", element_size_bytes = "6")]
+    pub contacts: [TouchpadContact; 5],
+    /// Scan time in units of 100us
+    #[packed_field(bytes = "30..=31")]
+    pub scan_time: u16,
+    #[packed_field(bytes = "32")]
+    pub contact_count: u8,
+    #[packed_field(bits = "271")]
+    pub button: bool,
+}
+
+impl TouchpadReport {
+    pub fn new<C: IntoIterator<Item = TouchpadContact>>(
+        contacts: C,
+        button: bool,
+        scan_time: u16,
+    ) -> Self {
+        let mut report = Self {
+            scan_time,
+            button,
+            ..Default::default()
+        };
+
+        for (i, c) in contacts.into_iter().take(TOUCHPAD_MAX_CONTACTS).enumerate() {
+            report.contacts[i] = c;
+            report.contact_count += 1;
+        }
+
+        report
+    }
+}
+
+/// Relative mouse report used as a fallback when the host has not enabled touchpad input
+///
+/// Boot protocol hosts receive this report without a report ID
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", size_bytes = "3")]
+pub struct TouchpadMouseReport {
+    #[packed_field]
+    pub buttons: u8,
+    #[packed_field]
+    pub x: i8,
+    #[packed_field]
+    pub y: i8,
+}
+
+impl TouchpadMouseReport {
+    fn new(
+        last: Option<&TouchpadContact>,
+        current: Option<&TouchpadContact>,
+        button: bool,
+    ) -> Self {
+        let (x, y) = match (last, current) {
+            (Some(l), Some(c)) if l.contact_id == c.contact_id => {
+                (delta(l.x, c.x), delta(l.y, c.y))
+            }
+            _ => (0, 0),
+        };
+
+        Self {
+            buttons: u8::from(button),
+            x,
+            y,
+        }
+    }
+}
+
+fn delta(from: u16, to: u16) -> i8 {
+    //clamp to the report's logical range
+    i8::try_from((i32::from(to) - i32::from(from)).clamp(-127, 127)).unwrap_or_default()
+}
+
+/// Input mode selected by the host through the Input Mode feature report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum InputMode {
+    #[default]
+    Mouse = 0x00,
+    Touchpad = 0x03,
+}
+
+/// Physical button arrangement reported through the Button Type feature report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum ButtonType {
+    /// The whole surface can be pressed to click
+    #[default]
+    ClickPad = 0x00,
+    /// Clicks are detected from contact pressure
+    PressurePad = 0x01,
+    /// The touchpad has separate physical buttons
+    NonClickable = 0x02,
+}
+
+/// Windows Precision Touchpad
+///
+/// Devices start in [`InputMode::Mouse`] and report the primary contact as a relative mouse until
+/// the host selects [`InputMode::Touchpad`]. While the host has selected [`HidProtocol::Boot`]
+/// boot mouse reports are sent.
+pub struct Touchpad<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes64, OutNone, ReportSingle>,
+    button_type: ButtonType,
+    certification_status: &'static [u8; 257],
+    input_mode: InputMode,
+    surface_reporting: bool,
+    button_reporting: bool,
+    last_contact: Option<TouchpadContact>,
+    feature_report: [u8; 2],
+}
+
+impl<B: UsbBus> Touchpad<'_, B> {
+    pub fn write_report<C: IntoIterator<Item = TouchpadContact>>(
+        &mut self,
+        contacts: C,
+        button: bool,
+        scan_time: u16,
+    ) -> Result<(), UsbHidError> {
+        let mut report = TouchpadReport::new(contacts, button, scan_time);
+
+        if self.interface.protocol() == HidProtocol::Boot {
+            let data = self.mouse_report(&report).pack().map_err(|_| {
+                error!("Error packing TouchpadMouseReport");
+                UsbHidError::SerializationError
+            })?;
+            return self.write_data(&data);
+        }
+
+        match self.input_mode {
+            InputMode::Mouse => {
+                let data = self.mouse_report(&report).pack().map_err(|_| {
+                    error!("Error packing TouchpadMouseReport");
+                    UsbHidError::SerializationError
+                })?;
+                self.write_with_id(MOUSE_REPORT_ID, &data)
+            }
+            InputMode::Touchpad => {
+                if !self.surface_reporting {
+                    report.contacts = Default::default();
+                    report.contact_count = 0;
+                }
+                if !self.button_reporting {
+                    report.button = false;
+                }
+                let data = report.pack().map_err(|_| {
+                    error!("Error packing TouchpadReport");
+                    UsbHidError::SerializationError
+                })?;
+                self.write_with_id(TOUCHPAD_REPORT_ID, &data)
+            }
+        }
+    }
+
+    /// The input mode most recently selected by the host
+    #[must_use]
+    pub fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    /// `false` if the host has requested that surface contacts are not reported
+    #[must_use]
+    pub fn surface_reporting(&self) -> bool {
+        self.surface_reporting
+    }
+
+    /// `false` if the host has requested that button presses are not reported
+    #[must_use]
+    pub fn button_reporting(&self) -> bool {
+        self.button_reporting
+    }
+
+    fn mouse_report(&mut self, report: &TouchpadReport) -> TouchpadMouseReport {
+        let contact = report.contacts[..usize::from(report.contact_count)]
+            .iter()
+            .find(|c| c.tip_switch)
+            .copied();
+        let mouse_report =
+            TouchpadMouseReport::new(self.last_contact.as_ref(), contact.as_ref(), report.button);
+        self.last_contact = contact;
+        mouse_report
+    }
+
+    fn write_with_id(&mut self, report_id: u8, data: &[u8]) -> Result<(), UsbHidError> {
+        let mut buffer = [0u8; 64];
+        buffer[0] = report_id;
+        buffer[1..=data.len()].copy_from_slice(data);
+        self.write_data(&buffer[..=data.len()])
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), UsbHidError> {
+        self.interface
+            .write_report(data)
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for Touchpad<'a, B> {
+    type I = Interface<'a, B, InBytes64, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.input_mode = InputMode::default();
+        self.surface_reporting = true;
+        self.button_reporting = true;
+        self.last_contact = None;
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        Ok(())
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        match report_id {
+            CAPABILITIES_REPORT_ID => {
+                #[allow(clippy::cast_possible_truncation)]
+                let max_contacts = TOUCHPAD_MAX_CONTACTS as u8;
+                self.feature_report = [report_id, (u8::from(self.button_type) << 4) | max_contacts];
+                Some(FeatureReport::DynamicReport(&self.feature_report))
+            }
+            CERTIFICATION_STATUS_REPORT_ID => {
+                Some(FeatureReport::StaticReport(self.certification_status))
+            }
+            INPUT_MODE_REPORT_ID => {
+                self.feature_report = [report_id, self.input_mode.into()];
+                Some(FeatureReport::DynamicReport(&self.feature_report))
+            }
+            SELECTIVE_REPORTING_REPORT_ID => {
+                self.feature_report = [
+                    report_id,
+                    u8::from(self.surface_reporting) | (u8::from(self.button_reporting) << 1),
+                ];
+                Some(FeatureReport::DynamicReport(&self.feature_report))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        match (report_id, data) {
+            (INPUT_MODE_REPORT_ID, [_, mode, ..]) => {
+                self.input_mode = InputMode::try_from(*mode).map_err(|_| UsbError::ParseError)?;
+                self.last_contact = None;
+                info!("Set touchpad input mode to {:?}", self.input_mode);
+                Ok(())
+            }
+            (SELECTIVE_REPORTING_REPORT_ID, [_, flags, ..]) => {
+                self.surface_reporting = flags & 0x01 != 0;
+                self.button_reporting = flags & 0x02 != 0;
+                info!(
+                    "Set touchpad selective reporting, surface: {}, button: {}",
+                    self.surface_reporting, self.button_reporting
+                );
+                Ok(())
+            }
+            _ => Err(UsbError::Unsupported),
+        }
+    }
+}
+
+pub struct TouchpadConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes64, OutNone, ReportSingle>,
+    button_type: ButtonType,
+    certification_status: &'static [u8; 257],
+}
+
+impl Default for TouchpadConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                TOUCHPAD_REPORT_DESCRIPTOR
+            ))
+            .boot_device(InterfaceProtocol::Mouse)
+            .description("Touchpad")
+            .in_endpoint(5.millis()))
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a> TouchpadConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes64, OutNone, ReportSingle>) -> Self {
+        Self {
+            interface,
+            button_type: ButtonType::default(),
+            certification_status: &UNCERTIFIED_STATUS,
+        }
+    }
+
+    #[must_use]
+    pub fn button_type(mut self, button_type: ButtonType) -> Self {
+        self.button_type = button_type;
+        self
+    }
+
+    /// Set the Device Certification Status feature report
+    ///
+    /// The first byte must be [`CERTIFICATION_STATUS_REPORT_ID`] followed by the 256 byte
+    /// certification blob
+    #[must_use]
+    pub fn certification_status(mut self, certification_status: &'static [u8; 257]) -> Self {
+        self.certification_status = certification_status;
+        self
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for TouchpadConfig<'a> {
+    type Allocated = Touchpad<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            button_type: self.button_type,
+            certification_status: self.certification_status,
+            input_mode: InputMode::default(),
+            surface_reporting: true,
+            button_reporting: true,
+            last_contact: None,
+            feature_report: [0; 2],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+
+    use packed_struct::prelude::*;
+
+    use super::*;
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    fn contact(contact_id: u8, x: u16, y: u16) -> TouchpadContact {
+        TouchpadContact {
+            confidence: true,
+            tip_switch: true,
+            contact_id,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn touchpad_report_packing() {
+        let bytes = TouchpadReport::new([contact(2, 0x0102, 0x0304)], true, 0x1234)
+            .pack()
+            .unwrap();
+
+        assert_eq!(bytes[..6], [0x03, 0x02, 0x02, 0x01, 0x04, 0x03]);
+        assert_eq!(bytes[6..30], [0; 24]);
+        assert_eq!(bytes[30..], [0x34, 0x12, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn touchpad_report_max_contacts() {
+        let report = TouchpadReport::new((0..7).map(|i| contact(i, 0, 0)), false, 0);
+
+        assert_eq!(report.contact_count, 5);
+        assert_eq!(report.contacts[4].contact_id, 4);
+    }
+
+    #[test]
+    fn mouse_report_relative_motion() {
+        let report = TouchpadMouseReport::new(
+            Some(&contact(1, 100, 100)),
+            Some(&contact(1, 110, 1000)),
+            true,
+        );
+
+        assert_eq!(
+            report,
+            TouchpadMouseReport {
+                buttons: 1,
+                x: 10,
+                y: 127
+            }
+        );
+    }
+
+    #[test]
+    fn mouse_report_new_contact() {
+        let report = TouchpadMouseReport::new(
+            Some(&contact(1, 100, 100)),
+            Some(&contact(2, 110, 90)),
+            false,
+        );
+
+        assert_eq!(report, TouchpadMouseReport::default());
+    }
+
+    #[test]
+    fn touchpad_configuration_features() {
+        static CERTIFIED: [u8; 257] = {
+            let mut status = [0xA5; 257];
+            status[0] = CERTIFICATION_STATUS_REPORT_ID;
+            status
+        };
+
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(
+                TouchpadConfig::default()
+                    .button_type(ButtonType::PressurePad)
+                    .certification_status(&CERTIFIED),
+            )
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Button type in the high nibble, Contact Count Maximum in the low nibble
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, CAPABILITIES_REPORT_ID, 2),
+            [CAPABILITIES_REPORT_ID, 0x15]
+        );
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, CERTIFICATION_STATUS_REPORT_ID, 257),
+            CERTIFIED
+        );
+
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, INPUT_MODE_REPORT_ID, 2),
+            [INPUT_MODE_REPORT_ID, 0x00]
+        );
+        manager.host_set_feature(&mut usb_dev, &mut hid, INPUT_MODE_REPORT_ID, &[0x03, 0x03]);
+        assert_eq!(hid.device().input_mode(), InputMode::Touchpad);
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, INPUT_MODE_REPORT_ID, 2),
+            [INPUT_MODE_REPORT_ID, 0x03]
+        );
+
+        // Surface reporting off, button reporting on
+        manager.host_set_feature(
+            &mut usb_dev,
+            &mut hid,
+            SELECTIVE_REPORTING_REPORT_ID,
+            &[0x04, 0x02],
+        );
+        assert!(!hid.device().surface_reporting());
+        assert!(hid.device().button_reporting());
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, SELECTIVE_REPORTING_REPORT_ID, 2),
+            [SELECTIVE_REPORTING_REPORT_ID, 0x02]
+        );
+
+        hid.device()
+            .write_report([contact(1, 100, 200)], true, 10)
+            .unwrap();
+        let report = manager.host_read_in();
+        assert_eq!(report[0], TOUCHPAD_REPORT_ID);
+        assert_eq!(report[1..31], [0; 30], "Expected contacts to be dropped");
+        assert_eq!(report[31..], [10, 0, 0, 0x01]);
+    }
+
+    #[test]
+    fn touchpad_mouse_fallback() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(TouchpadConfig::default())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        // Mouse input mode until the host selects touchpad input
+        hid.device()
+            .write_report([contact(1, 100, 100)], false, 0)
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [MOUSE_REPORT_ID, 0, 0, 0]);
+        hid.device()
+            .write_report([contact(1, 90, 105)], true, 0)
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [MOUSE_REPORT_ID, 1, 0xF6, 5]);
+
+        // Boot protocol hosts get a boot mouse report without a report ID, whatever the input mode
+        manager.host_set_feature(&mut usb_dev, &mut hid, INPUT_MODE_REPORT_ID, &[0x03, 0x03]);
+        manager.host_set_protocol(&mut usb_dev, &mut hid, HidProtocol::Boot);
+        hid.device()
+            .write_report([contact(2, 0, 0)], false, 0)
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0, 0, 0]);
+        hid.device()
+            .write_report([contact(2, 3, 4)], false, 0)
+            .unwrap();
+        assert_eq!(manager.host_read_in(), [0, 3, 4]);
+    }
+}
@@ -0,0 +1,701 @@
+//!HID multi-touch touchscreen
+use crate::usb_class::prelude::*;
+use crate::usb_class::{BuilderResult, UsbHidBuilderError};
+use core::default::Default;
+use fugit::ExtU32;
+use heapless::Deque;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use packed_struct::prelude::*;
+use usb_device::bus::UsbBus;
+use usb_device::class_prelude::UsbBusAllocator;
+use usb_device::UsbError;
+
+/// Number of contacts carried by a single [`TouchscreenReport`]
+pub const CONTACTS_PER_REPORT: usize = 5;
+/// Largest contact count maximum supported by [`Touchscreen`]
+pub const MAX_CONTACTS: usize = 20;
+
+const MAX_REPORTS: usize = MAX_CONTACTS.div_ceil(CONTACTS_PER_REPORT);
+const TOUCH_REPORT_ID: u8 = 0x01;
+const CONTACT_COUNT_MAXIMUM_REPORT_ID: u8 = 0x02;
+const DEVICE_MODE_REPORT_ID: u8 = 0x03;
+const MOUSE_REPORT_ID: u8 = 0x04;
+
+/// Windows compatible multi-touch touchscreen report descriptor
+///
+/// Five contacts per report using hybrid reporting mode. Includes the Contact Count Maximum
+/// feature report, the Device Configuration collection and a mouse collection used while the host
+/// has the device in [`DeviceMode::Mouse`].
+///
+/// Reference: <https://learn.microsoft.com/en-us/windows-hardware/design/component-guidelines/touchscreen-required-hid-top-level-collections>
+#[rustfmt::skip]
+pub const TOUCHSCREEN_REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x0D,           // Usage Page (Digitizer)
+    0x09, 0x04,           // Usage (Touch Screen)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x01,           //   Report ID (1)
+    // contact 1
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xFF, 0x0F,     //     Physical Maximum (4095)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x09, 0x49,           //     Usage (Height)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 2
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xFF, 0x0F,     //     Physical Maximum (4095)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x09, 0x49,           //     Usage (Height)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 3
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xFF, 0x0F,     //     Physical Maximum (4095)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x09, 0x49,           //     Usage (Height)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 4
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xFF, 0x0F,     //     Physical Maximum (4095)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x09, 0x49,           //     Usage (Height)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    // contact 5
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x42,           //     Usage (Tip Switch)
+    0x09, 0x47,           //     Usage (Confidence)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x09, 0x51,           //     Usage (Contact Identifier)
+    0x25, 0x7F,           //     Logical Maximum (127)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x01,           //     Report Count (1)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xA4,                 //     Push
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x55, 0x0E,           //     Unit Exponent (-2)
+    0x65, 0x11,           //     Unit (Centimeter, SI Linear)
+    0x35, 0x00,           //     Physical Minimum (0)
+    0x46, 0xFF, 0x0F,     //     Physical Maximum (4095)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x05, 0x0D,           //     Usage Page (Digitizer)
+    0x09, 0x48,           //     Usage (Width)
+    0x09, 0x49,           //     Usage (Height)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xB4,                 //     Pop
+    0xC0,                 //   End Collection
+    0x05, 0x0D,           //   Usage Page (Digitizer)
+    0xA4,                 //   Push
+    0x55, 0x0C,           //   Unit Exponent (-4)
+    0x66, 0x01, 0x10,     //   Unit (Seconds, SI Linear)
+    0x47, 0xFF, 0xFF, 0x00, 0x00, // Physical Maximum (65535)
+    0x27, 0xFF, 0xFF, 0x00, 0x00, // Logical Maximum (65535)
+    0x75, 0x10,           //   Report Size (16)
+    0x95, 0x01,           //   Report Count (1)
+    0x09, 0x56,           //   Usage (Scan Time)
+    0x81, 0x02,           //   Input (Data, Variable, Absolute)
+    0xB4,                 //   Pop
+    0x09, 0x54,           //   Usage (Contact Count)
+    0x25, 0x7F,           //   Logical Maximum (127)
+    0x75, 0x08,           //   Report Size (8)
+    0x95, 0x01,           //   Report Count (1)
+    0x81, 0x02,           //   Input (Data, Variable, Absolute)
+    0x85, 0x02,           //   Report ID (2)
+    0x09, 0x55,           //   Usage (Contact Count Maximum)
+    0xB1, 0x02,           //   Feature (Data, Variable, Absolute)
+    0xC0,                 // End Collection
+
+    0x05, 0x0D,           // Usage Page (Digitizer)
+    0x09, 0x0E,           // Usage (Device Configuration)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x03,           //   Report ID (3)
+    0x09, 0x22,           //   Usage (Finger)
+    0xA1, 0x02,           //   Collection (Logical)
+    0x09, 0x52,           //     Usage (Device Mode)
+    0x09, 0x53,           //     Usage (Device Identifier)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x0A,           //     Logical Maximum (10)
+    0x75, 0x08,           //     Report Size (8)
+    0x95, 0x02,           //     Report Count (2)
+    0xB1, 0x02,           //     Feature (Data, Variable, Absolute)
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+
+    0x05, 0x01,           // Usage Page (Generic Desktop)
+    0x09, 0x02,           // Usage (Mouse)
+    0xA1, 0x01,           // Collection (Application)
+    0x85, 0x04,           //   Report ID (4)
+    0x09, 0x01,           //   Usage (Pointer)
+    0xA1, 0x00,           //   Collection (Physical)
+    0x05, 0x09,           //     Usage Page (Buttons)
+    0x19, 0x01,           //     Usage Minimum (1)
+    0x29, 0x02,           //     Usage Maximum (2)
+    0x15, 0x00,           //     Logical Minimum (0)
+    0x25, 0x01,           //     Logical Maximum (1)
+    0x75, 0x01,           //     Report Size (1)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0x95, 0x06,           //     Report Count (6)
+    0x81, 0x03,           //     Input (Constant, Variable, Absolute)
+    0x05, 0x01,           //     Usage Page (Generic Desktop)
+    0x09, 0x30,           //     Usage (X)
+    0x09, 0x31,           //     Usage (Y)
+    0x26, 0xFF, 0x0F,     //     Logical Maximum (4095)
+    0x75, 0x10,           //     Report Size (16)
+    0x95, 0x02,           //     Report Count (2)
+    0x81, 0x02,           //     Input (Data, Variable, Absolute)
+    0xC0,                 //   End Collection
+    0xC0,                 // End Collection
+];
+
+/// A single touch contact
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "10")]
+pub struct TouchscreenContact {
+    #[packed_field(bits = "7")]
+    pub tip_switch: bool,
+    #[packed_field(bits = "6")]
+    pub confidence: bool,
+    #[packed_field(bytes = "1")]
+    pub contact_id: u8,
+    #[packed_field(bytes = "2..=3")]
+    pub x: u16,
+    #[packed_field(bytes = "4..=5")]
+    pub y: u16,
+    #[packed_field(bytes = "6..=7")]
+    pub width: u16,
+    #[packed_field(bytes = "8..=9")]
+    pub height: u16,
+}
+
+/// Touch report, excluding the leading report ID
+///
+/// In hybrid reporting mode only the first report of a frame carries the total `contact_count`,
+/// subsequent reports of the same frame have a `contact_count` of zero.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "53")]
+pub struct TouchscreenReport {
+    #[packed_field(bytes = "0..=49", element_size_bytes = "10")]
+    pub contacts: [TouchscreenContact; 5],
+    /// Scan time in units of 100us
+    #[packed_field(bytes = "50..=51")]
+    pub scan_time: u16,
+    #[packed_field(bytes = "52")]
+    pub contact_count: u8,
+}
+
+impl TouchscreenReport {
+    /// Split a frame of contacts into hybrid mode reports
+    ///
+    /// Contacts in excess of `max_contacts` are dropped, `max_contacts` is limited to
+    /// [`MAX_CONTACTS`]
+    pub fn hybrid<C: IntoIterator<Item = TouchscreenContact>>(
+        contacts: C,
+        scan_time: u16,
+        max_contacts: u8,
+    ) -> heapless::Vec<Self, MAX_REPORTS> {
+        let mut reports = heapless::Vec::<Self, MAX_REPORTS>::new();
+        let mut count: u8 = 0;
+
+        for c in contacts
+            .into_iter()
+            .take(usize::from(max_contacts).min(MAX_CONTACTS))
+        {
+            let i = usize::from(count) % CONTACTS_PER_REPORT;
+            if i == 0 {
+                unwrap!(reports
+                    .push(Self {
+                        scan_time,
+                        ..Default::default()
+                    })
+                    .ok());
+            }
+            if let Some(r) = reports.last_mut() {
+                r.contacts[i] = c;
+            }
+            count += 1;
+        }
+
+        if let Some(first) = reports.first_mut() {
+            first.contact_count = count;
+        } else {
+            //a frame with no contacts still needs to be reported to release any previous contacts
+            unwrap!(reports
+                .push(Self {
+                    scan_time,
+                    ..Default::default()
+                })
+                .ok());
+        }
+
+        reports
+    }
+}
+
+/// Report sent while the host has the touchscreen in [`DeviceMode::Mouse`], excluding the
+/// leading report ID
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default, PackedStruct)]
+#[packed_struct(endian = "lsb", size_bytes = "5")]
+pub struct TouchscreenMouseReport {
+    #[packed_field]
+    pub buttons: u8,
+    #[packed_field]
+    pub x: u16,
+    #[packed_field]
+    pub y: u16,
+}
+
+/// Input mode selected by the host through the Device Mode feature report
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum DeviceMode {
+    #[default]
+    Mouse = 0x00,
+    SingleInput = 0x01,
+    MultiInput = 0x02,
+}
+
+/// Multi-touch touchscreen
+///
+/// Devices start in [`DeviceMode::Mouse`] and report the primary contact as an absolute pointer until
+/// the host selects [`DeviceMode::MultiInput`], as Windows and Linux do during enumeration.
+///
+/// **Note:** Frames with more than [`CONTACTS_PER_REPORT`] contacts are sent over several reports,
+/// [`UsbHidClass::tick()`] must be called every 1ms to send the remainder of the frame.
+pub struct Touchscreen<'a, B: UsbBus> {
+    interface: Interface<'a, B, InBytes64, OutNone, ReportSingle>,
+    max_contacts: u8,
+    device_mode: DeviceMode,
+    pending: Deque<TouchscreenReport, MAX_REPORTS>,
+    feature_report: [u8; 3],
+}
+
+impl<B: UsbBus> Touchscreen<'_, B> {
+    /// Write a frame of contacts, splitting it across several reports if required
+    ///
+    /// Returns [`UsbHidError::WouldBlock`] if the previous frame has not been completely sent
+    pub fn write_report<C: IntoIterator<Item = TouchscreenContact>>(
+        &mut self,
+        contacts: C,
+        scan_time: u16,
+    ) -> Result<(), UsbHidError> {
+        if !self.pending.is_empty() {
+            return Err(UsbHidError::WouldBlock);
+        }
+
+        match self.device_mode {
+            DeviceMode::Mouse => {
+                let contact = contacts.into_iter().next().unwrap_or_default();
+                let report = TouchscreenMouseReport {
+                    buttons: u8::from(contact.tip_switch),
+                    x: contact.x,
+                    y: contact.y,
+                };
+                let data = report.pack().map_err(|_| {
+                    error!("Error packing TouchscreenMouseReport");
+                    UsbHidError::SerializationError
+                })?;
+                self.write_with_id(MOUSE_REPORT_ID, &data)
+            }
+            DeviceMode::SingleInput | DeviceMode::MultiInput => {
+                let max_contacts = if self.device_mode == DeviceMode::SingleInput {
+                    1
+                } else {
+                    self.max_contacts
+                };
+
+                let mut reports =
+                    TouchscreenReport::hybrid(contacts, scan_time, max_contacts).into_iter();
+
+                if let Some(first) = reports.next() {
+                    self.write_touch_report(&first)?;
+                }
+                for r in reports {
+                    unwrap!(self.pending.push_back(r).ok());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The maximum number of contacts reported to the host
+    #[must_use]
+    pub fn max_contacts(&self) -> u8 {
+        self.max_contacts
+    }
+
+    /// The input mode most recently selected by the host
+    #[must_use]
+    pub fn device_mode(&self) -> DeviceMode {
+        self.device_mode
+    }
+
+    fn write_touch_report(&mut self, report: &TouchscreenReport) -> Result<(), UsbHidError> {
+        let data = report.pack().map_err(|_| {
+            error!("Error packing TouchscreenReport");
+            UsbHidError::SerializationError
+        })?;
+        self.write_with_id(TOUCH_REPORT_ID, &data)
+    }
+
+    fn write_with_id(&mut self, report_id: u8, data: &[u8]) -> Result<(), UsbHidError> {
+        let mut buffer = [0u8; 64];
+        buffer[0] = report_id;
+        buffer[1..=data.len()].copy_from_slice(data);
+        self.interface
+            .write_report(&buffer[..=data.len()])
+            .map(|_| ())
+            .map_err(UsbHidError::from)
+    }
+}
+
+impl<'a, B: UsbBus> DeviceClass<'a> for Touchscreen<'a, B> {
+    type I = Interface<'a, B, InBytes64, OutNone, ReportSingle>;
+
+    fn interface(&mut self) -> &mut Self::I {
+        &mut self.interface
+    }
+
+    fn reset(&mut self) {
+        self.device_mode = DeviceMode::default();
+        self.pending.clear();
+    }
+
+    fn tick(&mut self) -> Result<(), UsbHidError> {
+        let Some(report) = self.pending.pop_front() else {
+            return Ok(());
+        };
+
+        match self.write_touch_report(&report) {
+            Err(UsbHidError::WouldBlock) => {
+                //retry on the next tick
+                unwrap!(self.pending.push_front(report).ok());
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+        match report_id {
+            CONTACT_COUNT_MAXIMUM_REPORT_ID => {
+                self.feature_report[..2].copy_from_slice(&[report_id, self.max_contacts]);
+                Some(FeatureReport::DynamicReport(&self.feature_report[..2]))
+            }
+            DEVICE_MODE_REPORT_ID => {
+                self.feature_report = [report_id, self.device_mode.into(), 0];
+                Some(FeatureReport::DynamicReport(&self.feature_report))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> usb_device::Result<()> {
+        match (report_id, data) {
+            (DEVICE_MODE_REPORT_ID, [_, mode, ..]) => {
+                self.device_mode = DeviceMode::try_from(*mode).map_err(|_| UsbError::ParseError)?;
+                self.pending.clear();
+                info!("Set touchscreen device mode to {:?}", self.device_mode);
+                Ok(())
+            }
+            _ => Err(UsbError::Unsupported),
+        }
+    }
+}
+
+pub struct TouchscreenConfig<'a> {
+    interface: InterfaceConfig<'a, InBytes64, OutNone, ReportSingle>,
+    max_contacts: u8,
+}
+
+impl Default for TouchscreenConfig<'_> {
+    fn default() -> Self {
+        Self::new(
+            unwrap!(unwrap!(InterfaceBuilder::with_static_descriptor(
+                TOUCHSCREEN_REPORT_DESCRIPTOR
+            ))
+            .description("Touchscreen")
+            .in_endpoint(5.millis()))
+            .without_out_endpoint()
+            .build(),
+        )
+    }
+}
+
+impl<'a> TouchscreenConfig<'a> {
+    #[must_use]
+    pub fn new(interface: InterfaceConfig<'a, InBytes64, OutNone, ReportSingle>) -> Self {
+        Self {
+            interface,
+            #[allow(clippy::cast_possible_truncation)]
+            max_contacts: MAX_CONTACTS as u8,
+        }
+    }
+
+    /// Set the maximum number of simultaneous contacts reported to the host, between 1 and
+    /// [`MAX_CONTACTS`]
+    pub fn max_contacts(mut self, max_contacts: u8) -> BuilderResult<Self> {
+        if max_contacts == 0 || usize::from(max_contacts) > MAX_CONTACTS {
+            return Err(UsbHidBuilderError::ValueOverflow);
+        }
+        self.max_contacts = max_contacts;
+        Ok(self)
+    }
+}
+
+impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for TouchscreenConfig<'a> {
+    type Allocated = Touchscreen<'a, B>;
+
+    fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+        Self::Allocated {
+            interface: Interface::new(usb_alloc, self.interface),
+            max_contacts: self.max_contacts,
+            device_mode: DeviceMode::default(),
+            pending: Deque::new(),
+            feature_report: [0; 3],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+
+    use packed_struct::prelude::*;
+
+    use crate::device::touchscreen::{
+        DeviceMode, TouchscreenConfig, TouchscreenContact, TouchscreenReport, MAX_CONTACTS,
+        MAX_REPORTS,
+    };
+    use crate::test_util::{test_usb, test_usb_device};
+    use crate::usb_class::UsbHidClassBuilder;
+
+    fn contact(contact_id: u8) -> TouchscreenContact {
+        TouchscreenContact {
+            tip_switch: true,
+            confidence: true,
+            contact_id,
+            x: 0x0123,
+            y: 0x0456,
+            width: 0x10,
+            height: 0x20,
+        }
+    }
+
+    #[test]
+    fn contact_packing() {
+        assert_eq!(
+            contact(3).pack().unwrap(),
+            [0x03, 0x03, 0x23, 0x01, 0x56, 0x04, 0x10, 0x00, 0x20, 0x00]
+        );
+    }
+
+    #[test]
+    fn hybrid_single_report() {
+        let reports = TouchscreenReport::hybrid([contact(0), contact(1)], 100, 10);
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].contact_count, 2);
+        assert_eq!(reports[0].scan_time, 100);
+        assert_eq!(reports[0].contacts[1], contact(1));
+        assert_eq!(reports[0].contacts[2], TouchscreenContact::default());
+    }
+
+    #[test]
+    fn hybrid_split_reports() {
+        let reports = TouchscreenReport::hybrid((0..7).map(contact), 100, 10);
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].contact_count, 7);
+        assert_eq!(reports[1].contact_count, 0);
+        assert_eq!(reports[1].contacts[0], contact(5));
+        assert_eq!(reports[1].contacts[1], contact(6));
+        assert_eq!(reports[1].scan_time, 100);
+    }
+
+    #[test]
+    fn hybrid_max_contacts() {
+        let reports = TouchscreenReport::hybrid((0..7).map(contact), 100, 4);
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].contact_count, 4);
+    }
+
+    #[test]
+    fn hybrid_max_contacts_limited() {
+        let reports = TouchscreenReport::hybrid((0..30).map(contact), 100, u8::MAX);
+
+        assert_eq!(reports.len(), MAX_REPORTS);
+        assert_eq!(usize::from(reports[0].contact_count), MAX_CONTACTS);
+    }
+
+    #[test]
+    fn hybrid_no_contacts() {
+        let reports = TouchscreenReport::hybrid([], 100, 10);
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].contact_count, 0);
+    }
+
+    #[test]
+    fn contact_count_maximum_and_device_mode_features() {
+        let (manager, usb_alloc) = test_usb();
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(TouchscreenConfig::default().max_contacts(5).unwrap())
+            .build(usb_alloc);
+
+        let mut usb_dev = test_usb_device(usb_alloc);
+
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x02, 2),
+            [0x02, 5]
+        );
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x03, 3),
+            [0x03, 0x00, 0x00]
+        );
+
+        manager.host_set_feature(&mut usb_dev, &mut hid, 0x03, &[0x03, 0x02, 0x00]);
+        assert_eq!(hid.device().device_mode(), DeviceMode::MultiInput);
+        assert_eq!(
+            manager.host_get_feature(&mut usb_dev, &mut hid, 0x03, 3),
+            [0x03, 0x02, 0x00]
+        );
+    }
+}
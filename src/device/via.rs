@@ -0,0 +1,557 @@
+//! VIA keyboard configuration protocol over raw HID
+//!
+//! Implements the command set used by the [VIA](https://caniusevia.com) configurator on top of a
+//! [`RawHid`] interface, delegating keymap, macro and lighting storage to a [`ViaApp`].
+//!
+//! See the [QMK VIA implementation](https://github.com/qmk/qmk_firmware/blob/master/quantum/via.c)
+//! for protocol detail
+use fugit::ExtU32;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use usb_device::bus::UsbBus;
+use usb_device::UsbError;
+
+use crate::device::raw::{RawHid, RawHidConfig, RawHidDescriptor};
+use crate::usb_class::prelude::*;
+
+/// Size of a VIA report
+pub const REPORT_SIZE: usize = 32;
+/// VIA protocol version implemented
+pub const PROTOCOL_VERSION: u16 = 0x000C;
+/// Largest block of keymap or macro data transferred in a single report
+pub const MAX_BUFFER_CHUNK: usize = REPORT_SIZE - 4;
+
+const UNHANDLED: u8 = 0xFF;
+
+/// VIA raw HID report descriptor, vendor usage page 0xFF60 and usage 0x61 with 32 byte reports
+pub static VIA_DESCRIPTOR: RawHidDescriptor<InBytes32, OutBytes32> =
+    RawHidDescriptor::new(0xFF60, 0x61, 0);
+
+/// Raw HID interface used by [`Via`]
+pub type RawVia<'a, B> = RawHid<'a, B, InBytes32, OutBytes32>;
+
+/// Raw HID configuration with the [`VIA_DESCRIPTOR`]
+#[must_use]
+pub fn via_config() -> RawHidConfig<'static, InBytes32, OutBytes32> {
+    RawHidConfig::with_interface(
+        unwrap!(
+            unwrap!(unwrap!(InterfaceBuilder::new(VIA_DESCRIPTOR.as_bytes()))
+                .description("VIA")
+                .in_endpoint(1.millis()))
+            .with_out_endpoint(1.millis())
+        )
+        .build(),
+        0,
+    )
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum Command {
+    GetProtocolVersion = 0x01,
+    GetKeyboardValue = 0x02,
+    SetKeyboardValue = 0x03,
+    DynamicKeymapGetKeycode = 0x04,
+    DynamicKeymapSetKeycode = 0x05,
+    DynamicKeymapReset = 0x06,
+    CustomSetValue = 0x07,
+    CustomGetValue = 0x08,
+    CustomSave = 0x09,
+    EepromReset = 0x0A,
+    BootloaderJump = 0x0B,
+    DynamicKeymapMacroGetCount = 0x0C,
+    DynamicKeymapMacroGetBufferSize = 0x0D,
+    DynamicKeymapMacroGetBuffer = 0x0E,
+    DynamicKeymapMacroSetBuffer = 0x0F,
+    DynamicKeymapMacroReset = 0x10,
+    DynamicKeymapGetLayerCount = 0x11,
+    DynamicKeymapGetBuffer = 0x12,
+    DynamicKeymapSetBuffer = 0x13,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum KeyboardValue {
+    Uptime = 0x01,
+    LayoutOptions = 0x02,
+    SwitchMatrixState = 0x03,
+    FirmwareVersion = 0x04,
+    DeviceIndication = 0x05,
+}
+
+/// Firmware storage and behaviour used by [`Via`]
+///
+/// Keycodes are 16-bit QMK keycodes. Rows and columns are zero based positions in the switch
+/// matrix.
+pub trait ViaApp {
+    /// Number of keymap layers
+    fn layer_count(&self) -> u8;
+
+    /// Number of rows and columns in the switch matrix
+    fn matrix_size(&self) -> (u8, u8);
+
+    fn keycode(&self, layer: u8, row: u8, col: u8) -> u16;
+
+    fn set_keycode(&mut self, layer: u8, row: u8, col: u8, keycode: u16);
+
+    /// Restore the default keymap
+    fn reset_keymap(&mut self) {}
+
+    /// Layout options bit field, as defined by the keyboard's VIA definition
+    fn layout_options(&self) -> u32 {
+        0
+    }
+
+    fn set_layout_options(&mut self, _options: u32) {}
+
+    fn firmware_version(&self) -> u32 {
+        0
+    }
+
+    fn macro_count(&self) -> u8 {
+        0
+    }
+
+    /// Size of the macro buffer in bytes
+    fn macro_buffer_size(&self) -> u16 {
+        0
+    }
+
+    /// Read `data.len()` bytes from the macro buffer, starting at `offset`
+    fn read_macro_buffer(&self, _offset: u16, _data: &mut [u8]) {}
+
+    /// Write `data` to the macro buffer, starting at `offset`
+    fn write_macro_buffer(&mut self, _offset: u16, _data: &[u8]) {}
+
+    /// Clear the macro buffer
+    fn reset_macros(&mut self) {}
+
+    /// Read a custom value, such as a lighting setting, into `data`
+    ///
+    /// Returns `false` if the channel or value is not supported
+    fn get_custom_value(&mut self, _channel: u8, _value_id: u8, _data: &mut [u8]) -> bool {
+        false
+    }
+
+    /// Set a custom value, such as a lighting setting, from `data`
+    ///
+    /// Returns `false` if the channel or value is not supported
+    fn set_custom_value(&mut self, _channel: u8, _value_id: u8, _data: &[u8]) -> bool {
+        false
+    }
+
+    /// Persist the custom values of a channel
+    ///
+    /// Returns `false` if the channel is not supported
+    fn save_custom_values(&mut self, _channel: u8) -> bool {
+        false
+    }
+
+    /// Restore all settings to their defaults
+    fn reset_eeprom(&mut self) {}
+
+    /// Reboot into the bootloader
+    fn jump_to_bootloader(&mut self) {}
+}
+
+/// VIA protocol handler for a [`RawVia`] interface
+///
+/// **Note:** [`Via::tick()`] must be called every 1ms, and [`Via::poll()`] after every USB poll.
+pub struct Via<A: ViaApp> {
+    app: A,
+    uptime: u32,
+    response: Option<[u8; REPORT_SIZE]>,
+}
+
+impl<A: ViaApp> Via<A> {
+    #[must_use]
+    pub fn new(app: A) -> Self {
+        Self {
+            app,
+            uptime: 0,
+            response: None,
+        }
+    }
+
+    pub fn app(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    /// Advance the uptime reported to the host, call every 1ms
+    pub fn tick(&mut self) {
+        self.uptime = self.uptime.wrapping_add(1);
+    }
+
+    /// Handle any request received by `raw` and write the response
+    ///
+    /// A new request is not read until the response to the previous request has been written
+    pub fn poll<B: UsbBus>(&mut self, raw: &mut RawVia<'_, B>) -> Result<(), UsbHidError> {
+        if self.response.is_none() {
+            let mut report = [0u8; REPORT_SIZE];
+            match raw.read_report(&mut report) {
+                Ok(_) => {
+                    self.handle_report(&mut report);
+                    self.response = Some(report);
+                }
+                Err(UsbError::WouldBlock) => {}
+                Err(e) => return Err(UsbHidError::UsbError(e)),
+            }
+        }
+
+        if let Some(response) = self.response {
+            match raw.write_report(&response) {
+                Ok(()) => self.response = None,
+                //retry on the next poll
+                Err(UsbHidError::WouldBlock) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a request, replacing it with the response
+    ///
+    /// Unsupported requests are echoed back with the command byte set to 0xFF
+    pub fn handle_report(&mut self, report: &mut [u8; REPORT_SIZE]) {
+        let Ok(command) = Command::try_from(report[0]) else {
+            report[0] = UNHANDLED;
+            return;
+        };
+
+        let handled = match command {
+            Command::GetProtocolVersion => {
+                report[1..3].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                true
+            }
+            Command::GetKeyboardValue => self.get_keyboard_value(report),
+            Command::SetKeyboardValue => self.set_keyboard_value(report),
+            Command::DynamicKeymapGetKeycode => {
+                let [layer, row, col] = [report[1], report[2], report[3]];
+                let valid = self.is_valid_key(layer, row, col);
+                if valid {
+                    let keycode = self.app.keycode(layer, row, col);
+                    report[4..6].copy_from_slice(&keycode.to_be_bytes());
+                }
+                valid
+            }
+            Command::DynamicKeymapSetKeycode => {
+                let [layer, row, col] = [report[1], report[2], report[3]];
+                let valid = self.is_valid_key(layer, row, col);
+                if valid {
+                    let keycode = u16::from_be_bytes([report[4], report[5]]);
+                    self.app.set_keycode(layer, row, col, keycode);
+                }
+                valid
+            }
+            Command::DynamicKeymapReset => {
+                self.app.reset_keymap();
+                true
+            }
+            Command::CustomSetValue => {
+                let (channel, value_id) = (report[1], report[2]);
+                self.app.set_custom_value(channel, value_id, &report[3..])
+            }
+            Command::CustomGetValue => {
+                let (channel, value_id) = (report[1], report[2]);
+                self.app
+                    .get_custom_value(channel, value_id, &mut report[3..])
+            }
+            Command::CustomSave => self.app.save_custom_values(report[1]),
+            Command::EepromReset => {
+                self.app.reset_eeprom();
+                true
+            }
+            Command::BootloaderJump => {
+                self.app.jump_to_bootloader();
+                true
+            }
+            Command::DynamicKeymapMacroGetCount => {
+                report[1] = self.app.macro_count();
+                true
+            }
+            Command::DynamicKeymapMacroGetBufferSize => {
+                report[1..3].copy_from_slice(&self.app.macro_buffer_size().to_be_bytes());
+                true
+            }
+            Command::DynamicKeymapMacroGetBuffer => {
+                let (offset, len) = self.macro_chunk(report);
+                self.app.read_macro_buffer(offset, &mut report[4..4 + len]);
+                true
+            }
+            Command::DynamicKeymapMacroSetBuffer => {
+                let (offset, len) = self.macro_chunk(report);
+                self.app.write_macro_buffer(offset, &report[4..4 + len]);
+                true
+            }
+            Command::DynamicKeymapMacroReset => {
+                self.app.reset_macros();
+                true
+            }
+            Command::DynamicKeymapGetLayerCount => {
+                report[1] = self.app.layer_count();
+                true
+            }
+            Command::DynamicKeymapGetBuffer => {
+                self.get_keymap_buffer(report);
+                true
+            }
+            Command::DynamicKeymapSetBuffer => {
+                self.set_keymap_buffer(report);
+                true
+            }
+        };
+
+        if !handled {
+            report[0] = UNHANDLED;
+        }
+    }
+
+    fn get_keyboard_value(&mut self, report: &mut [u8; REPORT_SIZE]) -> bool {
+        let value = match KeyboardValue::try_from(report[1]) {
+            Ok(KeyboardValue::Uptime) => self.uptime,
+            Ok(KeyboardValue::LayoutOptions) => self.app.layout_options(),
+            Ok(KeyboardValue::FirmwareVersion) => self.app.firmware_version(),
+            _ => return false,
+        };
+        report[2..6].copy_from_slice(&value.to_be_bytes());
+        true
+    }
+
+    fn set_keyboard_value(&mut self, report: &mut [u8; REPORT_SIZE]) -> bool {
+        match KeyboardValue::try_from(report[1]) {
+            Ok(KeyboardValue::LayoutOptions) => {
+                self.app.set_layout_options(u32::from_be_bytes([
+                    report[2], report[3], report[4], report[5],
+                ]));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn get_keymap_buffer(&mut self, report: &mut [u8; REPORT_SIZE]) {
+        let (offset, len) = buffer_chunk(report);
+        for i in 0..len {
+            if let Some((layer, row, col, high)) = self.keymap_position(offset + i) {
+                let keycode = self.app.keycode(layer, row, col).to_be_bytes();
+                report[4 + i] = keycode[usize::from(!high)];
+            }
+        }
+    }
+
+    fn set_keymap_buffer(&mut self, report: &[u8; REPORT_SIZE]) {
+        let (offset, len) = buffer_chunk(report);
+        for i in 0..len {
+            if let Some((layer, row, col, high)) = self.keymap_position(offset + i) {
+                let mut keycode = self.app.keycode(layer, row, col).to_be_bytes();
+                keycode[usize::from(!high)] = report[4 + i];
+                self.app
+                    .set_keycode(layer, row, col, u16::from_be_bytes(keycode));
+            }
+        }
+    }
+
+    fn is_valid_key(&self, layer: u8, row: u8, col: u8) -> bool {
+        let (rows, cols) = self.app.matrix_size();
+        layer < self.app.layer_count() && row < rows && col < cols
+    }
+
+    /// Layer, row, column and high byte flag of a byte offset into the keymap buffer
+    fn keymap_position(&self, offset: usize) -> Option<(u8, u8, u8, bool)> {
+        let (rows, cols) = self.app.matrix_size();
+        let (rows, cols) = (usize::from(rows), usize::from(cols));
+        let keys = rows * cols;
+        if keys == 0 {
+            return None;
+        }
+        let key = offset / 2;
+        let layer = key / keys;
+        if layer >= usize::from(self.app.layer_count()) {
+            return None;
+        }
+        let key = key % keys;
+        Some((
+            u8::try_from(layer).ok()?,
+            u8::try_from(key / cols).ok()?,
+            u8::try_from(key % cols).ok()?,
+            offset & 1 == 0,
+        ))
+    }
+
+    /// Offset and length of a macro buffer request, limited to the macro buffer size
+    fn macro_chunk(&self, report: &[u8; REPORT_SIZE]) -> (u16, usize) {
+        let (offset, len) = buffer_chunk(report);
+        let size = usize::from(self.app.macro_buffer_size());
+        let len = len.min(size.saturating_sub(offset));
+        (u16::try_from(offset).unwrap_or(u16::MAX), len)
+    }
+}
+
+/// Offset and length of a buffer request, the length limited to [`MAX_BUFFER_CHUNK`]
+fn buffer_chunk(report: &[u8; REPORT_SIZE]) -> (usize, usize) {
+    let offset = usize::from(u16::from_be_bytes([report[1], report[2]]));
+    (offset, usize::from(report[3]).min(MAX_BUFFER_CHUNK))
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    const ROWS: u8 = 2;
+    const COLS: u8 = 3;
+
+    struct TestApp {
+        keymap: [[[u16; COLS as usize]; ROWS as usize]; 2],
+        macros: [u8; 40],
+        brightness: u8,
+        rows: u8,
+    }
+
+    impl ViaApp for TestApp {
+        fn layer_count(&self) -> u8 {
+            2
+        }
+
+        fn matrix_size(&self) -> (u8, u8) {
+            (self.rows, COLS)
+        }
+
+        fn keycode(&self, layer: u8, row: u8, col: u8) -> u16 {
+            self.keymap[usize::from(layer)][usize::from(row)][usize::from(col)]
+        }
+
+        fn set_keycode(&mut self, layer: u8, row: u8, col: u8, keycode: u16) {
+            self.keymap[usize::from(layer)][usize::from(row)][usize::from(col)] = keycode;
+        }
+
+        fn macro_count(&self) -> u8 {
+            4
+        }
+
+        fn macro_buffer_size(&self) -> u16 {
+            40
+        }
+
+        fn read_macro_buffer(&self, offset: u16, data: &mut [u8]) {
+            let offset = usize::from(offset);
+            data.copy_from_slice(&self.macros[offset..offset + data.len()]);
+        }
+
+        fn write_macro_buffer(&mut self, offset: u16, data: &[u8]) {
+            let offset = usize::from(offset);
+            self.macros[offset..offset + data.len()].copy_from_slice(data);
+        }
+
+        fn get_custom_value(&mut self, channel: u8, value_id: u8, data: &mut [u8]) -> bool {
+            if (channel, value_id) == (1, 1) {
+                data[0] = self.brightness;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn set_custom_value(&mut self, channel: u8, value_id: u8, data: &[u8]) -> bool {
+            if (channel, value_id) == (1, 1) {
+                self.brightness = data[0];
+                true
+            } else {
+                false
+            }
+        }
+    }
+
+    fn via() -> Via<TestApp> {
+        let mut keymap = [[[0; COLS as usize]; ROWS as usize]; 2];
+        for (l, layer) in keymap.iter_mut().enumerate() {
+            for (r, row) in layer.iter_mut().enumerate() {
+                for (c, key) in row.iter_mut().enumerate() {
+                    *key = u16::try_from(0x0100 * l + 0x10 * r + c).unwrap();
+                }
+            }
+        }
+        Via::new(TestApp {
+            keymap,
+            macros: [0; 40],
+            brightness: 0,
+            rows: ROWS,
+        })
+    }
+
+    fn request(via: &mut Via<TestApp>, data: &[u8]) -> [u8; REPORT_SIZE] {
+        let mut report = [0; REPORT_SIZE];
+        report[..data.len()].copy_from_slice(data);
+        via.handle_report(&mut report);
+        report
+    }
+
+    #[test]
+    fn protocol_version_and_uptime() {
+        let mut via = via();
+        assert_eq!(request(&mut via, &[0x01])[..3], [0x01, 0x00, 0x0C]);
+
+        for _ in 0..300 {
+            via.tick();
+        }
+        assert_eq!(
+            request(&mut via, &[0x02, 0x01])[..6],
+            [0x02, 0x01, 0, 0, 1, 44]
+        );
+        assert_eq!(request(&mut via, &[0x02, 0x03])[..2], [0xFF, 0x03]);
+        assert_eq!(request(&mut via, &[0x42, 0x01])[..2], [0xFF, 0x01]);
+    }
+
+    #[test]
+    fn keycodes() {
+        let mut via = via();
+        assert_eq!(
+            request(&mut via, &[0x04, 1, 1, 2])[..6],
+            [0x04, 1, 1, 2, 0x01, 0x12]
+        );
+        request(&mut via, &[0x05, 0, 1, 0, 0x12, 0x34]);
+        assert_eq!(via.app().keymap[0][1][0], 0x1234);
+        assert_eq!(request(&mut via, &[0x04, 2, 0, 0])[0], 0xFF);
+        assert_eq!(request(&mut via, &[0x11])[..2], [0x11, 2]);
+    }
+
+    #[test]
+    fn keymap_buffer() {
+        let mut via = via();
+        // Last key of layer 0 and first key of layer 1, starting and ending mid keycode
+        let response = request(&mut via, &[0x12, 0x00, 11, 4]);
+        assert_eq!(response[4..8], [0x12, 0x01, 0x00, 0x01]);
+
+        request(&mut via, &[0x13, 0x00, 23, 3, 0xAB, 0xCD, 0xEF]);
+        assert_eq!(via.app().keymap[1][1][2], 0x01AB);
+        assert_eq!(via.app().keymap[0][1][2], 0x0012);
+    }
+
+    #[test]
+    fn empty_matrix_keymap_buffer() {
+        let mut via = via();
+        via.app().rows = 0;
+        let response = request(&mut via, &[0x12, 0x00, 0, 4]);
+        assert_eq!(response[4..8], [0, 0, 0, 0]);
+
+        request(&mut via, &[0x13, 0x00, 0, 2, 0xAB, 0xCD]);
+        assert_eq!(via.app().keymap[0][0][0], 0x0000);
+    }
+
+    #[test]
+    fn macros_and_custom_values() {
+        let mut via = via();
+        assert_eq!(request(&mut via, &[0x0C])[..2], [0x0C, 4]);
+        assert_eq!(request(&mut via, &[0x0D])[..3], [0x0D, 0, 40]);
+
+        request(&mut via, &[0x0F, 0, 36, 8, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(via.app().macros[36..], [1, 2, 3, 4]);
+        assert_eq!(request(&mut via, &[0x0E, 0, 35, 3])[4..7], [0, 1, 2]);
+
+        request(&mut via, &[0x07, 1, 1, 200]);
+        assert_eq!(request(&mut via, &[0x08, 1, 1])[..4], [0x08, 1, 1, 200]);
+        assert_eq!(request(&mut via, &[0x08, 2, 1])[0], 0xFF);
+    }
+}
@@ -247,6 +247,16 @@ pub enum ReportDescriptor<'a> {
     DynamicDescriptor(&'a [u8]),
 }
 
+/// Feature report data returned to the host in response to a `GET_REPORT(Feature)` request
+///
+/// If the interface uses report IDs the first byte must be the report ID. Dynamic reports must fit
+/// within the `usb-device` control buffer, larger reports should be returned as static data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureReport<'a> {
+    StaticReport(&'static [u8]),
+    DynamicReport(&'a [u8]),
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct InterfaceConfig<'a, I, O, R>
@@ -733,6 +743,13 @@ where
     }
 }
 
+/// Progress of a momentary report written with [`ManagedIdleInterface::write_momentary_report`]
+enum MomentaryState<R> {
+    Idle,
+    Press { press: R, release: R },
+    Release(R),
+}
+
 pub struct ManagedIdleInterface<'a, B: UsbBus, Report, I, O>
 where
     B: UsbBus,
@@ -741,6 +758,7 @@ where
 {
     interface: Interface<'a, B, I, O, ReportSingle>,
     idle_manager: IdleManager<Report>,
+    momentary: MomentaryState<Report>,
 }
 
 #[allow(clippy::inline_always)]
@@ -754,6 +772,7 @@ where
         Self {
             interface,
             idle_manager: IdleManager::default(),
+            momentary: MomentaryState::Idle,
         }
     }
 }
@@ -766,7 +785,17 @@ where
     I: InSize,
     O: OutSize,
 {
+    /// Write a report, suppressing duplicates of the last report sent
+    ///
+    /// Returns [`UsbHidError::WouldBlock`] while a momentary report is in progress
     pub fn write_report(&mut self, report: &Report) -> Result<(), UsbHidError> {
+        if !matches!(self.momentary, MomentaryState::Idle) {
+            return Err(UsbHidError::WouldBlock);
+        }
+        self.write_report_unchecked(report)
+    }
+
+    fn write_report_unchecked(&mut self, report: &Report) -> Result<(), UsbHidError> {
         if self.idle_manager.is_duplicate(report) {
             Err(UsbHidError::Duplicate)
         } else {
@@ -787,6 +816,70 @@ where
     pub fn read_report(&mut self, data: &mut [u8]) -> usb_device::Result<usize> {
         self.interface.read_report(data)
     }
+
+    /// The last report successfully written to the host
+    #[must_use]
+    pub fn last_report(&self) -> Option<Report> {
+        self.idle_manager.last_report()
+    }
+
+    /// Write a report that is automatically released on a later call to [`DeviceClass::tick()`]
+    ///
+    /// The release restores the last report written before the press, or `Report::default()` if
+    /// none has been written. The press is retried from `tick` if the endpoint is busy, so the
+    /// host always sees both the press and the release.
+    ///
+    /// Returns [`UsbHidError::WouldBlock`] if a previous momentary report is still in progress, or
+    /// [`UsbHidError::Duplicate`] without sending anything if `report` is already being held
+    pub fn write_momentary_report(&mut self, report: &Report) -> Result<(), UsbHidError>
+    where
+        Report: Default,
+    {
+        if !matches!(self.momentary, MomentaryState::Idle) {
+            return Err(UsbHidError::WouldBlock);
+        }
+
+        let release = self.idle_manager.last_report().unwrap_or_default();
+        if *report == release {
+            return Err(UsbHidError::Duplicate);
+        }
+        match self.write_report_unchecked(report) {
+            Ok(()) | Err(UsbHidError::Duplicate) => {
+                self.momentary = MomentaryState::Release(release);
+                Ok(())
+            }
+            Err(UsbHidError::WouldBlock) => {
+                self.momentary = MomentaryState::Press {
+                    press: *report,
+                    release,
+                };
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn tick_momentary(&mut self) -> Result<(), UsbHidError> {
+        match self.momentary {
+            MomentaryState::Idle => Ok(()),
+            MomentaryState::Press { press, release } => match self.write_report_unchecked(&press) {
+                Ok(()) | Err(UsbHidError::Duplicate) => {
+                    self.momentary = MomentaryState::Release(release);
+                    Ok(())
+                }
+                Err(UsbHidError::WouldBlock) => Ok(()),
+                Err(e) => Err(e),
+            },
+            MomentaryState::Release(release) => match self.write_report_unchecked(&release) {
+                Ok(()) | Err(UsbHidError::Duplicate) => {
+                    self.momentary = MomentaryState::Idle;
+                    Ok(())
+                }
+                Err(UsbHidError::WouldBlock) => Ok(()),
+                Err(e) => Err(e),
+            },
+        }
+    }
 }
 
 impl<'a, B: UsbBus, Report, I, O, const LEN: usize> DeviceClass<'a>
@@ -805,9 +898,12 @@ where
 
     fn reset(&mut self) {
         self.idle_manager = IdleManager::default();
+        self.momentary = MomentaryState::Idle;
     }
 
     fn tick(&mut self) -> Result<(), UsbHidError> {
+        self.tick_momentary()?;
+
         if !(self.idle_manager.tick(self.interface.global_idle())) {
             Ok(())
         } else if let Some(r) = self.idle_manager.last_report() {
@@ -155,6 +155,8 @@ pub mod device;
 pub mod interface;
 pub mod page;
 pub mod prelude;
+#[cfg(test)]
+pub(crate) mod test_util;
 pub mod usb_class;
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -23,6 +23,7 @@ use packed_struct::prelude::*;
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -34,7 +35,7 @@ use packed_struct::prelude::*;
 )]
 #[repr(u8)]
 pub enum Leds {
-    #[num_enum(default)]
+    #[default]
     Undefined = 0x00,
     NumLock = 0x01,
     CapsLock = 0x02,
@@ -114,21 +115,19 @@ pub enum Leds {
     //0x4C-0xFFFF Reserved
 }
 
-impl Default for Leds {
-    fn default() -> Self {
-        Self::Undefined
-    }
-}
-
 /// Consumer usage page
 ///
 /// See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
 /// Section 15 Consumer Page (0x0C)
+///
+/// Application Launch (AL) usages from 0x1C8 and Application Control (AC) usages from 0x29D are
+/// from later revisions of the HID Usage Tables
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -140,7 +139,7 @@ impl Default for Leds {
 )]
 #[repr(u16)]
 pub enum Consumer {
-    #[num_enum(default)]
+    #[default]
     Unassigned = 0x00,
     ConsumerControl = 0x01,
     NumericKeyPad = 0x02,
@@ -380,7 +379,11 @@ pub enum Consumer {
     ALOnlineActivityBrowser = 0x1C5,
     ALResearchSearchBrowser = 0x1C6,
     ALAudioPlayer = 0x1C7,
-    //0x1C8-0x1FF Reserved
+    ALMessageStatus = 0x1C8,
+    ALContactSync = 0x1C9,
+    ALNavigation = 0x1CA,
+    ALContextAwareDesktopAssistant = 0x1CB,
+    //0x1CC-0x1FF Reserved
     GenericGUIApplicationControls = 0x200,
     ACNew = 0x201,
     ACOpen = 0x202,
@@ -522,13 +525,15 @@ pub enum Consumer {
     ACSplit = 0x29A,
     ACDistributeHorizontally = 0x29B,
     ACDistributeVertically = 0x29C,
-    //0x29D-0xFFFF Reserved
-}
-
-impl Default for Consumer {
-    fn default() -> Self {
-        Self::Unassigned
-    }
+    ACKeyboardLayoutSelect = 0x29D,
+    ACNavigationGuidance = 0x29E,
+    ACDesktopShowAllWindows = 0x29F,
+    ACSoftKeyLeft = 0x2A0,
+    ACSoftKeyRight = 0x2A1,
+    ACDesktopShowAllApplications = 0x2A2,
+    //0x2A3-0x2AF Reserved
+    ACIdleKeepAlive = 0x2B0,
+    //0x2B1-0xFFFF Reserved
 }
 
 /// Generic Desktop usage page
@@ -540,6 +545,7 @@ impl Default for Consumer {
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -551,7 +557,7 @@ impl Default for Consumer {
 )]
 #[repr(u8)]
 pub enum Desktop {
-    #[num_enum(default)]
+    #[default]
     Undefined = 0x00,
     Pointer = 0x01,
     Mouse = 0x02,
@@ -612,12 +618,6 @@ pub enum Desktop {
     //0x94-0xFFFF Reserved
 }
 
-impl Default for Desktop {
-    fn default() -> Self {
-        Self::Undefined
-    }
-}
-
 /// Game Controls usage page
 ///
 /// See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
@@ -627,6 +627,7 @@ impl Default for Desktop {
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -638,7 +639,7 @@ impl Default for Desktop {
 )]
 #[repr(u8)]
 pub enum Game {
-    #[num_enum(default)]
+    #[default]
     Undefined = 0x00,
     Game3DController = 0x01,
     PinballDevice = 0x02,
@@ -672,12 +673,6 @@ pub enum Game {
     //0x3A-0xFFFF Reserved
 }
 
-impl Default for Game {
-    fn default() -> Self {
-        Self::Undefined
-    }
-}
-
 /// Keyboard usage page
 ///
 /// See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
@@ -690,6 +685,7 @@ impl Default for Game {
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -701,7 +697,7 @@ impl Default for Game {
 )]
 #[repr(u8)]
 pub enum Keyboard {
-    #[num_enum(default)]
+    #[default]
     NoEventIndicated = 0x00,
     ErrorRollOver = 0x01,
     POSTFail = 0x02,
@@ -879,12 +875,6 @@ pub enum Keyboard {
     //0xE8-0xFFFF Reserved
 }
 
-impl Default for Keyboard {
-    fn default() -> Self {
-        Self::NoEventIndicated
-    }
-}
-
 /// Simulation Controls usage page
 ///
 /// See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
@@ -894,6 +884,7 @@ impl Default for Keyboard {
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -905,7 +896,7 @@ impl Default for Keyboard {
 )]
 #[repr(u8)]
 pub enum Simulation {
-    #[num_enum(default)]
+    #[default]
     Undefined = 0x00,
     FlightSimulationDevice = 0x01,
     AutomobileSimulationDevice = 0x02,
@@ -964,12 +955,6 @@ pub enum Simulation {
     //0xD1-0xFFFF Reserved
 }
 
-impl Default for Simulation {
-    fn default() -> Self {
-        Self::Undefined
-    }
-}
-
 /// Telephony Device usage page
 ///
 /// See [Universal Serial Bus (USB) HID Usage Tables Version 1.12](<https://www.usb.org/sites/default/files/documents/hut1_12v2.pdf>):
@@ -979,6 +964,7 @@ impl Default for Simulation {
     Debug,
     Copy,
     Clone,
+    Default,
     Eq,
     PartialEq,
     Ord,
@@ -990,7 +976,7 @@ impl Default for Simulation {
 )]
 #[repr(u8)]
 pub enum Telephony {
-    #[num_enum(default)]
+    #[default]
     Unassigned = 0x00,
     Phone = 0x01,
     AnsweringMachine = 0x02,
@@ -1064,8 +1050,92 @@ pub enum Telephony {
     PhoneKeyD = 0xBF,
     //0xC0-0xFFFF Reserved
 }
-impl Default for Telephony {
-    fn default() -> Self {
-        Self::Unassigned
-    }
+
+/// Camera Control usage page
+///
+/// See [HID Usage Tables FOR Universal Serial Bus (USB) Version 1.5](<https://usb.org/sites/default/files/hut1_5.pdf>):
+/// Section 28 Camera Control Page (0x90)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    PrimitiveEnum,
+    IntoPrimitive,
+    FromPrimitive,
+)]
+#[repr(u8)]
+pub enum Camera {
+    #[default]
+    Undefined = 0x00,
+    //0x01-0x1F Reserved
+    CameraAutoFocus = 0x20,
+    CameraShutter = 0x21,
+    //0x22-0xFFFF Reserved
+}
+
+/// Haptics usage page
+///
+/// See [HID Usage Tables FOR Universal Serial Bus (USB) Version 1.5](<https://usb.org/sites/default/files/hut1_5.pdf>):
+/// Haptics Page (0x0E)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    PrimitiveEnum,
+    IntoPrimitive,
+    FromPrimitive,
+)]
+#[repr(u16)]
+pub enum Haptics {
+    #[default]
+    Undefined = 0x00,
+    SimpleHapticController = 0x01,
+    //0x02-0x0F Reserved
+    WaveformList = 0x10,
+    DurationList = 0x11,
+    //0x12-0x1F Reserved
+    AutoTrigger = 0x20,
+    ManualTrigger = 0x21,
+    AutoTriggerAssociatedControl = 0x22,
+    Intensity = 0x23,
+    RepeatCount = 0x24,
+    RetriggerPeriod = 0x25,
+    WaveformVendorPage = 0x26,
+    WaveformVendorId = 0x27,
+    WaveformCutoffTime = 0x28,
+    //0x29-0x1000 Reserved
+    WaveformNone = 0x1001,
+    WaveformStop = 0x1002,
+    WaveformClick = 0x1003,
+    WaveformBuzzContinuous = 0x1004,
+    WaveformRumbleContinuous = 0x1005,
+    WaveformPress = 0x1006,
+    WaveformRelease = 0x1007,
+    WaveformHover = 0x1008,
+    WaveformSuccess = 0x1009,
+    WaveformError = 0x100A,
+    WaveformInkContinuous = 0x100B,
+    WaveformPencilContinuous = 0x100C,
+    WaveformMarkerContinuous = 0x100D,
+    WaveformChiselMarkerContinuous = 0x100E,
+    WaveformBrushContinuous = 0x100F,
+    WaveformEraserContinuous = 0x1010,
+    WaveformSparkleContinuous = 0x1011,
+    //0x1012-0x1FFF Reserved
+    //0x2000-0x2FFF Vendor Waveforms
+    //0x3000-0xFFFF Reserved
 }
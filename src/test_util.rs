@@ -0,0 +1,333 @@
+//! Mock USB bus shared by the class and device tests
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use env_logger::Env;
+use log::SetLoggerError;
+use packed_struct::prelude::*;
+use usb_device::bus::PollResult;
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, RequestType};
+use usb_device::prelude::*;
+use usb_device::{Result, UsbDirection};
+
+use crate::descriptor::{HidProtocol, HidRequest, ReportType, USB_CLASS_HID};
+
+pub(crate) fn init_logging() {
+    let _: core::result::Result<(), SetLoggerError> =
+        env_logger::Builder::from_env(Env::default().default_filter_or("trace"))
+            .is_test(true)
+            .try_init();
+}
+
+/// Create a mock bus and allocator for a test
+///
+/// Both are leaked so that the class and device can borrow them for the rest of the test.
+pub(crate) fn test_usb() -> (
+    &'static UsbTestManager,
+    &'static UsbBusAllocator<TestUsbBus<'static>>,
+) {
+    init_logging();
+    let manager: &'static UsbTestManager = Box::leak(Box::default());
+    let usb_alloc = Box::leak(Box::new(UsbBusAllocator::new(TestUsbBus::new(manager))));
+    (manager, usb_alloc)
+}
+
+/// Max packet size of the control endpoint of [`test_usb_device`]
+const CONTROL_PACKET_SIZE: usize = 64;
+
+/// Build a HID class device with a 64 byte control endpoint
+pub(crate) fn test_usb_device<'a>(
+    usb_alloc: &'a UsbBusAllocator<TestUsbBus<'a>>,
+) -> UsbDevice<'a, TestUsbBus<'a>> {
+    UsbDeviceBuilder::new(usb_alloc, UsbVidPid(0x1209, 0x0001))
+        .device_class(USB_CLASS_HID)
+        .max_packet_size_0(64)
+        .unwrap()
+        .build()
+}
+
+/// Size in bits of each report of a report descriptor
+///
+/// Returns `(main item, report ID, bits)` sorted by main item (`0x80` Input, `0x90` Output,
+/// `0xB0` Feature) and report ID. Panics if the collections are not balanced.
+pub(crate) fn report_bits(descriptor: &[u8]) -> Vec<(u8, u8, u32)> {
+    let mut reports: Vec<(u8, u8, u32)> = Vec::new();
+    let (mut report_id, mut report_size, mut report_count, mut depth) = (0, 0, 0, 0);
+    let mut i = 0;
+    while i < descriptor.len() {
+        let prefix = descriptor[i];
+        let size = [0, 1, 2, 4][usize::from(prefix & 0x03)];
+        let mut data = [0u8; 4];
+        data[..size].copy_from_slice(&descriptor[i + 1..i + 1 + size]);
+        let value = u32::from_le_bytes(data);
+        match prefix & 0xFC {
+            0x84 => report_id = data[0],
+            0x74 => report_size = value,
+            0x94 => report_count = value,
+            0xA0 => depth += 1,
+            0xC0 => {
+                assert!(depth > 0, "End Collection without Collection");
+                depth -= 1;
+            }
+            main @ (0x80 | 0x90 | 0xB0) => {
+                match reports
+                    .iter_mut()
+                    .find(|(m, id, _)| *m == main && *id == report_id)
+                {
+                    Some((_, _, bits)) => *bits += report_size * report_count,
+                    None => reports.push((main, report_id, report_size * report_count)),
+                }
+            }
+            _ => {}
+        }
+        i += 1 + size;
+    }
+    assert_eq!(depth, 0, "Unterminated Collection");
+    reports.sort_unstable();
+    reports
+}
+
+#[derive(Default)]
+pub(crate) struct UsbTestManager {
+    in_buf: Mutex<RefCell<Vec<u8>>>,
+    setup_buf: Mutex<RefCell<Vec<u8>>>,
+    out_data: Mutex<RefCell<Option<Vec<u8>>>>,
+}
+
+impl UsbTestManager {
+    pub(crate) fn host_write_setup(&self, data: &[u8]) -> Result<()> {
+        let buf = self.setup_buf.lock().unwrap();
+        if buf.borrow().is_empty() {
+            buf.borrow_mut().extend_from_slice(data);
+            Ok(())
+        } else {
+            Err(UsbError::WouldBlock)
+        }
+    }
+
+    /// Queue an OUT packet for the device, an empty `data` is sent as a zero length packet
+    pub(crate) fn host_write_out(&self, data: &[u8]) -> Result<()> {
+        let buf = self.out_data.lock().unwrap();
+        if buf.borrow().is_none() {
+            buf.replace(Some(Vec::from(data)));
+            Ok(())
+        } else {
+            Err(UsbError::WouldBlock)
+        }
+    }
+
+    pub(crate) fn host_read_in(&self) -> Vec<u8> {
+        self.in_buf.lock().unwrap().take()
+    }
+
+    /// Request a feature report with `GET_REPORT`, returning the data sent by the device
+    pub(crate) fn host_get_feature<'a>(
+        &self,
+        usb_dev: &mut UsbDevice<'a, TestUsbBus<'a>>,
+        class: &mut dyn UsbClass<TestUsbBus<'a>>,
+        report_id: u8,
+        length: u16,
+    ) -> Vec<u8> {
+        self.host_write_setup(
+            &UsbRequest {
+                direction: UsbDirection::In != UsbDirection::Out,
+                request_type: RequestType::Class as u8,
+                recipient: Recipient::Interface as u8,
+                request: HidRequest::GetReport.into(),
+                value: (u16::from(u8::from(ReportType::Feature)) << 8) | u16::from(report_id),
+                index: 0x0,
+                length,
+            }
+            .pack()
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(usb_dev.poll(&mut [&mut *class]));
+
+        // Reports longer than the control endpoint are sent in several packets
+        let mut packet = self.host_read_in();
+        let mut data = Vec::new();
+        while packet.len() == CONTROL_PACKET_SIZE && data.len() + packet.len() < length.into() {
+            data.extend(packet);
+            usb_dev.poll(&mut [&mut *class]);
+            packet = self.host_read_in();
+        }
+        data.extend(packet);
+        data
+    }
+
+    /// Send a feature report with `SET_REPORT`, `data` includes the report ID if it has one
+    pub(crate) fn host_set_feature<'a>(
+        &self,
+        usb_dev: &mut UsbDevice<'a, TestUsbBus<'a>>,
+        class: &mut dyn UsbClass<TestUsbBus<'a>>,
+        report_id: u8,
+        data: &[u8],
+    ) {
+        self.host_write_setup(
+            &UsbRequest {
+                direction: UsbDirection::Out == UsbDirection::In,
+                request_type: RequestType::Class as u8,
+                recipient: Recipient::Interface as u8,
+                request: HidRequest::SetReport.into(),
+                value: (u16::from(u8::from(ReportType::Feature)) << 8) | u16::from(report_id),
+                index: 0x0,
+                length: u16::try_from(data.len()).unwrap(),
+            }
+            .pack()
+            .unwrap(),
+        )
+        .unwrap();
+        usb_dev.poll(&mut [&mut *class]);
+        self.host_write_out(data).unwrap();
+        assert!(usb_dev.poll(&mut [class]));
+    }
+
+    /// Send a `SET_PROTOCOL` request to interface 0
+    pub(crate) fn host_set_protocol<'a>(
+        &self,
+        usb_dev: &mut UsbDevice<'a, TestUsbBus<'a>>,
+        class: &mut dyn UsbClass<TestUsbBus<'a>>,
+        protocol: HidProtocol,
+    ) {
+        self.host_write_setup(
+            &UsbRequest {
+                direction: UsbDirection::Out == UsbDirection::In,
+                request_type: RequestType::Class as u8,
+                recipient: Recipient::Interface as u8,
+                request: HidRequest::SetProtocol.into(),
+                value: protocol as u16,
+                index: 0x0,
+                length: 0x0,
+            }
+            .pack()
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(usb_dev.poll(&mut [class]));
+    }
+
+    fn has_setup_data(&self) -> bool {
+        !self.setup_buf.lock().unwrap().borrow().is_empty()
+    }
+
+    fn has_out_data(&self) -> bool {
+        self.out_data.lock().unwrap().borrow().is_some()
+    }
+
+    fn device_read_setup(&self, data: &mut [u8]) -> Result<usize> {
+        let buf = self.setup_buf.lock().unwrap();
+        if buf.borrow().is_empty() {
+            Err(UsbError::WouldBlock)
+        } else {
+            let tmp = buf.take();
+            data[..tmp.len()].copy_from_slice(&tmp);
+            Ok(tmp.len())
+        }
+    }
+
+    fn device_read_out(&self, data: &mut [u8]) -> Result<usize> {
+        let buf = self.out_data.lock().unwrap();
+        let Some(tmp) = buf.take() else {
+            return Err(UsbError::WouldBlock);
+        };
+        data[..tmp.len()].copy_from_slice(&tmp);
+        Ok(tmp.len())
+    }
+
+    fn device_write(&self, data: &[u8]) -> Result<usize> {
+        let buf = self.in_buf.lock().unwrap();
+        if buf.borrow().is_empty() {
+            buf.borrow_mut().extend_from_slice(data);
+            Ok(data.len())
+        } else {
+            Err(UsbError::WouldBlock)
+        }
+    }
+}
+
+pub(crate) struct TestUsbBus<'a> {
+    next_ep_index: usize,
+    manager: &'a UsbTestManager,
+}
+
+impl<'a> TestUsbBus<'a> {
+    pub(crate) fn new(manager: &'a UsbTestManager) -> Self {
+        TestUsbBus {
+            next_ep_index: 0,
+            manager,
+        }
+    }
+}
+
+impl UsbBus for TestUsbBus<'_> {
+    fn alloc_ep(
+        &mut self,
+        ep_dir: UsbDirection,
+        _ep_addr: Option<EndpointAddress>,
+        _ep_type: EndpointType,
+        _max_packet_size: u16,
+        _interval: u8,
+    ) -> Result<EndpointAddress> {
+        let ep = EndpointAddress::from_parts(self.next_ep_index, ep_dir);
+        self.next_ep_index += 1;
+        Ok(ep)
+    }
+
+    fn enable(&mut self) {}
+    fn reset(&self) {
+        todo!()
+    }
+    fn set_device_address(&self, _addr: u8) {
+        todo!()
+    }
+    fn write(&self, _ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
+        self.manager.device_write(buf)
+    }
+    fn read(&self, _ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
+        if self.manager.has_setup_data() {
+            self.manager.device_read_setup(buf)
+        } else {
+            self.manager.device_read_out(buf)
+        }
+    }
+    fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+    fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+        todo!()
+    }
+    fn suspend(&self) {
+        todo!()
+    }
+    fn resume(&self) {
+        todo!()
+    }
+    fn poll(&self) -> PollResult {
+        PollResult::Data {
+            ep_out: u16::from(self.manager.has_out_data()),
+            ep_in_complete: 1,
+            ep_setup: u16::from(self.manager.has_setup_data()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PackedStruct)]
+#[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "8")]
+pub(crate) struct UsbRequest {
+    #[packed_field(bits = "0")]
+    pub(crate) direction: bool,
+    #[packed_field(bits = "1:2")]
+    pub(crate) request_type: u8,
+    #[packed_field(bits = "4:7")]
+    pub(crate) recipient: u8,
+    pub(crate) request: u8,
+    pub(crate) value: u16,
+    pub(crate) index: u16,
+    pub(crate) length: u16,
+}
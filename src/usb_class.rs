@@ -1,8 +1,8 @@
 //! USB Class for implementing Human Interface Devices
 
-use crate::descriptor::{DescriptorType, HidProtocol, HidRequest};
+use crate::descriptor::{DescriptorType, HidProtocol, HidRequest, ReportType};
 use crate::device::{DeviceClass, DeviceHList};
-use crate::interface::{InterfaceClass, ReportDescriptor, UsbAllocatable};
+use crate::interface::{FeatureReport, InterfaceClass, ReportDescriptor, UsbAllocatable};
 use crate::UsbHidError;
 use core::cell::RefCell;
 use core::default::Default;
@@ -26,12 +26,12 @@ pub mod prelude {
     //! use usbd_human_interface_device::usb_class::prelude::*;
     //! ```
 
-    pub use crate::descriptor::{HidProtocol, InterfaceProtocol};
+    pub use crate::descriptor::{HidProtocol, InterfaceProtocol, ReportType};
     pub use crate::device::DeviceClass;
     pub use crate::interface::{
-        InBytes16, InBytes32, InBytes64, InBytes8, InNone, Interface, InterfaceBuilder,
-        InterfaceConfig, OutBytes16, OutBytes32, OutBytes64, OutBytes8, OutNone, ReportSingle,
-        Reports128, Reports16, Reports32, Reports64, Reports8, UsbAllocatable,
+        FeatureReport, InBytes16, InBytes32, InBytes64, InBytes8, InNone, Interface,
+        InterfaceBuilder, InterfaceConfig, OutBytes16, OutBytes32, OutBytes64, OutBytes8, OutNone,
+        ReportSingle, Reports128, Reports16, Reports32, Reports64, Reports8, UsbAllocatable,
     };
     pub use crate::interface::{ManagedIdleInterface, ManagedIdleInterfaceConfig};
     pub use crate::usb_class::{UsbHidClass, UsbHidClassBuilder};
@@ -183,6 +183,43 @@ impl<'a, B: UsbBus + 'a, Devices> UsbHidClass<'a, B, Devices> {
     }
 }
 
+impl<'a, B: UsbBus + 'a, Devices: DeviceHList<'a>> UsbHidClass<'a, B, Devices> {
+    fn get_report(transfer: ControlIn<B>, interface: &mut dyn InterfaceClass<'a>) {
+        let requested_n = transfer.request().length.into();
+        if let Err(e) = transfer.accept(|buffer| {
+            interface.get_report(buffer).inspect(|&n| {
+                if n != requested_n {
+                    warn!("GetReport requested {} bytes, got {} bytes", requested_n, n);
+                }
+            })
+        }) {
+            error!("Failed to send report - {:?}", e);
+        } else {
+            trace!("Sent report");
+            unwrap!(interface.get_report_ack());
+        }
+    }
+
+    fn get_feature_report(transfer: ControlIn<B>, devices: &mut Devices, interface_id: u8) {
+        let report_id = (transfer.request().value & 0xFF) as u8;
+        let result = match devices.get_feature_report(interface_id, report_id) {
+            Some(FeatureReport::StaticReport(data)) => transfer.accept_with_static(data),
+            Some(FeatureReport::DynamicReport(data)) => transfer.accept_with(data),
+            None => {
+                warn!("Unsupported feature report ID{:X}", report_id);
+                transfer.reject()
+            }
+        };
+
+        match result {
+            Err(e) => error!("Failed to send feature report - {:?}", e),
+            Ok(()) => {
+                trace!("Sent feature report ID{:X}", report_id);
+            }
+        }
+    }
+}
+
 impl<'a, B, Devices> UsbClass<B> for UsbHidClass<'a, B, Devices>
 where
     B: UsbBus + 'a,
@@ -213,10 +250,11 @@ where
             return;
         }
 
-        let Some(interface) = u8::try_from(request.index)
-            .ok()
-            .and_then(|id| self.devices.get_mut().get(id))
-        else {
+        let Ok(interface_id) = u8::try_from(request.index) else {
+            return;
+        };
+
+        let Some(interface) = self.devices.get_mut().get(interface_id) else {
             return;
         };
 
@@ -229,8 +267,26 @@ where
 
         match HidRequest::try_from(request.request) {
             Ok(HidRequest::SetReport) => {
-                interface.set_report(transfer.data()).ok();
-                transfer.accept().ok();
+                let report_id = (request.value & 0xFF) as u8;
+                if ReportType::try_from((request.value >> 8) as u8) == Ok(ReportType::Feature) {
+                    match self.devices.get_mut().set_feature_report(
+                        interface_id,
+                        report_id,
+                        transfer.data(),
+                    ) {
+                        Ok(()) => {
+                            trace!("Set feature report ID{:X}", report_id);
+                            transfer.accept().ok();
+                        }
+                        Err(e) => {
+                            warn!("Failed to set feature report ID{:X} - {:?}", report_id, e);
+                            transfer.reject().ok();
+                        }
+                    }
+                } else {
+                    interface.set_report(transfer.data()).ok();
+                    transfer.accept().ok();
+                }
             }
             Ok(HidRequest::SetIdle) => {
                 if request.length != 0 {
@@ -303,23 +359,14 @@ where
                 };
 
                 match HidRequest::try_from(request.request) {
+                    Ok(HidRequest::GetReport)
+                        if ReportType::try_from((request.value >> 8) as u8)
+                            == Ok(ReportType::Feature) =>
+                    {
+                        Self::get_feature_report(transfer, self.devices.get_mut(), interface_id);
+                    }
                     Ok(HidRequest::GetReport) => {
-                        let requested_n = transfer.request().length.into();
-                        if let Err(e) = transfer.accept(|buffer| {
-                            interface.get_report(buffer).inspect(|&n| {
-                                if n != requested_n {
-                                    warn!(
-                                        "GetReport requested {} bytes, got {} bytes",
-                                        requested_n, n
-                                    );
-                                }
-                            })
-                        }) {
-                            error!("Failed to send report - {:?}", e);
-                        } else {
-                            trace!("Sent report");
-                            unwrap!(interface.get_report_ack());
-                        }
+                        Self::get_report(transfer, interface);
                     }
                     Ok(HidRequest::GetIdle) => {
                         if request.length != 1 {
@@ -382,152 +429,67 @@ mod test {
     #![allow(clippy::unwrap_used)]
     #![allow(clippy::expect_used)]
 
-    use std::cell::RefCell;
-    use std::sync::Mutex;
-    use std::vec::Vec;
-
     use crate::descriptor::USB_CLASS_HID;
-    use crate::interface::{InBytes64, InterfaceBuilder, OutBytes64, ReportSingle, Reports8};
-    use env_logger::Env;
+    use crate::interface::{
+        InBytes64, InBytes8, Interface, InterfaceBuilder, InterfaceConfig, OutBytes64, OutNone,
+        ReportSingle, Reports8,
+    };
+    use crate::test_util::{init_logging, TestUsbBus, UsbRequest, UsbTestManager};
     use fugit::MillisDurationU32;
-    use log::SetLoggerError;
     use packed_struct::prelude::*;
-    use usb_device::bus::PollResult;
+    use std::vec::Vec;
     use usb_device::prelude::*;
     use usb_device::UsbDirection;
 
     use super::*;
 
-    fn init_logging() {
-        let _: core::result::Result<(), SetLoggerError> =
-            env_logger::Builder::from_env(Env::default().default_filter_or("trace"))
-                .is_test(true)
-                .try_init();
+    struct FeatureTestDevice<'a, B: UsbBus> {
+        interface: Interface<'a, B, InBytes8, OutNone, ReportSingle>,
+        feature: [u8; 3],
     }
 
-    #[derive(Default)]
-    struct UsbTestManager {
-        in_buf: Mutex<RefCell<Vec<u8>>>,
-        setup_buf: Mutex<RefCell<Vec<u8>>>,
-    }
+    impl<'a, B: UsbBus> DeviceClass<'a> for FeatureTestDevice<'a, B> {
+        type I = Interface<'a, B, InBytes8, OutNone, ReportSingle>;
 
-    impl UsbTestManager {
-        fn host_write_setup(&self, data: &[u8]) -> Result<()> {
-            let buf = self.setup_buf.lock().unwrap();
-            if buf.borrow().is_empty() {
-                buf.borrow_mut().extend_from_slice(data);
-                Ok(())
-            } else {
-                Err(UsbError::WouldBlock)
-            }
+        fn interface(&mut self) -> &mut Self::I {
+            &mut self.interface
         }
 
-        fn host_read_in(&self) -> Vec<u8> {
-            self.in_buf.lock().unwrap().take()
-        }
+        fn reset(&mut self) {}
 
-        fn has_setup_data(&self) -> bool {
-            !self.setup_buf.lock().unwrap().borrow().is_empty()
+        fn tick(&mut self) -> core::result::Result<(), UsbHidError> {
+            Ok(())
         }
 
-        fn device_read_setup(&self, data: &mut [u8]) -> Result<usize> {
-            let buf = self.setup_buf.lock().unwrap();
-            if buf.borrow().is_empty() {
-                Err(UsbError::WouldBlock)
-            } else {
-                let tmp = buf.take();
-                data[..tmp.len()].copy_from_slice(&tmp);
-                Ok(tmp.len())
-            }
+        fn get_feature_report(&mut self, report_id: u8) -> Option<FeatureReport<'_>> {
+            (report_id == self.feature[0]).then_some(FeatureReport::DynamicReport(&self.feature))
         }
 
-        fn device_write(&self, data: &[u8]) -> Result<usize> {
-            let buf = self.in_buf.lock().unwrap();
-            if buf.borrow().is_empty() {
-                buf.borrow_mut().extend_from_slice(data);
-                Ok(data.len())
+        fn set_feature_report(&mut self, report_id: u8, data: &[u8]) -> Result<()> {
+            if report_id == self.feature[0] && data.len() == self.feature.len() {
+                self.feature.copy_from_slice(data);
+                Ok(())
             } else {
-                Err(UsbError::WouldBlock)
+                Err(UsbError::ParseError)
             }
         }
     }
 
-    struct TestUsbBus<'a> {
-        next_ep_index: usize,
-        manager: &'a UsbTestManager,
+    struct FeatureTestDeviceConfig<'a> {
+        interface: InterfaceConfig<'a, InBytes8, OutNone, ReportSingle>,
     }
 
-    impl<'a> TestUsbBus<'a> {
-        fn new(manager: &'a UsbTestManager) -> Self {
-            TestUsbBus {
-                next_ep_index: 0,
-                manager,
-            }
-        }
-    }
-
-    impl UsbBus for TestUsbBus<'_> {
-        fn alloc_ep(
-            &mut self,
-            ep_dir: UsbDirection,
-            _ep_addr: Option<EndpointAddress>,
-            _ep_type: EndpointType,
-            _max_packet_size: u16,
-            _interval: u8,
-        ) -> Result<EndpointAddress> {
-            let ep = EndpointAddress::from_parts(self.next_ep_index, ep_dir);
-            self.next_ep_index += 1;
-            Ok(ep)
-        }
+    impl<'a, B: UsbBus + 'a> UsbAllocatable<'a, B> for FeatureTestDeviceConfig<'a> {
+        type Allocated = FeatureTestDevice<'a, B>;
 
-        fn enable(&mut self) {}
-        fn reset(&self) {
-            todo!()
-        }
-        fn set_device_address(&self, _addr: u8) {
-            todo!()
-        }
-        fn write(&self, _ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
-            self.manager.device_write(buf)
-        }
-        fn read(&self, _ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
-            self.manager.device_read_setup(buf)
-        }
-        fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
-        fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
-            todo!()
-        }
-        fn suspend(&self) {
-            todo!()
-        }
-        fn resume(&self) {
-            todo!()
-        }
-        fn poll(&self) -> PollResult {
-            PollResult::Data {
-                ep_out: 0,
-                ep_in_complete: 1,
-                ep_setup: u16::from(self.manager.has_setup_data()),
+        fn allocate(self, usb_alloc: &'a UsbBusAllocator<B>) -> Self::Allocated {
+            FeatureTestDevice {
+                interface: self.interface.allocate(usb_alloc),
+                feature: [0x02, 0xAB, 0xCD],
             }
         }
     }
 
-    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, PackedStruct)]
-    #[packed_struct(endian = "lsb", bit_numbering = "msb0", size_bytes = "8")]
-    struct UsbRequest {
-        #[packed_field(bits = "0")]
-        direction: bool,
-        #[packed_field(bits = "1:2")]
-        request_type: u8,
-        #[packed_field(bits = "4:7")]
-        recipient: u8,
-        request: u8,
-        value: u16,
-        index: u16,
-        length: u16,
-    }
-
     #[test]
     fn descriptor_ordering_satisfies_boot_spec() {
         init_logging();
@@ -1337,4 +1299,90 @@ mod test {
             "Unexpected report idle value"
         );
     }
+
+    #[test]
+    fn get_feature_report() {
+        init_logging();
+
+        let manager = UsbTestManager::default();
+
+        let usb_alloc = UsbBusAllocator::new(TestUsbBus::new(&manager));
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(FeatureTestDeviceConfig {
+                interface: InterfaceBuilder::new(&[]).unwrap().build(),
+            })
+            .build(&usb_alloc);
+
+        let mut usb_dev = UsbDeviceBuilder::new(&usb_alloc, UsbVidPid(0x1209, 0x0001))
+            .device_class(USB_CLASS_HID)
+            .build();
+
+        // Get feature report
+        manager
+            .host_write_setup(
+                &UsbRequest {
+                    direction: UsbDirection::In != UsbDirection::Out,
+                    request_type: RequestType::Class as u8,
+                    recipient: Recipient::Interface as u8,
+                    request: HidRequest::GetReport.into(),
+                    value: (u16::from(u8::from(ReportType::Feature)) << 8) | 0x02,
+                    index: 0x0,
+                    length: 0x3,
+                }
+                .pack()
+                .unwrap(),
+            )
+            .unwrap();
+
+        assert!(usb_dev.poll(&mut [&mut hid]));
+
+        let data = manager.host_read_in();
+        assert_eq!(data, [0x02, 0xAB, 0xCD], "Unexpected feature report");
+    }
+
+    #[test]
+    fn set_feature_report() {
+        init_logging();
+
+        let manager = UsbTestManager::default();
+
+        let usb_alloc = UsbBusAllocator::new(TestUsbBus::new(&manager));
+
+        let mut hid = UsbHidClassBuilder::new()
+            .add_device(FeatureTestDeviceConfig {
+                interface: InterfaceBuilder::new(&[]).unwrap().build(),
+            })
+            .build(&usb_alloc);
+
+        let mut usb_dev = UsbDeviceBuilder::new(&usb_alloc, UsbVidPid(0x1209, 0x0001))
+            .device_class(USB_CLASS_HID)
+            .build();
+
+        // Set feature report
+        manager
+            .host_write_setup(
+                &UsbRequest {
+                    direction: UsbDirection::In != UsbDirection::In,
+                    request_type: RequestType::Class as u8,
+                    recipient: Recipient::Interface as u8,
+                    request: HidRequest::SetReport.into(),
+                    value: (u16::from(u8::from(ReportType::Feature)) << 8) | 0x02,
+                    index: 0x0,
+                    length: 0x3,
+                }
+                .pack()
+                .unwrap(),
+            )
+            .unwrap();
+
+        usb_dev.poll(&mut [&mut hid]);
+
+        manager.host_write_out(&[0x02, 0x12, 0x34]).unwrap();
+
+        assert!(usb_dev.poll(&mut [&mut hid]));
+
+        let device = hid.device::<FeatureTestDevice<'_, _>, _>();
+        assert_eq!(device.feature, [0x02, 0x12, 0x34], "Feature report not set");
+    }
 }